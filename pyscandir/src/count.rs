@@ -1,6 +1,4 @@
-use std::io::ErrorKind;
-
-use pyo3::exceptions::{PyException, PyFileNotFoundError, PyRuntimeError, PyValueError};
+use pyo3::exceptions::{PyException, PyRuntimeError, PyValueError};
 use pyo3::prelude::*;
 use pyo3::types::PyType;
 
@@ -10,7 +8,10 @@ use pyo3::types::PyBytes;
 #[cfg(feature = "speedy")]
 use speedy::Writable;
 
-use crate::def::{ReturnType, Statistics};
+use crate::def::error::io_error_to_pyerr;
+use crate::def::{
+    DirUsage, ReturnType, RootPaths, Statistics, TimeBound, TopN, parse_entry_types, time_range,
+};
 
 #[pyclass]
 #[derive(Debug)]
@@ -23,9 +24,9 @@ pub struct Count {
 impl Count {
     #[allow(clippy::too_many_arguments)]
     #[new]
-    #[pyo3(signature = (root_path, skip_hidden=None, max_depth=None, max_file_cnt=None, dir_include=None, dir_exclude=None, file_include=None, file_exclude=None, case_sensitive=None, follow_links=None, return_type=None))]
+    #[pyo3(signature = (root_path, skip_hidden=None, max_depth=None, max_file_cnt=None, dir_include=None, dir_exclude=None, file_include=None, file_exclude=None, case_sensitive=None, follow_links=None, return_type=None, du=None, top_n=None, extension_stats=None, extension_case_sensitive=None, multi_part_extensions=None, owner_stats=None, resolve_names=None, histograms=None, age_buckets=None, min_size=None, max_size=None, mtime_newer=None, mtime_older=None, ctime_newer=None, ctime_older=None, atime_newer=None, atime_older=None, entry_types=None, perm=None, uid=None, gid=None, nouser=None, nogroup=None, min_links=None, max_links=None, dir_include_regex=None, dir_exclude_regex=None, file_include_regex=None, file_exclude_regex=None, gitignore=None, filter_expr=None, file_match_path=None, channel_capacity=None))]
     fn new(
-        root_path: &str,
+        root_path: RootPaths,
         skip_hidden: Option<bool>,
         max_depth: Option<usize>,
         max_file_cnt: Option<usize>,
@@ -36,9 +37,46 @@ impl Count {
         case_sensitive: Option<bool>,
         follow_links: Option<bool>,
         return_type: Option<ReturnType>,
+        du: Option<bool>,
+        top_n: Option<usize>,
+        extension_stats: Option<bool>,
+        extension_case_sensitive: Option<bool>,
+        multi_part_extensions: Option<Vec<String>>,
+        owner_stats: Option<bool>,
+        resolve_names: Option<bool>,
+        histograms: Option<bool>,
+        age_buckets: Option<Vec<u64>>,
+        min_size: Option<u64>,
+        max_size: Option<u64>,
+        mtime_newer: Option<TimeBound>,
+        mtime_older: Option<TimeBound>,
+        ctime_newer: Option<TimeBound>,
+        ctime_older: Option<TimeBound>,
+        atime_newer: Option<TimeBound>,
+        atime_older: Option<TimeBound>,
+        entry_types: Option<Vec<String>>,
+        perm: Option<&str>,
+        uid: Option<u32>,
+        gid: Option<u32>,
+        nouser: Option<bool>,
+        nogroup: Option<bool>,
+        min_links: Option<u64>,
+        max_links: Option<u64>,
+        dir_include_regex: Option<Vec<String>>,
+        dir_exclude_regex: Option<Vec<String>>,
+        file_include_regex: Option<Vec<String>>,
+        file_exclude_regex: Option<Vec<String>>,
+        gitignore: Option<bool>,
+        filter_expr: Option<String>,
+        file_match_path: Option<bool>,
+        channel_capacity: Option<usize>,
     ) -> PyResult<Self> {
+        let instance = match root_path {
+            RootPaths::Single(root_path) => scandir::Count::new(root_path),
+            RootPaths::Multi(root_paths) => scandir::Count::new_multi(&root_paths),
+        };
         Ok(Count {
-            instance: match scandir::Count::new(root_path) {
+            instance: match instance {
                 Ok(c) => c
                     .skip_hidden(skip_hidden.unwrap_or(false))
                     .max_depth(max_depth.unwrap_or(0))
@@ -49,18 +87,45 @@ impl Count {
                     .file_exclude(file_exclude)
                     .case_sensitive(case_sensitive.unwrap_or(false))
                     .follow_links(follow_links.unwrap_or(false))
-                    .extended(return_type.unwrap_or(ReturnType::Base) == ReturnType::Ext),
-                Err(e) => match e.kind() {
-                    ErrorKind::InvalidInput => {
-                        return Err(PyValueError::new_err(e.to_string()));
-                    }
-                    ErrorKind::NotFound => {
-                        return Err(PyFileNotFoundError::new_err(e.to_string()));
-                    }
-                    _ => {
-                        return Err(PyException::new_err(e.to_string()));
-                    }
-                },
+                    .extended(return_type.unwrap_or(ReturnType::Base) == ReturnType::Ext)
+                    .du(du.unwrap_or(false))
+                    .top_n(top_n.unwrap_or(0))
+                    .extension_stats(extension_stats.unwrap_or(false))
+                    .extension_case_sensitive(extension_case_sensitive.unwrap_or(false))
+                    .multi_part_extensions(
+                        multi_part_extensions
+                            .unwrap_or_else(scandir::default_multi_part_extensions),
+                    )
+                    .owner_stats(owner_stats.unwrap_or(false))
+                    .resolve_names(resolve_names.unwrap_or(false))
+                    .histograms(histograms.unwrap_or(false))
+                    .age_buckets(age_buckets.unwrap_or_else(scandir::default_age_buckets))
+                    .min_size(min_size)
+                    .max_size(max_size)
+                    .mtime(time_range(mtime_newer, mtime_older))
+                    .ctime(time_range(ctime_newer, ctime_older))
+                    .atime(time_range(atime_newer, atime_older))
+                    .entry_types(parse_entry_types(entry_types)?)
+                    .perm(
+                        perm.map(|perm| perm.parse())
+                            .transpose()
+                            .map_err(io_error_to_pyerr)?,
+                    )
+                    .uid(uid)
+                    .gid(gid)
+                    .nouser(nouser.unwrap_or(false))
+                    .nogroup(nogroup.unwrap_or(false))
+                    .min_links(min_links)
+                    .max_links(max_links)
+                    .dir_include_regex(dir_include_regex)
+                    .dir_exclude_regex(dir_exclude_regex)
+                    .file_include_regex(file_include_regex)
+                    .file_exclude_regex(file_exclude_regex)
+                    .gitignore(gitignore.unwrap_or(false))
+                    .filter_expr(filter_expr)
+                    .file_match_path(file_match_path.unwrap_or(false))
+                    .channel_capacity(channel_capacity.unwrap_or(0)),
+                Err(e) => return Err(io_error_to_pyerr(e)),
             },
             busy: false,
         })
@@ -75,9 +140,7 @@ impl Count {
     }
 
     pub fn start(&mut self) -> PyResult<()> {
-        self.instance
-            .start()
-            .map_err(|e| PyException::new_err(e.to_string()))
+        self.instance.start().map_err(io_error_to_pyerr)
     }
 
     pub fn join(&mut self, py: Python) -> PyResult<bool> {
@@ -96,7 +159,9 @@ impl Count {
     }
 
     pub fn collect(&mut self, py: Python) -> PyResult<Py<PyAny>> {
-        let results = py.detach(|| self.instance.collect())?;
+        let results = py
+            .detach(|| self.instance.collect())
+            .map_err(io_error_to_pyerr)?;
         Ok(Py::new(py, Statistics::from(&results)).unwrap().into_any())
     }
 
@@ -114,11 +179,29 @@ impl Count {
         self.instance.has_errors()
     }
 
+    #[pyo3(signature = (only_new=None))]
+    pub fn dir_usages(&mut self, only_new: Option<bool>) -> Vec<DirUsage> {
+        self.instance
+            .dir_usages(only_new.unwrap_or(true))
+            .iter()
+            .map(DirUsage::from)
+            .collect()
+    }
+
+    pub fn largest(&mut self) -> Option<TopN> {
+        self.instance.largest().map(TopN)
+    }
+
     #[getter]
     pub fn duration(&mut self) -> f64 {
         self.instance.duration()
     }
 
+    #[getter]
+    pub fn peak_queue_len(&self) -> usize {
+        self.instance.peak_queue_len()
+    }
+
     #[getter]
     pub fn finished(&mut self) -> bool {
         self.instance.finished()
@@ -167,9 +250,7 @@ impl Count {
     }
 
     fn __enter__(mut slf: PyRefMut<Self>) -> PyResult<PyRefMut<Self>> {
-        slf.instance
-            .start()
-            .map_err(|e| PyException::new_err(e.to_string()))?;
+        slf.instance.start().map_err(io_error_to_pyerr)?;
         Ok(slf)
     }
 
@@ -194,7 +275,7 @@ impl Count {
         if slf.busy {
             return Err(PyRuntimeError::new_err("Busy"));
         }
-        slf.instance.start()?;
+        slf.instance.start().map_err(io_error_to_pyerr)?;
         slf.busy = true;
         Ok(slf)
     }
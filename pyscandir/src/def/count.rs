@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 #[cfg(any(feature = "speedy", feature = "bincode", feature = "json"))]
 use pyo3::exceptions::PyException;
 use pyo3::prelude::*;
@@ -8,6 +10,40 @@ use pyo3::types::PyDict;
 #[cfg(feature = "speedy")]
 use speedy::Writable;
 
+use super::error::scan_errors_to_py;
+
+fn extensions_to_dict(statistics: &scandir::Statistics, py: Python) -> PyResult<Py<PyAny>> {
+    let pydict = PyDict::new(py);
+    for (extension, extension_stats) in statistics.extensions.iter() {
+        let pystats = PyDict::new(py);
+        pystats.set_item("count", extension_stats.count)?;
+        pystats.set_item("size", extension_stats.size)?;
+        pystats.set_item("usage", extension_stats.usage)?;
+        pydict.set_item(extension, pystats)?;
+    }
+    Ok(pydict.into_any().unbind())
+}
+
+fn owners_to_dict(owners: &BTreeMap<u32, scandir::OwnerStats>, py: Python) -> PyResult<Py<PyAny>> {
+    let pydict = PyDict::new(py);
+    for (id, owner_stats) in owners.iter() {
+        let pystats = PyDict::new(py);
+        pystats.set_item("name", owner_stats.name.clone())?;
+        pystats.set_item("files", owner_stats.files)?;
+        pystats.set_item("size", owner_stats.size)?;
+        pystats.set_item("usage", owner_stats.usage)?;
+        pydict.set_item(id, pystats)?;
+    }
+    Ok(pydict.into_any().unbind())
+}
+
+fn histogram_to_dict(histogram: &scandir::Histogram, py: Python) -> PyResult<Py<PyAny>> {
+    let pydict = PyDict::new(py);
+    pydict.set_item("bounds", histogram.bounds.clone())?;
+    pydict.set_item("counts", histogram.counts.clone())?;
+    Ok(pydict.into_any().unbind())
+}
+
 #[pyclass(from_py_object)]
 #[derive(Debug, Clone)]
 pub struct Statistics(pub scandir::Statistics);
@@ -61,8 +97,52 @@ impl Statistics {
     }
 
     #[getter]
-    fn errors(&self) -> Vec<String> {
-        self.0.errors.clone()
+    fn errors(&self, py: Python) -> PyResult<Vec<Py<PyAny>>> {
+        scan_errors_to_py(py, &self.0.errors)
+    }
+
+    #[getter]
+    fn symlink_loops(&self) -> Vec<(String, String)> {
+        self.0
+            .symlink_loops
+            .iter()
+            .map(|e| (e.path.clone(), e.target.clone()))
+            .collect()
+    }
+
+    #[getter]
+    fn extensions(&self, py: Python) -> PyResult<Py<PyAny>> {
+        extensions_to_dict(&self.0, py)
+    }
+
+    #[getter]
+    fn users(&self, py: Python) -> PyResult<Py<PyAny>> {
+        owners_to_dict(&self.0.users, py)
+    }
+
+    #[getter]
+    fn groups(&self, py: Python) -> PyResult<Py<PyAny>> {
+        owners_to_dict(&self.0.groups, py)
+    }
+
+    #[getter]
+    fn size_histogram(&self) -> Vec<u64> {
+        self.0.size_histogram.clone()
+    }
+
+    #[getter]
+    fn mtime_histogram(&self, py: Python) -> PyResult<Py<PyAny>> {
+        histogram_to_dict(&self.0.mtime_histogram, py)
+    }
+
+    #[getter]
+    fn atime_histogram(&self, py: Python) -> PyResult<Py<PyAny>> {
+        histogram_to_dict(&self.0.atime_histogram, py)
+    }
+
+    #[getter]
+    fn depth_histogram(&self) -> Vec<u64> {
+        self.0.depth_histogram.clone()
     }
 
     #[getter]
@@ -98,7 +178,34 @@ impl Statistics {
             pyresult.set_item("usage", self.0.usage).unwrap();
         }
         if !self.0.errors.is_empty() {
-            pyresult.set_item("errors", self.0.errors.to_vec()).unwrap();
+            let errors: Vec<String> = self.0.errors.iter().map(|e| e.to_string()).collect();
+            pyresult.set_item("errors", errors).unwrap();
+        }
+        if !self.0.symlink_loops.is_empty() {
+            pyresult
+                .set_item("symlink_loops", self.symlink_loops())
+                .unwrap();
+        }
+        if !self.0.extensions.is_empty() {
+            pyresult.set_item("extensions", self.extensions(py)?)?;
+        }
+        if !self.0.users.is_empty() {
+            pyresult.set_item("users", self.users(py)?)?;
+        }
+        if !self.0.groups.is_empty() {
+            pyresult.set_item("groups", self.groups(py)?)?;
+        }
+        if !self.0.size_histogram.is_empty() {
+            pyresult.set_item("size_histogram", self.size_histogram())?;
+        }
+        if !self.0.mtime_histogram.is_empty() {
+            pyresult.set_item("mtime_histogram", self.mtime_histogram(py)?)?;
+        }
+        if !self.0.atime_histogram.is_empty() {
+            pyresult.set_item("atime_histogram", self.atime_histogram(py)?)?;
+        }
+        if !self.0.depth_histogram.is_empty() {
+            pyresult.set_item("depth_histogram", self.depth_histogram())?;
         }
         if duration.unwrap_or(false) {
             pyresult.set_item("duration", self.0.duration).unwrap();
@@ -0,0 +1,72 @@
+use std::ffi::OsString;
+
+#[cfg(feature = "json")]
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+fn totals_to_dict(totals: &scandir::UsageTotals, py: Python) -> PyResult<Py<PyAny>> {
+    let pydict = PyDict::new(py);
+    pydict.set_item("files", totals.files)?;
+    pydict.set_item("dirs", totals.dirs)?;
+    pydict.set_item("size", totals.size)?;
+    pydict.set_item("usage", totals.usage)?;
+    Ok(pydict.into_any().unbind())
+}
+
+#[pyclass(from_py_object)]
+#[derive(Debug, Clone)]
+pub struct DirUsage(pub scandir::DirUsage);
+
+impl DirUsage {
+    pub fn from(entry: &scandir::DirUsage) -> Self {
+        DirUsage(entry.clone())
+    }
+}
+
+#[pymethods]
+impl DirUsage {
+    #[getter]
+    fn path(&self) -> OsString {
+        self.0.os_path()
+    }
+
+    #[getter]
+    fn depth(&self) -> usize {
+        self.0.depth
+    }
+
+    #[getter]
+    fn direct(&self, py: Python) -> PyResult<Py<PyAny>> {
+        totals_to_dict(&self.0.direct, py)
+    }
+
+    #[getter]
+    fn recursive(&self, py: Python) -> PyResult<Py<PyAny>> {
+        totals_to_dict(&self.0.recursive, py)
+    }
+
+    fn as_dict(&self, py: Python) -> PyResult<Py<PyAny>> {
+        let pydict = PyDict::new(py);
+        pydict.set_item("path", self.0.os_path())?;
+        pydict.set_item("depth", self.0.depth)?;
+        pydict.set_item("direct", self.direct(py)?)?;
+        pydict.set_item("recursive", self.recursive(py)?)?;
+        Ok(pydict.into_any().unbind())
+    }
+
+    #[cfg(feature = "json")]
+    fn to_json(&self) -> PyResult<String> {
+        self.0
+            .to_json()
+            .map_err(|e| PyException::new_err(e.to_string()))
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+
+    fn __str__(&self) -> String {
+        format!("{self:?}")
+    }
+}
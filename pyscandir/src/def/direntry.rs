@@ -1,3 +1,4 @@
+use std::ffi::OsString;
 use std::time::SystemTime;
 
 #[cfg(any(feature = "speedy", feature = "bincode", feature = "json"))]
@@ -23,8 +24,8 @@ impl DirEntry {
 #[pymethods]
 impl DirEntry {
     #[getter]
-    fn path(&self) -> String {
-        self.0.path.clone()
+    fn path(&self) -> OsString {
+        self.0.os_path()
     }
 
     #[getter]
@@ -62,6 +63,11 @@ impl DirEntry {
         self.0.st_size
     }
 
+    #[getter]
+    fn digest(&self) -> Option<String> {
+        self.0.digest.clone()
+    }
+
     #[getter]
     fn ctime(&self) -> f64 {
         self.0.ctime()
@@ -79,7 +85,7 @@ impl DirEntry {
 
     fn as_dict(&self, py: Python) -> PyResult<Py<PyAny>> {
         let pydict = PyDict::new(py);
-        pydict.set_item("path", self.0.path.clone())?;
+        pydict.set_item("path", self.0.os_path())?;
         pydict.set_item("is_symlink", self.0.is_symlink)?;
         pydict.set_item("is_dir", self.0.is_dir)?;
         pydict.set_item("is_file", self.0.is_file)?;
@@ -87,6 +93,9 @@ impl DirEntry {
         pydict.set_item("st_mtime", self.0.st_mtime)?;
         pydict.set_item("st_atime", self.0.st_atime)?;
         pydict.set_item("st_size", self.0.st_size)?;
+        if let Some(ref digest) = self.0.digest {
+            pydict.set_item("digest", digest)?;
+        }
         Ok(pydict.into_any().unbind())
     }
 
@@ -143,8 +152,8 @@ impl DirEntryExt {
 #[pymethods]
 impl DirEntryExt {
     #[getter]
-    fn path(&self) -> String {
-        self.0.path.clone()
+    fn path(&self) -> OsString {
+        self.0.os_path()
     }
 
     #[getter]
@@ -227,6 +236,11 @@ impl DirEntryExt {
         self.0.st_rdev
     }
 
+    #[getter]
+    fn digest(&self) -> Option<String> {
+        self.0.digest.clone()
+    }
+
     #[getter]
     fn ctime(&self) -> f64 {
         self.0.ctime()
@@ -244,7 +258,7 @@ impl DirEntryExt {
 
     fn as_dict(&self, py: Python) -> PyResult<Py<PyAny>> {
         let pydict = PyDict::new(py);
-        pydict.set_item("path", self.0.path.clone())?;
+        pydict.set_item("path", self.0.os_path())?;
         pydict.set_item("is_symlink", self.0.is_symlink)?;
         pydict.set_item("is_dir", self.0.is_dir)?;
         pydict.set_item("is_file", self.0.is_file)?;
@@ -261,6 +275,9 @@ impl DirEntryExt {
         pydict.set_item("st_ino", self.0.st_ino)?;
         pydict.set_item("st_dev", self.0.st_dev)?;
         pydict.set_item("st_rdev", self.0.st_rdev)?;
+        if let Some(ref digest) = self.0.digest {
+            pydict.set_item("digest", digest)?;
+        }
         Ok(pydict.into_any().unbind())
     }
 
@@ -0,0 +1,69 @@
+#[cfg(feature = "json")]
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use super::error::scan_errors_to_py;
+
+#[pyclass(from_py_object)]
+#[derive(Debug, Clone)]
+pub struct Duplicates(pub scandir::Duplicates);
+
+#[pymethods]
+impl Duplicates {
+    /// List of dicts with the keys `size`, `digest` and `paths`.
+    #[getter]
+    fn sets(&self, py: Python) -> PyResult<Vec<Py<PyAny>>> {
+        self.0
+            .sets
+            .iter()
+            .map(|set| {
+                let pydict = PyDict::new(py);
+                pydict.set_item("size", set.size)?;
+                pydict.set_item("digest", &set.digest)?;
+                pydict.set_item("paths", set.os_paths())?;
+                Ok(pydict.into_any().unbind())
+            })
+            .collect()
+    }
+
+    #[getter]
+    fn reclaimable(&self) -> u64 {
+        self.0.reclaimable
+    }
+
+    #[getter]
+    fn errors(&self, py: Python) -> PyResult<Vec<Py<PyAny>>> {
+        scan_errors_to_py(py, &self.0.errors)
+    }
+
+    fn as_dict(&self, py: Python) -> PyResult<Py<PyAny>> {
+        let pydict = PyDict::new(py);
+        pydict.set_item("sets", self.sets(py)?)?;
+        pydict.set_item("reclaimable", self.0.reclaimable)?;
+        pydict.set_item(
+            "errors",
+            self.0
+                .errors
+                .iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>(),
+        )?;
+        Ok(pydict.into_any().unbind())
+    }
+
+    #[cfg(feature = "json")]
+    fn to_json(&self) -> PyResult<String> {
+        self.0
+            .to_json()
+            .map_err(|e| PyException::new_err(e.to_string()))
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+
+    fn __str__(&self) -> String {
+        format!("{self:?}")
+    }
+}
@@ -0,0 +1,58 @@
+use std::io;
+
+use pyo3::exceptions::{
+    PyException, PyFileNotFoundError, PyInterruptedError, PyIsADirectoryError,
+    PyNotADirectoryError, PyOSError, PyPermissionError, PyValueError,
+};
+use pyo3::prelude::*;
+use scandir::{ScanError, ScanErrorKind};
+
+/// Maps a `ScanError` to the matching Python exception type.
+///
+/// OS errors get the usual `errno`, `strerror` and `filename` attributes.
+fn scan_error_to_pyerr(error: &ScanError) -> PyErr {
+    if error.kind == ScanErrorKind::InvalidInput {
+        return PyValueError::new_err(error.to_string());
+    }
+    let Some(errno) = error.raw_os_error else {
+        return match error.kind {
+            ScanErrorKind::NotFound => PyFileNotFoundError::new_err(error.to_string()),
+            _ => PyOSError::new_err(error.to_string()),
+        };
+    };
+    let args = (errno, error.message.clone(), error.path.clone());
+    match error.kind {
+        ScanErrorKind::NotFound => PyFileNotFoundError::new_err(args),
+        ScanErrorKind::PermissionDenied => PyPermissionError::new_err(args),
+        ScanErrorKind::NotADirectory => PyNotADirectoryError::new_err(args),
+        ScanErrorKind::IsADirectory => PyIsADirectoryError::new_err(args),
+        ScanErrorKind::Interrupted => PyInterruptedError::new_err(args),
+        _ => PyOSError::new_err(args),
+    }
+}
+
+/// Converts a `ScanError` to a Python exception instance with an additional `phase` attribute.
+pub fn scan_error_to_py(py: Python, error: &ScanError) -> PyResult<Py<PyAny>> {
+    let value = scan_error_to_pyerr(error).into_value(py).into_any();
+    value.setattr(py, "phase", error.phase.to_string())?;
+    Ok(value)
+}
+
+pub fn scan_errors_to_py(py: Python, errors: &[ScanError]) -> PyResult<Vec<Py<PyAny>>> {
+    errors.iter().map(|e| scan_error_to_py(py, e)).collect()
+}
+
+/// Maps errors returned by the scandir crate to Python exceptions.
+pub fn io_error_to_pyerr(error: io::Error) -> PyErr {
+    if let Some(scan_error) = error.get_ref().and_then(|e| e.downcast_ref::<ScanError>()) {
+        return Python::attach(|py| match scan_error_to_py(py, scan_error) {
+            Ok(value) => PyErr::from_value(value.into_bound(py)),
+            Err(e) => e,
+        });
+    }
+    match error.kind() {
+        io::ErrorKind::InvalidInput => PyValueError::new_err(error.to_string()),
+        io::ErrorKind::NotFound => PyFileNotFoundError::new_err(error.to_string()),
+        _ => PyException::new_err(error.to_string()),
+    }
+}
@@ -1,13 +1,31 @@
+use std::str::FromStr;
+
 use pyo3::prelude::*;
 
 pub mod count;
 pub use count::Statistics;
+pub mod dir_usage;
+pub use dir_usage::DirUsage;
 pub mod direntry;
+pub mod duplicates;
+pub use duplicates::Duplicates;
+pub mod error;
 pub mod walk;
 pub use direntry::{DirEntry, DirEntryExt};
 pub mod scandir;
+pub mod time_range;
+pub use time_range::{TimeBound, time_range};
 pub mod toc;
 pub use toc::Toc;
+pub mod top_n;
+pub use top_n::TopN;
+
+/// Root path or list of root paths of a scan.
+#[derive(Debug, FromPyObject)]
+pub enum RootPaths {
+    Single(String),
+    Multi(Vec<String>),
+}
 
 #[pyclass(eq, eq_int, from_py_object)]
 #[derive(Debug, Clone, PartialEq)]
@@ -25,3 +43,18 @@ impl ReturnType {
         }
     }
 }
+
+/// Parses the names of entry types, e.g. `file`, `dir` or `symlink`.
+pub fn parse_entry_types(
+    entry_types: Option<Vec<String>>,
+) -> PyResult<Option<Vec<::scandir::EntryType>>> {
+    entry_types
+        .map(|entry_types| {
+            entry_types
+                .iter()
+                .map(|entry_type| ::scandir::EntryType::from_str(entry_type))
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .transpose()
+        .map_err(error::io_error_to_pyerr)
+}
@@ -1,3 +1,5 @@
+use std::ffi::OsString;
+
 #[cfg(any(feature = "speedy", feature = "bincode", feature = "json"))]
 use pyo3::exceptions::PyException;
 use pyo3::prelude::*;
@@ -5,6 +7,7 @@ use pyo3::prelude::*;
 use pyo3::types::PyBytes;
 
 use super::DirEntryExt;
+use super::error::scan_error_to_py;
 
 #[pyclass(from_py_object)]
 #[derive(Debug, Clone)]
@@ -13,13 +16,13 @@ pub struct ScandirResult(scandir::ScandirResult);
 #[pymethods]
 impl ScandirResult {
     #[getter]
-    fn path(&self) -> String {
-        self.0.path().clone()
+    fn path(&self) -> OsString {
+        self.0.os_path()
     }
 
     #[getter]
-    fn error(&self) -> Option<(String, String)> {
-        self.0.error().cloned()
+    fn error(&self, py: Python) -> PyResult<Option<Py<PyAny>>> {
+        self.0.error().map(|e| scan_error_to_py(py, e)).transpose()
     }
 
     #[getter]
@@ -0,0 +1,34 @@
+use std::time::{Duration, SystemTime};
+
+use pyo3::prelude::*;
+
+/// Bound of a time filter. A `datetime` is an absolute point in time and a `timedelta`
+/// an age relative to the start of the scan.
+#[derive(Debug, Clone, Copy, FromPyObject)]
+pub enum TimeBound {
+    At(SystemTime),
+    Age(Duration),
+}
+
+impl From<TimeBound> for scandir::TimeBound {
+    fn from(bound: TimeBound) -> Self {
+        match bound {
+            TimeBound::At(time) => scandir::TimeBound::At(time),
+            TimeBound::Age(age) => scandir::TimeBound::Age(age),
+        }
+    }
+}
+
+/// Returns the time range of the bounds or `None` if both are `None`.
+pub fn time_range(
+    newer: Option<TimeBound>,
+    older: Option<TimeBound>,
+) -> Option<scandir::TimeRange> {
+    if newer.is_none() && older.is_none() {
+        return None;
+    }
+    Some(scandir::TimeRange::new(
+        newer.map(scandir::TimeBound::from),
+        older.map(scandir::TimeBound::from),
+    ))
+}
@@ -1,3 +1,5 @@
+use std::ffi::OsString;
+
 #[cfg(any(feature = "speedy", feature = "bincode", feature = "json"))]
 use pyo3::exceptions::PyException;
 use pyo3::prelude::*;
@@ -5,6 +7,8 @@ use pyo3::prelude::*;
 use pyo3::types::PyBytes;
 use pyo3::types::PyDict;
 
+use super::error::scan_errors_to_py;
+
 #[pyclass(from_py_object)]
 #[derive(Debug, Clone)]
 pub struct Toc(scandir::Toc);
@@ -18,37 +22,48 @@ impl Toc {
 #[pymethods]
 impl Toc {
     #[getter]
-    fn dirs(&self) -> Vec<String> {
-        self.0.dirs()
+    fn dirs(&self) -> Vec<OsString> {
+        self.0.os_dirs()
+    }
+
+    #[getter]
+    fn files(&self) -> Vec<OsString> {
+        self.0.os_files()
     }
 
     #[getter]
-    fn files(&self) -> Vec<String> {
-        self.0.files()
+    fn symlinks(&self) -> Vec<OsString> {
+        self.0.os_symlinks()
     }
 
     #[getter]
-    fn symlinks(&self) -> Vec<String> {
-        self.0.symlinks()
+    fn other(&self) -> Vec<OsString> {
+        self.0.os_other()
     }
 
     #[getter]
-    fn other(&self) -> Vec<String> {
-        self.0.other()
+    fn errors(&self, py: Python) -> PyResult<Vec<Py<PyAny>>> {
+        scan_errors_to_py(py, &self.0.errors)
     }
 
     #[getter]
-    fn errors(&self) -> Vec<String> {
-        self.0.errors()
+    fn symlink_loops(&self) -> Vec<(String, String)> {
+        self.0
+            .symlink_loops
+            .iter()
+            .map(|e| (e.path.clone(), e.target.clone()))
+            .collect()
     }
 
     fn as_dict(&self, py: Python) -> PyResult<Py<PyAny>> {
         let pydict = PyDict::new(py);
-        pydict.set_item("dirs", self.0.dirs.clone())?;
-        pydict.set_item("files", self.0.files.clone())?;
-        pydict.set_item("symlinks", self.0.symlinks.clone())?;
-        pydict.set_item("other", self.0.other.clone())?;
-        pydict.set_item("errors", self.0.errors.clone())?;
+        pydict.set_item("dirs", self.0.os_dirs())?;
+        pydict.set_item("files", self.0.os_files())?;
+        pydict.set_item("symlinks", self.0.os_symlinks())?;
+        pydict.set_item("other", self.0.os_other())?;
+        let errors: Vec<String> = self.0.errors.iter().map(|e| e.to_string()).collect();
+        pydict.set_item("errors", errors)?;
+        pydict.set_item("symlink_loops", self.symlink_loops())?;
         Ok(pydict.into_any().unbind())
     }
 
@@ -0,0 +1,68 @@
+#[cfg(feature = "json")]
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+fn entries_to_list(entries: &[scandir::TopEntry], py: Python) -> PyResult<Vec<Py<PyAny>>> {
+    entries
+        .iter()
+        .map(|entry| {
+            let pydict = PyDict::new(py);
+            pydict.set_item("path", entry.os_path())?;
+            pydict.set_item("size", entry.size)?;
+            pydict.set_item("usage", entry.usage)?;
+            Ok(pydict.into_any().unbind())
+        })
+        .collect()
+}
+
+#[pyclass(from_py_object)]
+#[derive(Debug, Clone)]
+pub struct TopN(pub scandir::TopN);
+
+#[pymethods]
+impl TopN {
+    #[getter]
+    fn files_by_size(&self, py: Python) -> PyResult<Vec<Py<PyAny>>> {
+        entries_to_list(&self.0.files_by_size, py)
+    }
+
+    #[getter]
+    fn files_by_usage(&self, py: Python) -> PyResult<Vec<Py<PyAny>>> {
+        entries_to_list(&self.0.files_by_usage, py)
+    }
+
+    #[getter]
+    fn dirs_by_size(&self, py: Python) -> PyResult<Vec<Py<PyAny>>> {
+        entries_to_list(&self.0.dirs_by_size, py)
+    }
+
+    #[getter]
+    fn dirs_by_usage(&self, py: Python) -> PyResult<Vec<Py<PyAny>>> {
+        entries_to_list(&self.0.dirs_by_usage, py)
+    }
+
+    fn as_dict(&self, py: Python) -> PyResult<Py<PyAny>> {
+        let pydict = PyDict::new(py);
+        pydict.set_item("files_by_size", self.files_by_size(py)?)?;
+        pydict.set_item("files_by_usage", self.files_by_usage(py)?)?;
+        pydict.set_item("dirs_by_size", self.dirs_by_size(py)?)?;
+        pydict.set_item("dirs_by_usage", self.dirs_by_usage(py)?)?;
+        Ok(pydict.into_any().unbind())
+    }
+
+    #[cfg(feature = "json")]
+    fn to_json(&self) -> PyResult<String> {
+        self.0
+            .to_json()
+            .map_err(|e| PyException::new_err(e.to_string()))
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+
+    fn __str__(&self) -> String {
+        format!("{self:?}")
+    }
+}
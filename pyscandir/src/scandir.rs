@@ -1,14 +1,18 @@
-use std::io::ErrorKind;
+use std::str::FromStr;
 use std::thread;
 use std::time::Duration;
 
-use pyo3::exceptions::{PyException, PyFileNotFoundError, PyRuntimeError, PyValueError};
+use pyo3::exceptions::{PyException, PyRuntimeError, PyValueError};
 use pyo3::types::{PyBytes, PyDict, PyType};
 use pyo3::{IntoPyObjectExt, prelude::*};
 use scandir::def::scandir::ScandirResults;
 
-use crate::def::{DirEntry, DirEntryExt, ReturnType, Statistics};
-use scandir::{ErrorsType, ScandirResult};
+use crate::def::error::{io_error_to_pyerr, scan_error_to_py, scan_errors_to_py};
+use crate::def::{
+    DirEntry, DirEntryExt, Duplicates, ReturnType, RootPaths, Statistics, TimeBound, TopN,
+    parse_entry_types, time_range,
+};
+use scandir::ScandirResult;
 
 fn result2py(result: &ScandirResult, py: Python) -> Option<Py<PyAny>> {
     match result {
@@ -16,7 +20,8 @@ fn result2py(result: &ScandirResult, py: Python) -> Option<Py<PyAny>> {
         ScandirResult::DirEntryExt(e) => {
             Some(Py::new(py, DirEntryExt::from(e)).unwrap().into_any())
         }
-        ScandirResult::Error((_path, _e)) => None,
+        ScandirResult::Error(_) => None,
+        ScandirResult::SymlinkLoop(_) => None,
     }
 }
 
@@ -31,9 +36,9 @@ pub struct Scandir {
 impl Scandir {
     #[allow(clippy::too_many_arguments)]
     #[new]
-    #[pyo3(signature = (root_path, sorted=None, skip_hidden=None, max_depth=None, max_file_cnt=None, dir_include=None, dir_exclude=None, file_include=None, file_exclude=None, case_sensitive=None, follow_links=None, return_type=None, store=None))]
+    #[pyo3(signature = (root_path, sorted=None, skip_hidden=None, max_depth=None, max_file_cnt=None, dir_include=None, dir_exclude=None, file_include=None, file_exclude=None, case_sensitive=None, follow_links=None, return_type=None, store=None, top_n=None, hash=None, hash_partial=None, min_size=None, max_size=None, mtime_newer=None, mtime_older=None, ctime_newer=None, ctime_older=None, atime_newer=None, atime_older=None, entry_types=None, perm=None, uid=None, gid=None, nouser=None, nogroup=None, min_links=None, max_links=None, dir_include_regex=None, dir_exclude_regex=None, file_include_regex=None, file_exclude_regex=None, gitignore=None, filter_expr=None, file_match_path=None, sort_by=None, channel_capacity=None))]
     pub fn new(
-        root_path: &str,
+        root_path: RootPaths,
         sorted: Option<bool>,
         skip_hidden: Option<bool>,
         max_depth: Option<usize>,
@@ -46,10 +51,50 @@ impl Scandir {
         follow_links: Option<bool>,
         return_type: Option<ReturnType>,
         store: Option<bool>,
+        top_n: Option<usize>,
+        hash: Option<&str>,
+        hash_partial: Option<u64>,
+        min_size: Option<u64>,
+        max_size: Option<u64>,
+        mtime_newer: Option<TimeBound>,
+        mtime_older: Option<TimeBound>,
+        ctime_newer: Option<TimeBound>,
+        ctime_older: Option<TimeBound>,
+        atime_newer: Option<TimeBound>,
+        atime_older: Option<TimeBound>,
+        entry_types: Option<Vec<String>>,
+        perm: Option<&str>,
+        uid: Option<u32>,
+        gid: Option<u32>,
+        nouser: Option<bool>,
+        nogroup: Option<bool>,
+        min_links: Option<u64>,
+        max_links: Option<u64>,
+        dir_include_regex: Option<Vec<String>>,
+        dir_exclude_regex: Option<Vec<String>>,
+        file_include_regex: Option<Vec<String>>,
+        file_exclude_regex: Option<Vec<String>>,
+        gitignore: Option<bool>,
+        filter_expr: Option<String>,
+        file_match_path: Option<bool>,
+        sort_by: Option<&str>,
+        channel_capacity: Option<usize>,
     ) -> PyResult<Self> {
         let return_type = return_type.unwrap_or(ReturnType::Base).from_object();
+        let hash = hash
+            .map(scandir::HashAlgorithm::from_str)
+            .transpose()
+            .map_err(io_error_to_pyerr)?;
+        let hash_mode = match hash_partial {
+            Some(n) => scandir::HashMode::Partial(n),
+            None => scandir::HashMode::Full,
+        };
+        let instance = match root_path {
+            RootPaths::Single(root_path) => scandir::Scandir::new(root_path, store),
+            RootPaths::Multi(root_paths) => scandir::Scandir::new_multi(&root_paths, store),
+        };
         Ok(Scandir {
-            instance: match scandir::Scandir::new(root_path, store) {
+            instance: match instance {
                 Ok(s) => s
                     .sorted(sorted.unwrap_or(false))
                     .skip_hidden(skip_hidden.unwrap_or(false))
@@ -61,15 +106,42 @@ impl Scandir {
                     .file_exclude(file_exclude)
                     .case_sensitive(case_sensitive.unwrap_or(false))
                     .follow_links(follow_links.unwrap_or(false))
-                    .return_type(return_type),
-                Err(e) => match e.kind() {
-                    ErrorKind::NotFound => {
-                        return Err(PyFileNotFoundError::new_err(e.to_string()));
-                    }
-                    _ => {
-                        return Err(PyException::new_err(e.to_string()));
-                    }
-                },
+                    .return_type(return_type)
+                    .top_n(top_n.unwrap_or(0))
+                    .hash(hash)
+                    .hash_mode(hash_mode)
+                    .min_size(min_size)
+                    .max_size(max_size)
+                    .mtime(time_range(mtime_newer, mtime_older))
+                    .ctime(time_range(ctime_newer, ctime_older))
+                    .atime(time_range(atime_newer, atime_older))
+                    .entry_types(parse_entry_types(entry_types)?)
+                    .perm(
+                        perm.map(|perm| perm.parse())
+                            .transpose()
+                            .map_err(io_error_to_pyerr)?,
+                    )
+                    .uid(uid)
+                    .gid(gid)
+                    .nouser(nouser.unwrap_or(false))
+                    .nogroup(nogroup.unwrap_or(false))
+                    .min_links(min_links)
+                    .max_links(max_links)
+                    .dir_include_regex(dir_include_regex)
+                    .dir_exclude_regex(dir_exclude_regex)
+                    .file_include_regex(file_include_regex)
+                    .file_exclude_regex(file_exclude_regex)
+                    .gitignore(gitignore.unwrap_or(false))
+                    .filter_expr(filter_expr)
+                    .file_match_path(file_match_path.unwrap_or(false))
+                    .sort_by(
+                        sort_by
+                            .map(|sort_by| sort_by.parse())
+                            .transpose()
+                            .map_err(io_error_to_pyerr)?,
+                    )
+                    .channel_capacity(channel_capacity.unwrap_or(0)),
+                Err(e) => return Err(io_error_to_pyerr(e)),
             },
             entries: ScandirResults::new(),
         })
@@ -85,9 +157,7 @@ impl Scandir {
     }
 
     pub fn start(&mut self) -> PyResult<()> {
-        self.instance
-            .start()
-            .map_err(|e| PyException::new_err(e.to_string()))
+        self.instance.start().map_err(io_error_to_pyerr)
     }
 
     pub fn join(&mut self, py: Python) -> PyResult<bool> {
@@ -105,14 +175,34 @@ impl Scandir {
         Ok(true)
     }
 
-    pub fn collect(&mut self, py: Python) -> PyResult<(Vec<Py<PyAny>>, ErrorsType)> {
-        let entries = py.detach(|| self.instance.collect())?;
+    #[allow(clippy::type_complexity)]
+    /// Collects the results. If `sort_by` is set, the results of all directories are sorted by
+    /// this key.
+    #[pyo3(signature = (sort_by=None))]
+    pub fn collect(
+        &mut self,
+        sort_by: Option<&str>,
+        py: Python,
+    ) -> PyResult<(Vec<Py<PyAny>>, Vec<Py<PyAny>>)> {
+        let sort_by = sort_by
+            .map(|sort_by| sort_by.parse::<scandir::SortBy>())
+            .transpose()
+            .map_err(io_error_to_pyerr)?;
+        let entries = py
+            .detach(|| {
+                let mut entries = self.instance.collect()?;
+                if let Some(sort_by) = sort_by {
+                    entries.sort(sort_by);
+                }
+                Ok(entries)
+            })
+            .map_err(io_error_to_pyerr)?;
         let results = entries
             .results
             .iter()
             .filter_map(|r| result2py(r, py))
             .collect();
-        Ok((results, entries.errors))
+        Ok((results, scan_errors_to_py(py, &entries.errors)?))
     }
 
     #[pyo3(signature = (only_new=None))]
@@ -126,14 +216,19 @@ impl Scandir {
     }
 
     #[pyo3(signature = (only_new=None))]
-    pub fn results(&mut self, only_new: Option<bool>, py: Python) -> (Vec<Py<PyAny>>, ErrorsType) {
+    #[allow(clippy::type_complexity)]
+    pub fn results(
+        &mut self,
+        only_new: Option<bool>,
+        py: Python,
+    ) -> PyResult<(Vec<Py<PyAny>>, Vec<Py<PyAny>>)> {
         let entries = self.instance.results(only_new.unwrap_or(true));
         let results = entries
             .results
             .iter()
             .filter_map(|e| result2py(e, py))
             .collect();
-        (results, entries.errors)
+        Ok((results, scan_errors_to_py(py, &entries.errors)?))
     }
 
     #[pyo3(signature = (only_new=None))]
@@ -164,8 +259,18 @@ impl Scandir {
     }
 
     #[pyo3(signature = (only_new=None))]
-    pub fn errors(&mut self, only_new: Option<bool>) -> ErrorsType {
-        self.instance.errors(only_new.unwrap_or(true))
+    pub fn errors(&mut self, only_new: Option<bool>, py: Python) -> PyResult<Vec<Py<PyAny>>> {
+        scan_errors_to_py(py, &self.instance.errors(only_new.unwrap_or(true)))
+    }
+
+    /// Returns the detected symlink loops as list of tuples with link path and target.
+    #[pyo3(signature = (only_new=None))]
+    pub fn symlink_loops(&mut self, only_new: Option<bool>) -> Vec<(String, String)> {
+        self.instance
+            .symlink_loops(only_new.unwrap_or(true))
+            .into_iter()
+            .map(|e| (e.path, e.target))
+            .collect()
     }
 
     #[pyo3(signature = (only_new=None))]
@@ -175,18 +280,21 @@ impl Scandir {
         for entry in entries.results {
             let _ = match entry {
                 ScandirResult::DirEntry(e) => pyresults.set_item(
-                    e.path.clone().into_py_any(py)?,
+                    e.os_path().into_py_any(py)?,
                     Py::new(py, DirEntry::from(&e)).unwrap().into_any(),
                 ),
                 ScandirResult::DirEntryExt(e) => pyresults.set_item(
-                    e.path.clone().into_py_any(py)?,
+                    e.os_path().into_py_any(py)?,
                     Py::new(py, DirEntryExt::from(&e)).unwrap().into_any(),
                 ),
-                ScandirResult::Error((path, e)) => pyresults.set_item(path.into_py_any(py)?, e),
+                ScandirResult::Error(e) => {
+                    pyresults.set_item(e.path.clone().into_py_any(py)?, scan_error_to_py(py, &e)?)
+                }
+                ScandirResult::SymlinkLoop(_) => Ok(()),
             };
         }
         for error in entries.errors {
-            let _ = pyresults.set_item(error.0.into_py_any(py)?, error.1.into_py_any(py)?);
+            let _ = pyresults.set_item(error.path.clone(), scan_error_to_py(py, &error)?);
         }
         Ok(pyresults.into_any().unbind())
     }
@@ -233,11 +341,37 @@ impl Scandir {
         Statistics(self.instance.statistics())
     }
 
+    #[pyo3(signature = (hash=None, partial_size=None))]
+    pub fn duplicates(
+        &mut self,
+        py: Python,
+        hash: Option<&str>,
+        partial_size: Option<u64>,
+    ) -> PyResult<Duplicates> {
+        let algorithm =
+            scandir::HashAlgorithm::from_str(hash.unwrap_or("xxh3")).map_err(io_error_to_pyerr)?;
+        py.detach(|| {
+            self.instance
+                .duplicates(algorithm, partial_size.unwrap_or(4096))
+        })
+        .map(Duplicates)
+        .map_err(io_error_to_pyerr)
+    }
+
+    pub fn largest(&mut self) -> Option<TopN> {
+        self.instance.largest().map(TopN)
+    }
+
     #[getter]
     pub fn duration(&mut self) -> f64 {
         self.instance.duration()
     }
 
+    #[getter]
+    pub fn peak_queue_len(&self) -> usize {
+        self.instance.peak_queue_len()
+    }
+
     #[getter]
     pub fn finished(&mut self) -> bool {
         self.instance.finished()
@@ -249,9 +383,7 @@ impl Scandir {
     }
 
     fn __enter__(mut slf: PyRefMut<Self>) -> PyResult<PyRefMut<Self>> {
-        slf.instance
-            .start()
-            .map_err(|e| PyException::new_err(e.to_string()))?;
+        slf.instance.start().map_err(io_error_to_pyerr)?;
         Ok(slf)
     }
 
@@ -276,7 +408,7 @@ impl Scandir {
         if slf.instance.busy() {
             return Err(PyRuntimeError::new_err("Busy"));
         }
-        slf.instance.start()?;
+        slf.instance.start().map_err(io_error_to_pyerr)?;
         slf.entries.clear();
         Ok(slf)
     }
@@ -292,12 +424,15 @@ impl Scandir {
                         return Ok(Some(Py::new(py, DirEntryExt::from(&e)).unwrap().into_any()));
                     }
                     ScandirResult::Error(error) => {
-                        return Ok(Some(error.into_py_any(py)?));
+                        return Ok(Some(scan_error_to_py(py, &error)?));
+                    }
+                    ScandirResult::SymlinkLoop(e) => {
+                        return Ok(Some((e.path, e.target).into_py_any(py)?));
                     }
                 }
             }
             if let Some(error) = self.entries.errors.pop() {
-                return Ok(Some(error.into_py_any(py)?));
+                return Ok(Some(scan_error_to_py(py, &error)?));
             }
             let entries = self.instance.results(true);
             if entries.is_empty() {
@@ -1,14 +1,16 @@
+use std::ffi::OsString;
 use std::fmt::Debug;
-use std::io::ErrorKind;
 use std::thread;
 use std::time::Duration;
 
-use pyo3::exceptions::{PyException, PyFileNotFoundError, PyRuntimeError, PyValueError};
+use pyo3::exceptions::{PyException, PyRuntimeError, PyValueError};
 use pyo3::types::{PyBytes, PyType};
 use pyo3::{IntoPyObjectExt, prelude::*};
-use scandir::ErrorsType;
 
-use crate::def::{ReturnType, Statistics, Toc};
+use crate::def::error::{io_error_to_pyerr, scan_errors_to_py};
+use crate::def::{
+    ReturnType, RootPaths, Statistics, TimeBound, Toc, parse_entry_types, time_range,
+};
 
 #[pyclass]
 #[derive(Debug)]
@@ -24,9 +26,9 @@ pub struct Walk {
 impl Walk {
     #[allow(clippy::too_many_arguments)]
     #[new]
-    #[pyo3(signature = (root_path, sorted=None, skip_hidden=None, max_depth=None, max_file_cnt=None, dir_include=None, dir_exclude=None, file_include=None, file_exclude=None, case_sensitive=None, follow_links=None, return_type=None, store=None))]
+    #[pyo3(signature = (root_path, sorted=None, skip_hidden=None, max_depth=None, max_file_cnt=None, dir_include=None, dir_exclude=None, file_include=None, file_exclude=None, case_sensitive=None, follow_links=None, return_type=None, store=None, min_size=None, max_size=None, mtime_newer=None, mtime_older=None, ctime_newer=None, ctime_older=None, atime_newer=None, atime_older=None, entry_types=None, perm=None, uid=None, gid=None, nouser=None, nogroup=None, min_links=None, max_links=None, dir_include_regex=None, dir_exclude_regex=None, file_include_regex=None, file_exclude_regex=None, gitignore=None, filter_expr=None, file_match_path=None, sort_by=None, channel_capacity=None))]
     fn new(
-        root_path: &str,
+        root_path: RootPaths,
         sorted: Option<bool>,
         skip_hidden: Option<bool>,
         max_depth: Option<usize>,
@@ -39,10 +41,39 @@ impl Walk {
         follow_links: Option<bool>,
         return_type: Option<ReturnType>,
         store: Option<bool>,
+        min_size: Option<u64>,
+        max_size: Option<u64>,
+        mtime_newer: Option<TimeBound>,
+        mtime_older: Option<TimeBound>,
+        ctime_newer: Option<TimeBound>,
+        ctime_older: Option<TimeBound>,
+        atime_newer: Option<TimeBound>,
+        atime_older: Option<TimeBound>,
+        entry_types: Option<Vec<String>>,
+        perm: Option<&str>,
+        uid: Option<u32>,
+        gid: Option<u32>,
+        nouser: Option<bool>,
+        nogroup: Option<bool>,
+        min_links: Option<u64>,
+        max_links: Option<u64>,
+        dir_include_regex: Option<Vec<String>>,
+        dir_exclude_regex: Option<Vec<String>>,
+        file_include_regex: Option<Vec<String>>,
+        file_exclude_regex: Option<Vec<String>>,
+        gitignore: Option<bool>,
+        filter_expr: Option<String>,
+        file_match_path: Option<bool>,
+        sort_by: Option<&str>,
+        channel_capacity: Option<usize>,
     ) -> PyResult<Self> {
         let return_type = return_type.unwrap_or(ReturnType::Base);
+        let instance = match root_path {
+            RootPaths::Single(root_path) => scandir::Walk::new(root_path, store),
+            RootPaths::Multi(root_paths) => scandir::Walk::new_multi(&root_paths, store),
+        };
         Ok(Walk {
-            instance: match scandir::Walk::new(root_path, store) {
+            instance: match instance {
                 Ok(s) => s
                     .sorted(sorted.unwrap_or(false))
                     .skip_hidden(skip_hidden.unwrap_or(false))
@@ -54,15 +85,39 @@ impl Walk {
                     .file_exclude(file_exclude)
                     .case_sensitive(case_sensitive.unwrap_or(false))
                     .follow_links(follow_links.unwrap_or(false))
-                    .return_type(return_type.from_object()),
-                Err(e) => match e.kind() {
-                    ErrorKind::NotFound => {
-                        return Err(PyFileNotFoundError::new_err(e.to_string()));
-                    }
-                    _ => {
-                        return Err(PyException::new_err(e.to_string()));
-                    }
-                },
+                    .return_type(return_type.from_object())
+                    .min_size(min_size)
+                    .max_size(max_size)
+                    .mtime(time_range(mtime_newer, mtime_older))
+                    .ctime(time_range(ctime_newer, ctime_older))
+                    .atime(time_range(atime_newer, atime_older))
+                    .entry_types(parse_entry_types(entry_types)?)
+                    .perm(
+                        perm.map(|perm| perm.parse())
+                            .transpose()
+                            .map_err(io_error_to_pyerr)?,
+                    )
+                    .uid(uid)
+                    .gid(gid)
+                    .nouser(nouser.unwrap_or(false))
+                    .nogroup(nogroup.unwrap_or(false))
+                    .min_links(min_links)
+                    .max_links(max_links)
+                    .dir_include_regex(dir_include_regex)
+                    .dir_exclude_regex(dir_exclude_regex)
+                    .file_include_regex(file_include_regex)
+                    .file_exclude_regex(file_exclude_regex)
+                    .gitignore(gitignore.unwrap_or(false))
+                    .filter_expr(filter_expr)
+                    .file_match_path(file_match_path.unwrap_or(false))
+                    .sort_by(
+                        sort_by
+                            .map(|sort_by| sort_by.parse())
+                            .transpose()
+                            .map_err(io_error_to_pyerr)?,
+                    )
+                    .channel_capacity(channel_capacity.unwrap_or(0)),
+                Err(e) => return Err(io_error_to_pyerr(e)),
             },
             return_type,
             entries: Vec::new(),
@@ -81,9 +136,7 @@ impl Walk {
     }
 
     pub fn start(&mut self) -> PyResult<()> {
-        self.instance
-            .start()
-            .map_err(|e| PyException::new_err(e.to_string()))
+        self.instance.start().map_err(io_error_to_pyerr)
     }
 
     pub fn join(&mut self, py: Python) -> PyResult<bool> {
@@ -101,8 +154,24 @@ impl Walk {
         Ok(true)
     }
 
-    pub fn collect(&mut self, py: Python) -> PyResult<Toc> {
-        Ok(Toc::from(&py.detach(|| self.instance.collect())?))
+    /// Collects the results. If `sort_by` is set, all names are sorted by this key.
+    #[pyo3(signature = (sort_by=None))]
+    pub fn collect(&mut self, sort_by: Option<&str>, py: Python) -> PyResult<Toc> {
+        let sort_by = sort_by
+            .map(|sort_by| sort_by.parse::<scandir::SortBy>())
+            .transpose()
+            .map_err(io_error_to_pyerr)?;
+        let root_path = self.instance.options().root_path;
+        let toc = py
+            .detach(|| {
+                let mut toc = self.instance.collect()?;
+                if let Some(sort_by) = sort_by {
+                    toc.sort(sort_by, &root_path);
+                }
+                Ok(toc)
+            })
+            .map_err(io_error_to_pyerr)?;
+        Ok(Toc::from(&toc))
     }
 
     #[pyo3(signature = (only_new=None))]
@@ -120,11 +189,11 @@ impl Walk {
         &mut self,
         only_new: Option<bool>,
         py: Python,
-    ) -> PyResult<Vec<(String, Py<PyAny>)>> {
+    ) -> PyResult<Vec<(OsString, Py<PyAny>)>> {
         let mut results = Vec::new();
         for result in self.instance.results(only_new.unwrap_or(false)) {
             results.push((
-                result.0,
+                result.1.os_dir(&result.0),
                 Py::new(py, Toc::from(&result.1)).unwrap().into_py_any(py)?,
             ));
         }
@@ -140,13 +209,18 @@ impl Walk {
         self.instance.duration()
     }
 
+    #[getter]
+    pub fn peak_queue_len(&self) -> usize {
+        self.instance.peak_queue_len()
+    }
+
     pub fn errors_cnt(&mut self) -> usize {
         self.instance.errors_cnt()
     }
 
     #[pyo3(signature = (only_new=None))]
-    pub fn errors(&mut self, only_new: Option<bool>) -> ErrorsType {
-        self.instance.errors(only_new.unwrap_or(true))
+    pub fn errors(&mut self, only_new: Option<bool>, py: Python) -> PyResult<Vec<Py<PyAny>>> {
+        scan_errors_to_py(py, &self.instance.errors(only_new.unwrap_or(true)))
     }
 
     #[cfg(feature = "speedy")]
@@ -202,9 +276,7 @@ impl Walk {
     }
 
     fn __enter__(mut slf: PyRefMut<Self>) -> PyResult<PyRefMut<Self>> {
-        slf.instance
-            .start()
-            .map_err(|e| PyException::new_err(e.to_string()))?;
+        slf.instance.start().map_err(io_error_to_pyerr)?;
         Ok(slf)
     }
 
@@ -229,7 +301,7 @@ impl Walk {
         if slf.idx < usize::MAX {
             return Err(PyRuntimeError::new_err("Busy"));
         }
-        slf.instance.start()?;
+        slf.instance.start().map_err(io_error_to_pyerr)?;
         slf.entries.clear();
         slf.idx = 0;
         Ok(slf)
@@ -241,17 +313,17 @@ impl Walk {
                 self.idx += 1;
                 if self.return_type == ReturnType::Base {
                     return Ok(Some(
-                        (root_dir, toc.dirs.clone(), toc.files.clone()).into_py_any(py)?,
+                        (toc.os_dir(root_dir), toc.os_dirs(), toc.os_files()).into_py_any(py)?,
                     ));
                 } else {
                     return Ok(Some(
                         (
-                            root_dir,
-                            toc.dirs.clone(),
-                            toc.files.clone(),
-                            toc.symlinks.clone(),
-                            toc.other.clone(),
-                            toc.errors.clone(),
+                            toc.os_dir(root_dir),
+                            toc.os_dirs(),
+                            toc.os_files(),
+                            toc.os_symlinks(),
+                            toc.os_other(),
+                            scan_errors_to_py(py, &toc.errors)?,
                         )
                             .into_py_any(py)?,
                     ));
@@ -0,0 +1,96 @@
+use std::{path::Path, time::Duration};
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+
+/// Creates a monorepo like file tree with 100 packages of 20 directories with 10 files each.
+fn create_test_data(root: &Path) {
+    for package in 0..100 {
+        for dir in 0..20 {
+            let dir_path = root
+                .join(format!("pkg_{package}"))
+                .join(format!("dir_{dir}"));
+            std::fs::create_dir_all(&dir_path).unwrap();
+            for file in 0..10 {
+                let ext = ["rs", "py", "txt", "json", "o"][file % 5];
+                std::fs::write(dir_path.join(format!("file_{file}.{ext}")), b"").unwrap();
+            }
+        }
+    }
+}
+
+/// Creates `count` exclude patterns for directories and files. The patterns are a mix of
+/// literals, extensions, prefixes and wildcards, which don't match any entry of the test data.
+fn create_patterns(count: usize) -> (Vec<String>, Vec<String>) {
+    let dir_exclude = (0..count)
+        .map(|i| match i % 4 {
+            0 => format!("vendor_{i}"),
+            1 => format!("pkg_{i}/build/**"),
+            2 => format!("**/target_{i}"),
+            _ => format!("pkg_*/gen_{i}_*"),
+        })
+        .collect();
+    let file_exclude = (0..count)
+        .map(|i| match i % 4 {
+            0 => format!("*.ext{i}"),
+            1 => format!("file_{i}.tmp"),
+            2 => format!("backup_{i}*"),
+            _ => format!("*_{i}.[ch]pp"),
+        })
+        .collect();
+    (dir_exclude, file_exclude)
+}
+
+fn benchmark_patterns(c: &mut Criterion, path: &Path) {
+    let mut group = c.benchmark_group("Walk with exclude patterns");
+    group.measurement_time(Duration::from_secs(10));
+    group.sample_size(20);
+    for count in [0, 10, 100, 1000] {
+        let (dir_exclude, file_exclude) = create_patterns(count);
+        group.bench_with_input(BenchmarkId::new("file name", count), &count, |b, _| {
+            b.iter(|| {
+                scandir::Walk::new(path, Some(true))
+                    .unwrap()
+                    .dir_exclude(Some(dir_exclude.clone()))
+                    .file_exclude(Some(file_exclude.clone()))
+                    .collect()
+                    .unwrap();
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("relative path", count), &count, |b, _| {
+            b.iter(|| {
+                scandir::Walk::new(path, Some(true))
+                    .unwrap()
+                    .dir_exclude(Some(dir_exclude.clone()))
+                    .file_exclude(Some(file_exclude.clone()))
+                    .file_match_path(true)
+                    .collect()
+                    .unwrap();
+            })
+        });
+        group.bench_with_input(
+            BenchmarkId::new("case insensitive", count),
+            &count,
+            |b, _| {
+                b.iter(|| {
+                    scandir::Walk::new(path, Some(true))
+                        .unwrap()
+                        .dir_exclude(Some(dir_exclude.clone()))
+                        .file_exclude(Some(file_exclude.clone()))
+                        .case_sensitive(false)
+                        .collect()
+                        .unwrap();
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn benchmarks(c: &mut Criterion) {
+    let temp_dir = tempfile::tempdir().unwrap();
+    create_test_data(temp_dir.path());
+    benchmark_patterns(c, temp_dir.path());
+}
+
+criterion_group!(benches, benchmarks);
+criterion_main!(benches);
@@ -20,8 +20,8 @@ fn main() -> Result<(), Error> {
     //instance.stop();
     let entries = instance.collect()?;
     println!("dt={}", now.elapsed().as_secs_f64());
-    for (path, error) in entries.errors.iter() {
-        println!("ERROR {path:?}: {error}");
+    for error in entries.errors.iter() {
+        println!("ERROR {error}");
     }
     let first_result = entries.results.first().unwrap();
     println!(
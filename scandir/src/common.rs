@@ -1,234 +1,805 @@
-use std::fs::{self, Metadata};
+use std::cell::RefCell;
+use std::fs;
 use std::io::{Error, ErrorKind};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime};
 
 #[cfg(unix)]
 use expanduser::expanduser;
 
-use glob_sl::{MatchOptions, Pattern};
+use flume::{Receiver, SendTimeoutError, Sender, bounded, unbounded};
+use globset::{Candidate, Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+use jwalk_meta::ClientState;
+use rayon::prelude::*;
+use regex::{RegexSet, RegexSetBuilder};
 
-use crate::def::{Filter, Options};
+use crate::def::hash::{HashAlgorithm, HashMode, hash_file};
+use crate::def::{
+    EntryFilter, EntryInfo, EntryType, ErrorPhase, Filter, FilterAction, FilterExpr, Matcher,
+    Options, OwnerCache, ScanError, ScanErrorKind, SortBy, SortFields, SymlinkLoop, TimeField,
+};
+use crate::gitignore::IgnoreStack;
+
+/// Device and inode numbers of the directories from the root path down to the directory
+/// being read. Used for detecting symlink loops.
+pub type DirIds = Arc<Vec<(u64, u64)>>;
+
+/// Read dir state of the walker of `Scandir` and `Walk`.
+#[derive(Debug, Clone, Default)]
+pub struct ReadDirState {
+    pub dir_ids: DirIds,
+    pub ignores: IgnoreStack,
+}
+
+/// Entry state of the walker of `Count`, `Walk` and `Scandir`.
+#[derive(Debug, Clone, Default)]
+pub struct EntryState<T> {
+    /// Set by `filter_children` for directories which are descended into, but not returned,
+    /// because the custom entry filter skipped them.
+    pub skipped: bool,
+    /// State of the scanner, e.g. the digest of a file.
+    pub data: T,
+}
+
+/// Message reported for directories which were not descended into in one file system mode.
+pub const MOUNT_POINT_NOT_CROSSED: &str = "Mount point not crossed";
 
 pub fn check_and_expand_path<P: AsRef<Path>>(path_str: P) -> Result<PathBuf, Error> {
     #[cfg(unix)]
-    let path_result = fs::canonicalize(expanduser(path_str.as_ref().to_str().unwrap()).unwrap());
+    let path_result = match path_str.as_ref().to_str() {
+        Some(s) => expanduser(s).and_then(fs::canonicalize),
+        None => fs::canonicalize(&path_str),
+    };
     #[cfg(not(unix))]
     let path_result = fs::canonicalize(&path_str);
     let path = match path_result {
         Ok(p) => {
             if !p.exists() {
-                return Err(Error::new(
-                    ErrorKind::NotFound,
-                    path_str.as_ref().to_str().unwrap().to_string(),
-                ));
+                return Err(ScanError::new(
+                    &path_str,
+                    ScanErrorKind::NotFound,
+                    ErrorPhase::Stat,
+                    "Path does not exist",
+                )
+                .into());
             }
             p
         }
         Err(e) => {
-            return Err(Error::other(e.to_string()));
+            return Err(ScanError::from_io(&path_str, ErrorPhase::Stat, &e).into());
         }
     };
     Ok(path)
 }
 
+/// Expand and check a list of root paths. Paths which are equal to or located inside
+/// another root path are removed, so that no entry is scanned twice.
+pub fn check_and_expand_paths<P: AsRef<Path>>(paths: &[P]) -> Result<Vec<PathBuf>, Error> {
+    if paths.is_empty() {
+        return Err(Error::new(ErrorKind::InvalidInput, "No root path given"));
+    }
+    let paths = paths
+        .iter()
+        .map(check_and_expand_path)
+        .collect::<Result<Vec<_>, Error>>()?;
+    let mut root_paths: Vec<PathBuf> = Vec::new();
+    for (i, path) in paths.iter().enumerate() {
+        let overlaps = paths.iter().enumerate().any(|(j, other)| {
+            if path == other {
+                j < i
+            } else {
+                path.starts_with(other)
+            }
+        });
+        if !overlaps {
+            root_paths.push(path.clone());
+        }
+    }
+    Ok(root_paths)
+}
+
 pub fn get_root_path_len(root_path: &Path) -> usize {
-    let root_path = root_path.to_str().unwrap();
+    let root_path = root_path.as_os_str().as_encoded_bytes();
     let mut root_path_len = root_path.len();
     #[cfg(unix)]
-    if !root_path.ends_with('/') {
+    if !root_path.ends_with(b"/") {
         root_path_len += 1;
     }
     #[cfg(windows)]
-    if !root_path.ends_with('\\') {
+    if !root_path.ends_with(b"\\") {
         root_path_len += 1;
     }
     root_path_len
 }
 
-pub fn create_filter(options: &Options) -> Result<Option<Filter>, Error> {
-    let mut filter = Filter {
-        dir_include: Vec::new(),
-        dir_exclude: Vec::new(),
-        file_include: Vec::new(),
-        file_exclude: Vec::new(),
-        options: match options.case_sensitive {
-            true => None,
-            false => Some(MatchOptions {
-                case_sensitive: false,
-                ..MatchOptions::new()
-            }),
-        },
+/// Returns the part of a path after the root path. Paths which aren't valid UTF-8 are kept
+/// without loss on Unix.
+#[inline]
+pub fn relative_path(path: &Path, root_path_len: usize) -> PathBuf {
+    #[cfg(unix)]
+    {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = path.as_os_str().as_bytes();
+        PathBuf::from(OsStr::from_bytes(
+            path.get(root_path_len..).unwrap_or_default(),
+        ))
+    }
+    #[cfg(not(unix))]
+    {
+        // Replacement characters have the same length as invalid sequences of the encoding
+        let path = path.to_string_lossy();
+        PathBuf::from(path.get(root_path_len..).unwrap_or_default())
+    }
+}
+
+/// Sending side of the channel between a scanning thread and the consumer.
+///
+/// If the channel is bounded, sending blocks while the channel is full, until the task is stopped
+/// or the receiver is dropped. The peak number of queued results is tracked.
+#[derive(Debug, Clone)]
+pub struct ResultSender<T> {
+    tx: Sender<T>,
+    stop: Arc<AtomicBool>,
+    peak_queue_len: Arc<AtomicUsize>,
+}
+
+impl<T> ResultSender<T> {
+    #[inline]
+    pub fn send(&self, value: T) -> bool {
+        let mut value = value;
+        loop {
+            match self.tx.send_timeout(value, Duration::from_millis(10)) {
+                Ok(()) => {
+                    self.peak_queue_len
+                        .fetch_max(self.tx.len(), Ordering::Relaxed);
+                    return true;
+                }
+                Err(SendTimeoutError::Timeout(v)) => {
+                    if self.stop.load(Ordering::Relaxed) {
+                        return false;
+                    }
+                    value = v;
+                }
+                Err(SendTimeoutError::Disconnected(_)) => return false,
+            }
+        }
+    }
+}
+
+/// Create the channel for a scanning thread. A `capacity` of `0` creates an unbounded channel.
+pub fn create_channel<T>(
+    capacity: usize,
+    stop: Arc<AtomicBool>,
+    peak_queue_len: Arc<AtomicUsize>,
+) -> (ResultSender<T>, Receiver<T>) {
+    let (tx, rx) = match capacity {
+        0 => unbounded(),
+        _ => bounded(capacity),
     };
-    if let Some(ref f) = options.dir_include {
-        let f = &mut f
-            .iter()
-            .map(|s| Pattern::new(s))
-            .collect::<Result<Vec<_>, glob_sl::PatternError>>();
-        let f = match f {
-            Ok(f) => f,
-            Err(e) => {
-                return Err(Error::new(
-                    ErrorKind::InvalidInput,
-                    format!("dir_include: {}", e),
-                ));
+    peak_queue_len.store(0, Ordering::Relaxed);
+    (
+        ResultSender {
+            tx,
+            stop,
+            peak_queue_len,
+        },
+        rx,
+    )
+}
+
+#[inline]
+fn filter_error(option: &str, e: &impl std::fmt::Display) -> Error {
+    ScanError::new(
+        "",
+        ScanErrorKind::InvalidInput,
+        ErrorPhase::FilterCompile,
+        format!("{option}: {e}"),
+    )
+    .into()
+}
+
+/// Translates a class of `glob_sl` without the brackets and the `!` to the syntax of
+/// `globset`, which reads a leading `^` as negation. The `^` is moved behind the other
+/// characters, but before a trailing `-`.
+fn translate_class(class: &[char], negated: bool, translated: &mut String) -> Result<(), String> {
+    if negated || class.first() != Some(&'^') {
+        translated.push('[');
+        if negated {
+            translated.push('!');
+        }
+        translated.extend(class);
+        translated.push(']');
+        return Ok(());
+    }
+    let mut class = class;
+    let mut rest = String::new();
+    loop {
+        match class {
+            ['^', '-', end, tail @ ..] => {
+                if *end < '^' {
+                    return Err(format!("invalid range; '^' > '{end}'"));
+                }
+                if *end > '^' {
+                    rest.push_str(&format!("_-{end}"));
+                }
+                class = tail;
             }
-        };
-        filter.dir_include.append(f);
-    }
-    if let Some(ref f) = options.dir_exclude {
-        let f = &mut f
-            .iter()
-            .map(|s| Pattern::new(s))
-            .collect::<Result<Vec<_>, glob_sl::PatternError>>();
-        let f = match f {
-            Ok(f) => f,
-            Err(e) => {
-                return Err(Error::new(
-                    ErrorKind::InvalidInput,
-                    format!("dir_exclude: {}", e),
-                ));
+            ['^', tail @ ..] => class = tail,
+            _ => break,
+        }
+    }
+    rest.extend(class);
+    match rest.as_str() {
+        "" => translated.push('^'),
+        "-" => translated.push_str("[-^]"),
+        _ => match rest.strip_suffix('-') {
+            Some(head) => translated.push_str(&format!("[{head}^-]")),
+            None => translated.push_str(&format!("[{rest}^]")),
+        },
+    }
+    Ok(())
+}
+
+/// Translates a pattern with the syntax of `glob_sl` to the syntax of `globset`. `{` and `}`
+/// are literal characters in `glob_sl` and are put into brackets, and a `^` at the start of a
+/// class is a literal character too. Like `glob_sl`, `***` and `**` which isn't a whole path
+/// component are rejected. On Windows `\` is a path separator like `/`.
+fn translate_glob(pattern: &str) -> Result<String, String> {
+    let is_separator = |c: char| c == '/' || (cfg!(windows) && c == '\\');
+    let chars = pattern.chars().collect::<Vec<_>>();
+    let mut translated = String::with_capacity(pattern.len() + 4);
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            // A `]` directly after `[` or `[!` is part of the class
+            '[' => {
+                let negated = chars.get(i + 1) == Some(&'!');
+                let start = i + 1 + usize::from(negated);
+                match chars
+                    .get(start + 1..)
+                    .and_then(|rest| rest.iter().position(|c| *c == ']'))
+                {
+                    Some(end) => {
+                        let end = start + 1 + end;
+                        translate_class(&chars[start..end], negated, &mut translated)?;
+                        i = end + 1;
+                        continue;
+                    }
+                    None => return Err("invalid range pattern".to_string()),
+                }
             }
-        };
-        filter.dir_exclude.append(f);
-    }
-    if let Some(ref f) = options.file_include {
-        let f = &mut f
-            .iter()
-            .map(|s| Pattern::new(s))
-            .collect::<Result<Vec<_>, glob_sl::PatternError>>();
-        let f = match f {
-            Ok(f) => f,
-            Err(e) => {
-                return Err(Error::new(
-                    ErrorKind::InvalidInput,
-                    format!("file_include: {}", e),
-                ));
+            '*' => {
+                let count = chars[i..].iter().take_while(|c| **c == '*').count();
+                if count > 2 {
+                    return Err("wildcards are either regular `*` or recursive `**`".to_string());
+                }
+                if count == 2
+                    && (i > 0 && !is_separator(chars[i - 1])
+                        || chars.get(i + 2).is_some_and(|c| !is_separator(*c)))
+                {
+                    return Err("recursive wildcards must form a single path component".to_string());
+                }
+                translated.extend(&chars[i..i + count]);
+                i += count;
+                continue;
             }
-        };
-        filter.file_include.append(f);
-    }
-    if let Some(ref f) = options.file_exclude {
-        let f = &mut f
-            .iter()
-            .map(|s| Pattern::new(s))
-            .collect::<Result<Vec<_>, glob_sl::PatternError>>();
-        let f = match f {
-            Ok(f) => f,
-            Err(e) => {
-                return Err(Error::new(
-                    ErrorKind::InvalidInput,
-                    format!("file_exclude: {}", e),
-                ));
+            c @ ('{' | '}') => {
+                translated.push('[');
+                translated.push(c);
+                translated.push(']');
             }
+            #[cfg(windows)]
+            '\\' => translated.push('/'),
+            c => translated.push(c),
+        }
+        i += 1;
+    }
+    Ok(translated)
+}
+
+/// Compiles a glob pattern with the syntax of `glob_sl`. `*` matches `/` too.
+pub fn compile_glob(pattern: &str, case_sensitive: bool) -> Result<Glob, String> {
+    GlobBuilder::new(&translate_glob(pattern)?)
+        .case_insensitive(!case_sensitive)
+        .backslash_escape(false)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+/// Compiles a list of glob patterns into a set. A pattern `foo/**` matches `foo` itself too.
+fn compile_globs(
+    option: &str,
+    patterns: &Option<Vec<String>>,
+    case_sensitive: bool,
+) -> Result<GlobSet, Error> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns.iter().flatten() {
+        let mut add = |pattern: &str| -> Result<(), Error> {
+            let glob =
+                compile_glob(pattern, case_sensitive).map_err(|e| filter_error(option, &e))?;
+            builder.add(glob);
+            Ok(())
         };
-        filter.file_exclude.append(f);
+        add(pattern)?;
+        if let Some(dir_pattern) = pattern.strip_suffix("/**") {
+            add(dir_pattern)?;
+        }
     }
+    builder.build().map_err(|e| filter_error(option, &e))
+}
+
+/// Compiles a list of regular expressions into a set. Patterns are not anchored.
+fn compile_regexes(
+    option: &str,
+    patterns: &Option<Vec<String>>,
+    case_sensitive: bool,
+) -> Result<RegexSet, Error> {
+    RegexSetBuilder::new(patterns.iter().flatten())
+        .case_insensitive(!case_sensitive)
+        .build()
+        .map_err(|e| filter_error(option, &e))
+}
+
+/// Compiles the glob patterns and regular expressions of an include or exclude filter.
+fn compile_matcher(
+    option: &str,
+    patterns: &Option<Vec<String>>,
+    regexes: &Option<Vec<String>>,
+    case_sensitive: bool,
+) -> Result<Matcher, Error> {
+    Ok(Matcher::new(
+        compile_globs(option, patterns, case_sensitive)?,
+        compile_regexes(&format!("{option}_regex"), regexes, case_sensitive)?,
+    ))
+}
+
+pub fn create_filter(options: &Options) -> Result<Option<Filter>, Error> {
+    let filter = Filter {
+        dir_include: compile_matcher(
+            "dir_include",
+            &options.dir_include,
+            &options.dir_include_regex,
+            options.case_sensitive,
+        )?,
+        dir_exclude: compile_matcher(
+            "dir_exclude",
+            &options.dir_exclude,
+            &options.dir_exclude_regex,
+            options.case_sensitive,
+        )?,
+        file_include: compile_matcher(
+            "file_include",
+            &options.file_include,
+            &options.file_include_regex,
+            options.case_sensitive,
+        )?,
+        file_exclude: compile_matcher(
+            "file_exclude",
+            &options.file_exclude,
+            &options.file_exclude_regex,
+            options.case_sensitive,
+        )?,
+        min_size: options.min_size,
+        max_size: options.max_size,
+        mtime: options.mtime,
+        ctime: options.ctime,
+        atime: options.atime,
+        entry_types: options.entry_types.clone(),
+        perm: options.perm,
+        uid: options.uid,
+        gid: options.gid,
+        nouser: options.nouser,
+        nogroup: options.nogroup,
+        owners: OwnerCache::default(),
+        min_links: options.min_links,
+        max_links: options.max_links,
+        filter_expr: options
+            .filter_expr
+            .as_deref()
+            .map(str::parse::<FilterExpr>)
+            .transpose()
+            .map_err(|e| filter_error("filter_expr", &e))?,
+        entry_filter: options.entry_filter.clone(),
+        file_match_path: options.file_match_path,
+        now: SystemTime::now(),
+    };
     if filter.dir_include.is_empty()
         && filter.dir_exclude.is_empty()
         && filter.file_include.is_empty()
         && filter.file_exclude.is_empty()
+        && !filter.needs_metadata()
+        && filter.entry_types.is_none()
+        && filter.filter_expr.is_none()
+        && filter.entry_filter.is_none()
     {
         return Ok(None);
     }
     Ok(Some(filter))
 }
 
+thread_local! {
+    /// Buffer for the relative paths which are matched by the filters. Reused for all entries
+    /// of a thread, so that matching doesn't allocate.
+    static KEY_BUFFER: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+/// Calls `f` with the path of an entry relative to the root path. `/` is used as separator on
+/// all platforms. Invalid UTF-8 is replaced.
+#[inline]
+pub fn with_relative_key<C: ClientState, R>(
+    dir_entry: &jwalk_meta::DirEntry<C>,
+    root_path_len: usize,
+    f: impl FnOnce(&str) -> R,
+) -> R {
+    KEY_BUFFER.with_borrow_mut(|key| {
+        key.clear();
+        let parent_path = dir_entry.parent_path.as_os_str().as_encoded_bytes();
+        if let Some(parent_path) = parent_path.get(root_path_len..)
+            && !parent_path.is_empty()
+        {
+            let parent_path = String::from_utf8_lossy(parent_path);
+            #[cfg(windows)]
+            key.extend(parent_path.chars().map(|c| if c == '\\' { '/' } else { c }));
+            #[cfg(not(windows))]
+            key.push_str(&parent_path);
+            key.push('/');
+        }
+        key.push_str(&dir_entry.file_name.to_string_lossy());
+        f(key)
+    })
+}
+
+/// Returns `true` if `key` matches any of the exclude patterns or regular expressions.
+#[inline]
+pub fn filter_excluded(key: &str, matcher: &Matcher) -> bool {
+    matcher.is_match(key)
+}
+
+/// Returns `true` if no include filter is set or `key` matches any of the include patterns or
+/// regular expressions.
 #[inline]
-pub fn filter_direntry(
-    key: &str,
-    filter: &Vec<Pattern>,
-    options: Option<MatchOptions>,
-    empty: bool,
+pub fn filter_included(key: &str, matcher: &Matcher) -> bool {
+    matcher.is_empty() || matcher.is_match(key)
+}
+
+#[inline]
+pub fn filter_dir<C: ClientState>(
+    root_path_len: usize,
+    dir_entry: &jwalk_meta::DirEntry<C>,
+    filter_ref: &Filter,
 ) -> bool {
-    if filter.is_empty() || key.is_empty() {
-        return empty;
-    }
-    match options {
-        Some(options) => {
-            for f in filter {
-                if f.as_str().ends_with("**") && !key.ends_with('/') {
-                    // Workaround: glob currently has problems with "foo/**"
-                    let mut key = String::from(key);
-                    key.push('/');
-                    if f.matches_with(&key, options) {
-                        return true;
-                    }
-                }
-                if f.matches_with(key, options) {
-                    return true;
-                }
-            }
+    if filter_ref.dir_include.is_empty() && filter_ref.dir_exclude.is_empty() {
+        return true;
+    }
+    with_relative_key(dir_entry, root_path_len, |key| {
+        !filter_excluded(key, &filter_ref.dir_exclude)
+            && filter_included(key, &filter_ref.dir_include)
+    })
+}
+
+/// Returns `true` if a file passes the file include and exclude filters. The filters are matched
+/// against the file name or, if `file_match_path` is set, against the path relative to the root
+/// path like the directory filters.
+#[inline]
+pub fn filter_file<C: ClientState>(
+    root_path_len: usize,
+    dir_entry: &jwalk_meta::DirEntry<C>,
+    filter_ref: &Filter,
+) -> bool {
+    if filter_ref.file_include.is_empty() && filter_ref.file_exclude.is_empty() {
+        return true;
+    }
+    let filter_key = |key: &str| {
+        !filter_excluded(key, &filter_ref.file_exclude)
+            && filter_included(key, &filter_ref.file_include)
+    };
+    match filter_ref.file_match_path {
+        true => with_relative_key(dir_entry, root_path_len, filter_key),
+        false => filter_key(&dir_entry.file_name.to_string_lossy()),
+    }
+}
+
+/// Returns `true` if the size of a file is within the size range of the filter.
+/// Files without metadata are not within any range.
+#[inline]
+pub fn filter_size<C: ClientState>(
+    dir_entry: &jwalk_meta::DirEntry<C>,
+    filter_ref: &Filter,
+) -> bool {
+    if filter_ref.min_size.is_none() && filter_ref.max_size.is_none() {
+        return true;
+    }
+    let Some(ref metadata) = dir_entry.metadata else {
+        return false;
+    };
+    filter_ref
+        .min_size
+        .is_none_or(|min_size| metadata.size >= min_size)
+        && filter_ref
+            .max_size
+            .is_none_or(|max_size| metadata.size <= max_size)
+}
+
+/// Returns `true` if the timestamps of an entry are within the time ranges of the filter.
+/// Entries without metadata or with unknown timestamps are not within any range.
+#[inline]
+pub fn filter_times<C: ClientState>(
+    dir_entry: &jwalk_meta::DirEntry<C>,
+    filter_ref: &Filter,
+) -> bool {
+    if filter_ref.mtime.is_none() && filter_ref.ctime.is_none() && filter_ref.atime.is_none() {
+        return true;
+    }
+    let Some(ref metadata) = dir_entry.metadata else {
+        return false;
+    };
+    [
+        (&filter_ref.mtime, metadata.modified),
+        (&filter_ref.ctime, metadata.created),
+        (&filter_ref.atime, metadata.accessed),
+    ]
+    .iter()
+    .all(|(range, time)| match range {
+        Some(range) => time.is_some_and(|time| range.contains(time, filter_ref.now)),
+        None => true,
+    })
+}
+
+/// Returns `true` if the permission bits, owner and number of hard links of an entry match
+/// the filter. Entries without extended metadata don't match. Permission bits and owners are
+/// only supported on Unix.
+#[inline]
+pub fn filter_metadata_ext<C: ClientState>(
+    dir_entry: &jwalk_meta::DirEntry<C>,
+    filter_ref: &Filter,
+) -> bool {
+    let checks_owner = filter_ref.perm.is_some()
+        || filter_ref.uid.is_some()
+        || filter_ref.gid.is_some()
+        || filter_ref.nouser
+        || filter_ref.nogroup;
+    if !checks_owner && filter_ref.min_links.is_none() && filter_ref.max_links.is_none() {
+        return true;
+    }
+    let Some(ref metadata_ext) = dir_entry.metadata_ext else {
+        return false;
+    };
+    #[cfg(unix)]
+    let (owner_matches, links) = (
+        filter_ref
+            .perm
+            .is_none_or(|perm| perm.matches(metadata_ext.st_mode))
+            && filter_ref.uid.is_none_or(|uid| metadata_ext.st_uid == uid)
+            && filter_ref.gid.is_none_or(|gid| metadata_ext.st_gid == gid)
+            && (!filter_ref.nouser || !filter_ref.owners.uid_exists(metadata_ext.st_uid))
+            && (!filter_ref.nogroup || !filter_ref.owners.gid_exists(metadata_ext.st_gid)),
+        Some(metadata_ext.st_nlink),
+    );
+    #[cfg(not(unix))]
+    let (owner_matches, links) = (
+        !checks_owner,
+        metadata_ext.number_of_links.map(|links| links as u64),
+    );
+    owner_matches
+        && links.is_some_and(|links| {
+            filter_ref
+                .min_links
+                .is_none_or(|min_links| links >= min_links)
+                && filter_ref
+                    .max_links
+                    .is_none_or(|max_links| links <= max_links)
+        })
+}
+
+/// Returns the path of an entry relative to the root path. The root path itself has an empty
+/// relative path.
+#[inline]
+pub fn entry_relative_path<C: ClientState>(
+    dir_entry: &jwalk_meta::DirEntry<C>,
+    root_path_len: usize,
+) -> PathBuf {
+    if dir_entry.depth == 0 {
+        return PathBuf::new();
+    }
+    let mut path = relative_path(&dir_entry.parent_path, root_path_len);
+    path.push(&dir_entry.file_name);
+    path
+}
+
+/// Calls the custom entry filter for an entry.
+#[inline]
+pub fn entry_action<C: ClientState>(
+    dir_entry: &jwalk_meta::DirEntry<C>,
+    entry_filter: &dyn EntryFilter,
+    root_path_len: usize,
+) -> FilterAction {
+    let path = entry_relative_path(dir_entry, root_path_len);
+    entry_filter.filter_entry(&EntryInfo {
+        name: &dir_entry.file_name,
+        relative_path: &path,
+        file_type: dir_entry.file_type,
+        depth: dir_entry.depth,
+        metadata: dir_entry.metadata.as_ref(),
+        metadata_ext: dir_entry.metadata_ext.as_ref(),
+    })
+}
+
+/// Returns the number of hard links of an entry if the extended metadata was read.
+#[inline]
+fn entry_links<C: ClientState>(dir_entry: &jwalk_meta::DirEntry<C>) -> Option<u64> {
+    let metadata_ext = dir_entry.metadata_ext.as_ref()?;
+    #[cfg(unix)]
+    return Some(metadata_ext.st_nlink);
+    #[cfg(not(unix))]
+    return metadata_ext.number_of_links.map(u64::from);
+}
+
+/// Evaluates a filter expression for an entry. Tests which need metadata don't match entries
+/// without metadata. Permission bits and owners are only supported on Unix.
+pub fn eval_filter_expr<C: ClientState>(
+    dir_entry: &jwalk_meta::DirEntry<C>,
+    filter_expr: &FilterExpr,
+    filter_ref: &Filter,
+    root_path_len: usize,
+) -> bool {
+    let eval = |filter_expr| eval_filter_expr(dir_entry, filter_expr, filter_ref, root_path_len);
+    let metadata = dir_entry.metadata.as_ref();
+    match filter_expr {
+        FilterExpr::And(left, right) => eval(left) && eval(right),
+        FilterExpr::Or(left, right) => eval(left) || eval(right),
+        FilterExpr::Not(filter_expr) => !eval(filter_expr),
+        FilterExpr::True => true,
+        FilterExpr::False => false,
+        FilterExpr::Name(glob) => glob.is_match_candidate(&Candidate::from_bytes(
+            dir_entry.file_name.to_string_lossy().as_bytes(),
+        )),
+        FilterExpr::Path(glob) => with_relative_key(dir_entry, root_path_len, |key| {
+            glob.is_match_candidate(&Candidate::from_bytes(key))
+        }),
+        FilterExpr::Regex(regex) => {
+            regex.is_match(&entry_relative_path(dir_entry, root_path_len).to_string_lossy())
         }
-        None => {
-            for f in filter {
-                if f.as_str().ends_with("**") && !key.ends_with('/') {
-                    // Workaround: glob currently has problems with "foo/**"
-                    let mut key = String::from(key);
-                    key.push('/');
-                    if f.matches(&key) {
-                        return true;
-                    }
-                }
-                if f.matches(key) {
-                    return true;
-                }
-            }
+        FilterExpr::Type(entry_types) => {
+            entry_types.contains(&EntryType::from_file_type(&dir_entry.file_type))
+        }
+        FilterExpr::Size(compare, size) => {
+            metadata.is_some_and(|metadata| compare.compare(metadata.size, *size))
+        }
+        FilterExpr::Age(field, compare, age) => metadata
+            .and_then(|metadata| match field {
+                TimeField::Modified => metadata.modified,
+                TimeField::Created => metadata.created,
+                TimeField::Accessed => metadata.accessed,
+            })
+            .is_some_and(|time| {
+                compare.compare(
+                    filter_ref.now.duration_since(time).unwrap_or_default(),
+                    *age,
+                )
+            }),
+        FilterExpr::Depth(compare, depth) => compare.compare(dir_entry.depth, *depth),
+        FilterExpr::Links(compare, links) => {
+            entry_links(dir_entry).is_some_and(|entry_links| compare.compare(entry_links, *links))
+        }
+        #[cfg(unix)]
+        FilterExpr::Perm(perm) => dir_entry
+            .metadata_ext
+            .as_ref()
+            .is_some_and(|metadata_ext| perm.matches(metadata_ext.st_mode)),
+        #[cfg(unix)]
+        FilterExpr::Uid(uid) => dir_entry
+            .metadata_ext
+            .as_ref()
+            .is_some_and(|metadata_ext| metadata_ext.st_uid == *uid),
+        #[cfg(unix)]
+        FilterExpr::Gid(gid) => dir_entry
+            .metadata_ext
+            .as_ref()
+            .is_some_and(|metadata_ext| metadata_ext.st_gid == *gid),
+        #[cfg(unix)]
+        FilterExpr::NoUser => dir_entry
+            .metadata_ext
+            .as_ref()
+            .is_some_and(|metadata_ext| !filter_ref.owners.uid_exists(metadata_ext.st_uid)),
+        #[cfg(unix)]
+        FilterExpr::NoGroup => dir_entry
+            .metadata_ext
+            .as_ref()
+            .is_some_and(|metadata_ext| !filter_ref.owners.gid_exists(metadata_ext.st_gid)),
+        #[cfg(not(unix))]
+        FilterExpr::Perm(_)
+        | FilterExpr::Uid(_)
+        | FilterExpr::Gid(_)
+        | FilterExpr::NoUser
+        | FilterExpr::NoGroup => false,
+        FilterExpr::Empty => match dir_entry.file_type.is_dir() {
+            true => fs::read_dir(dir_entry.path()).is_ok_and(|mut dir| dir.next().is_none()),
+            false => metadata.is_some_and(|metadata| metadata.is_file && metadata.size == 0),
+        },
+        FilterExpr::ExistsTarget => {
+            !dir_entry.file_type.is_symlink() || fs::metadata(dir_entry.path()).is_ok()
         }
     }
-    false
 }
 
+/// Returns `true` if an entry is returned to the caller. Directories which are not returned
+/// because of their type, permission bits, owner or number of hard links, the filter expression
+/// or the custom entry filter, are still descended into.
 #[inline]
-pub fn filter_dir(
+pub fn output_entry<T, C: ClientState<DirEntryState = EntryState<T>>>(
+    dir_entry: &jwalk_meta::DirEntry<C>,
+    filter: &Option<Filter>,
     root_path_len: usize,
-    dir_entry: &jwalk_meta::DirEntry<((), Option<Result<Metadata, Error>>)>,
-    filter_ref: &Filter,
 ) -> bool {
-    let mut key = dir_entry.parent_path.to_path_buf();
-    key.push(dir_entry.file_name.clone().into_string().unwrap());
-    let key = key
-        .to_str()
-        .unwrap()
-        .get(root_path_len..)
-        .unwrap_or("")
-        .to_string();
-    if filter_direntry(&key, &filter_ref.dir_exclude, filter_ref.options, false)
-        || !filter_direntry(&key, &filter_ref.dir_include, filter_ref.options, true)
+    let Some(filter_ref) = filter else {
+        return true;
+    };
+    if !filter_ref.entry_types.as_ref().is_none_or(|entry_types| {
+        entry_types.contains(&EntryType::from_file_type(&dir_entry.file_type))
+    }) || !filter_metadata_ext(dir_entry, filter_ref)
     {
         return false;
     }
-    true
+    // Other entries are checked by `filter_children`
+    if !dir_entry.file_type.is_dir() {
+        return true;
+    }
+    !dir_entry.client_state.skipped
+        && filter_ref.filter_expr.as_ref().is_none_or(|filter_expr| {
+            eval_filter_expr(dir_entry, filter_expr, filter_ref, root_path_len)
+        })
 }
 
 #[inline]
-#[allow(clippy::type_complexity)]
-pub fn filter_children(
-    children: &mut Vec<
-        Result<jwalk_meta::DirEntry<((), Option<Result<Metadata, Error>>)>, jwalk_meta::Error>,
-    >,
+pub fn filter_children<T, C: ClientState<DirEntryState = EntryState<T>>>(
+    children: &mut Vec<Result<jwalk_meta::DirEntry<C>, jwalk_meta::Error>>,
     filter: &Option<Filter>,
     root_path_len: usize,
 ) {
     if let Some(filter_ref) = &filter {
-        children.retain(|dir_entry_result| {
+        children.retain_mut(|dir_entry_result| {
             dir_entry_result
-                .as_ref()
+                .as_mut()
                 .map(|dir_entry| {
                     if dir_entry.file_type.is_dir() {
-                        return filter_dir(root_path_len, dir_entry, filter_ref);
-                    } else {
-                        let options = filter_ref.options;
-                        let key = match dir_entry.file_name.to_str() {
-                            Some(s) => s,
-                            None => {
-                                return false;
+                        if !filter_dir(root_path_len, dir_entry, filter_ref) {
+                            return false;
+                        }
+                        // The action is evaluated only once. Skipped directories are descended
+                        // into and `output_entry` doesn't return them.
+                        if let Some(ref entry_filter) = filter_ref.entry_filter {
+                            match entry_action(dir_entry, entry_filter.as_ref(), root_path_len) {
+                                FilterAction::Keep => {}
+                                FilterAction::Skip => dir_entry.client_state.skipped = true,
+                                FilterAction::Prune => return false,
                             }
-                        };
-                        if filter_direntry(key, &filter_ref.file_exclude, options, false)
-                            || !filter_direntry(key, &filter_ref.file_include, options, true)
+                        }
+                    } else {
+                        if !filter_file(root_path_len, dir_entry, filter_ref) {
+                            return false;
+                        }
+                        if dir_entry.file_type.is_file() && !filter_size(dir_entry, filter_ref) {
+                            return false;
+                        }
+                        if !filter_times(dir_entry, filter_ref) {
+                            return false;
+                        }
+                        if !output_entry(dir_entry, filter, root_path_len) {
+                            return false;
+                        }
+                        if let Some(ref filter_expr) = filter_ref.filter_expr
+                            && !eval_filter_expr(dir_entry, filter_expr, filter_ref, root_path_len)
                         {
                             return false;
                         }
+                        if let Some(ref entry_filter) = filter_ref.entry_filter {
+                            return entry_action(dir_entry, entry_filter.as_ref(), root_path_len)
+                                == FilterAction::Keep;
+                        }
                     }
                     true
                 })
@@ -236,3 +807,388 @@ pub fn filter_children(
         });
     }
 }
+
+/// Returns the values of an entry which are compared when sorting the entries of a directory.
+#[inline]
+fn entry_sort_fields<C: ClientState>(dir_entry: &jwalk_meta::DirEntry<C>) -> SortFields<'_> {
+    let name = String::from_utf8_lossy(dir_entry.file_name.as_encoded_bytes());
+    match dir_entry.metadata {
+        Some(ref metadata) => SortFields {
+            name,
+            size: metadata.size,
+            mtime: metadata.modified,
+            ctime: metadata.created,
+            atime: metadata.accessed,
+        },
+        None => SortFields {
+            name,
+            ..Default::default()
+        },
+    }
+}
+
+/// Sorts the entries of a directory. Errors are moved to the end.
+#[inline]
+pub fn sort_children<C: ClientState>(
+    children: &mut [Result<jwalk_meta::DirEntry<C>, jwalk_meta::Error>],
+    sort_by: Option<SortBy>,
+) {
+    let Some(sort_by) = sort_by else {
+        return;
+    };
+    children.sort_by(|a, b| match (a, b) {
+        (Ok(a), Ok(b)) => sort_by.compare(&entry_sort_fields(a), &entry_sort_fields(b)),
+        (Ok(_), Err(_)) => std::cmp::Ordering::Less,
+        (Err(_), Ok(_)) => std::cmp::Ordering::Greater,
+        (Err(_), Err(_)) => std::cmp::Ordering::Equal,
+    });
+}
+
+/// Returns the device ID of the root path if the scan should not cross file system boundaries.
+/// Only supported on Unix. On other platforms `None` is returned.
+pub fn get_root_device(root_path: &Path, one_file_system: bool) -> Option<u64> {
+    if !one_file_system {
+        return None;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        fs::metadata(root_path).ok().map(|metadata| metadata.dev())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = root_path;
+        None
+    }
+}
+
+/// Returns the name of a user ID or `None` if the ID is unknown. Only supported on Unix.
+pub fn user_name(uid: u32) -> Option<String> {
+    #[cfg(unix)]
+    {
+        let mut buf = vec![0 as libc::c_char; 4096];
+        loop {
+            let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+            let mut result = std::ptr::null_mut();
+            let ret = unsafe {
+                libc::getpwuid_r(uid, &mut passwd, buf.as_mut_ptr(), buf.len(), &mut result)
+            };
+            if ret == libc::ERANGE && buf.len() < 1 << 20 {
+                buf.resize(buf.len() * 2, 0);
+                continue;
+            }
+            if ret != 0 || result.is_null() {
+                return None;
+            }
+            let name = unsafe { std::ffi::CStr::from_ptr(passwd.pw_name) };
+            return Some(name.to_string_lossy().to_string());
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = uid;
+        None
+    }
+}
+
+/// Returns the name of a group ID or `None` if the ID is unknown. Only supported on Unix.
+pub fn group_name(gid: u32) -> Option<String> {
+    #[cfg(unix)]
+    {
+        let mut buf = vec![0 as libc::c_char; 4096];
+        loop {
+            let mut group: libc::group = unsafe { std::mem::zeroed() };
+            let mut result = std::ptr::null_mut();
+            let ret = unsafe {
+                libc::getgrgid_r(gid, &mut group, buf.as_mut_ptr(), buf.len(), &mut result)
+            };
+            if ret == libc::ERANGE && buf.len() < 1 << 20 {
+                buf.resize(buf.len() * 2, 0);
+                continue;
+            }
+            if ret != 0 || result.is_null() {
+                return None;
+            }
+            let name = unsafe { std::ffi::CStr::from_ptr(group.gr_name) };
+            return Some(name.to_string_lossy().to_string());
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = gid;
+        None
+    }
+}
+
+/// Returns the ID of a user name or `None` if the name is unknown. Only supported on Unix.
+pub fn user_id(name: &str) -> Option<u32> {
+    #[cfg(unix)]
+    {
+        let name = std::ffi::CString::new(name).ok()?;
+        let mut buf = vec![0 as libc::c_char; 4096];
+        loop {
+            let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+            let mut result = std::ptr::null_mut();
+            let ret = unsafe {
+                libc::getpwnam_r(
+                    name.as_ptr(),
+                    &mut passwd,
+                    buf.as_mut_ptr(),
+                    buf.len(),
+                    &mut result,
+                )
+            };
+            if ret == libc::ERANGE && buf.len() < 1 << 20 {
+                buf.resize(buf.len() * 2, 0);
+                continue;
+            }
+            if ret != 0 || result.is_null() {
+                return None;
+            }
+            return Some(passwd.pw_uid);
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = name;
+        None
+    }
+}
+
+/// Returns the ID of a group name or `None` if the name is unknown. Only supported on Unix.
+pub fn group_id(name: &str) -> Option<u32> {
+    #[cfg(unix)]
+    {
+        let name = std::ffi::CString::new(name).ok()?;
+        let mut buf = vec![0 as libc::c_char; 4096];
+        loop {
+            let mut group: libc::group = unsafe { std::mem::zeroed() };
+            let mut result = std::ptr::null_mut();
+            let ret = unsafe {
+                libc::getgrnam_r(
+                    name.as_ptr(),
+                    &mut group,
+                    buf.as_mut_ptr(),
+                    buf.len(),
+                    &mut result,
+                )
+            };
+            if ret == libc::ERANGE && buf.len() < 1 << 20 {
+                buf.resize(buf.len() * 2, 0);
+                continue;
+            }
+            if ret != 0 || result.is_null() {
+                return None;
+            }
+            return Some(group.gr_gid);
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = name;
+        None
+    }
+}
+
+/// Returns `false` if the user ID has no entry in the user database. If the lookup fails for
+/// another reason, e.g. because the user database isn't reachable, `true` is returned. Only
+/// supported on Unix. On other platforms `true` is returned.
+pub fn uid_exists(uid: u32) -> bool {
+    #[cfg(unix)]
+    {
+        let mut buf = vec![0 as libc::c_char; 1024];
+        loop {
+            let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+            let mut result = std::ptr::null_mut();
+            let ret = unsafe {
+                libc::getpwuid_r(uid, &mut passwd, buf.as_mut_ptr(), buf.len(), &mut result)
+            };
+            if ret == libc::ERANGE && buf.len() < 1 << 20 {
+                buf.resize(buf.len() * 2, 0);
+                continue;
+            }
+            return !is_not_found(ret, result.is_null());
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = uid;
+        true
+    }
+}
+
+/// Returns `false` if the group ID has no entry in the group database. If the lookup fails for
+/// another reason `true` is returned. Only supported on Unix. On other platforms `true` is
+/// returned.
+pub fn gid_exists(gid: u32) -> bool {
+    #[cfg(unix)]
+    {
+        let mut buf = vec![0 as libc::c_char; 1024];
+        loop {
+            let mut group: libc::group = unsafe { std::mem::zeroed() };
+            let mut result = std::ptr::null_mut();
+            let ret = unsafe {
+                libc::getgrgid_r(gid, &mut group, buf.as_mut_ptr(), buf.len(), &mut result)
+            };
+            if ret == libc::ERANGE && buf.len() < 1 << 20 {
+                buf.resize(buf.len() * 2, 0);
+                continue;
+            }
+            return !is_not_found(ret, result.is_null());
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = gid;
+        true
+    }
+}
+
+/// Returns `true` if the return value of `getpwuid_r` or `getgrgid_r` means that the ID has no
+/// entry. Besides `0` without result, POSIX allows some error codes for missing entries.
+#[cfg(unix)]
+#[inline]
+fn is_not_found(ret: libc::c_int, no_result: bool) -> bool {
+    match ret {
+        0 => no_result,
+        libc::ENOENT | libc::ESRCH | libc::EBADF | libc::EPERM => true,
+        _ => false,
+    }
+}
+
+/// Creates the error reported for a mount point which was not crossed.
+#[inline]
+pub fn mount_point_error(path: String) -> ScanError {
+    ScanError::new(
+        path,
+        ScanErrorKind::CrossesDevices,
+        ErrorPhase::ReadDir,
+        MOUNT_POINT_NOT_CROSSED,
+    )
+}
+
+/// Creates the error reported if the metadata of a root path can't be read.
+#[inline]
+pub fn root_error(root_path: &Path, e: &jwalk_meta::Error) -> ScanError {
+    let mut error = ScanError::from(e);
+    error.path = root_path.to_string_lossy().to_string();
+    error.phase = ErrorPhase::Stat;
+    error
+}
+
+/// Prevents descending into child directories which are located on another device than
+/// the root path. Returns the paths of the mount points which were not crossed.
+#[inline]
+pub fn prune_mount_points<C: ClientState>(
+    children: &mut [Result<jwalk_meta::DirEntry<C>, jwalk_meta::Error>],
+    root_device: Option<u64>,
+) -> Vec<String> {
+    let mut mount_points = Vec::new();
+    #[cfg(unix)]
+    if let Some(root_device) = root_device {
+        use std::os::unix::fs::MetadataExt;
+
+        for dir_entry in children.iter_mut().flatten() {
+            let Some(ref read_children_path) = dir_entry.read_children_path else {
+                continue;
+            };
+            let device = match dir_entry.metadata_ext {
+                Some(ref metadata_ext) if !dir_entry.file_type.is_symlink() => {
+                    Some(metadata_ext.st_dev)
+                }
+                _ => fs::metadata(read_children_path).ok().map(|m| m.dev()),
+            };
+            if device.is_some_and(|device| device != root_device) {
+                mount_points.push(dir_entry.path().to_string_lossy().to_string());
+                dir_entry.read_children_path = None;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = (children, root_device);
+    mount_points
+}
+
+/// Detects symlinks which point to one of their ancestor directories by comparing device and
+/// inode numbers. Such symlinks are not descended into. Loops already detected by the walker
+/// are removed from `children`. Returns all detected loops. Comparing device and inode numbers
+/// is only supported on Unix.
+pub fn detect_symlink_loops<C: ClientState>(
+    dir_path: &Path,
+    ancestors: &mut DirIds,
+    children: &mut Vec<Result<jwalk_meta::DirEntry<C>, jwalk_meta::Error>>,
+) -> Vec<SymlinkLoop> {
+    let mut symlink_loops = Vec::new();
+    children.retain(|dir_entry_result| match dir_entry_result {
+        Err(e) if e.loop_ancestor().is_some() => {
+            let path = e.path().unwrap_or_else(|| Path::new(""));
+            let target =
+                fs::read_link(path).unwrap_or_else(|_| e.loop_ancestor().unwrap().to_path_buf());
+            symlink_loops.push(SymlinkLoop {
+                path: path.to_string_lossy().to_string(),
+                target: target.to_string_lossy().to_string(),
+            });
+            false
+        }
+        _ => true,
+    });
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        let Ok(metadata) = fs::metadata(dir_path) else {
+            return symlink_loops;
+        };
+        let mut dir_ids = ancestors.to_vec();
+        dir_ids.push((metadata.dev(), metadata.ino()));
+        *ancestors = Arc::new(dir_ids);
+        for dir_entry in children.iter_mut().flatten() {
+            if dir_entry.read_children_path.is_none() || !dir_entry.path_is_symlink() {
+                continue;
+            }
+            let path = dir_entry.path();
+            let Ok(metadata) = fs::metadata(&path) else {
+                continue;
+            };
+            if ancestors.contains(&(metadata.dev(), metadata.ino())) {
+                let target = fs::read_link(&path).unwrap_or_default();
+                symlink_loops.push(SymlinkLoop {
+                    path: path.to_string_lossy().to_string(),
+                    target: target.to_string_lossy().to_string(),
+                });
+                dir_entry.read_children_path = None;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = (dir_path, ancestors);
+    symlink_loops
+}
+
+/// Hashes the content of all files in `children` in parallel on the rayon pool of the walker.
+/// The hex encoded digest is stored in the client state of the entries. Returns the errors
+/// of files which couldn't be read.
+pub fn hash_children<C: ClientState<DirEntryState = EntryState<Option<String>>>>(
+    children: &mut [Result<jwalk_meta::DirEntry<C>, jwalk_meta::Error>],
+    algorithm: HashAlgorithm,
+    mode: HashMode,
+) -> Vec<ScanError> {
+    children
+        .par_iter_mut()
+        .filter_map(|dir_entry_result| {
+            let dir_entry = dir_entry_result.as_mut().ok()?;
+            if !dir_entry.file_type.is_file() {
+                return None;
+            }
+            let path = dir_entry.path();
+            match hash_file(&path, algorithm, mode) {
+                Ok(digest) => {
+                    dir_entry.client_state.data = Some(digest);
+                    None
+                }
+                Err(e) => Some(ScanError::from_io(&path, ErrorPhase::Hash, &e)),
+            }
+        })
+        .collect()
+}
@@ -1,54 +1,236 @@
-use std::collections::HashSet;
+use std::collections::{HashSet, VecDeque};
+use std::ffi::OsStr;
 use std::fs::Metadata;
 use std::io::Error;
-use std::path::Path;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Instant;
+use std::time::{Duration, Instant, SystemTime};
 
-use flume::{Receiver, Sender, unbounded};
-use jwalk_meta::WalkDirGeneric;
+use flume::Receiver;
+use jwalk_meta::{DirEntryIter, WalkDirGeneric};
 
 use crate::Statistics;
-use crate::common::{check_and_expand_path, create_filter, filter_children, get_root_path_len};
-use crate::def::{Filter, Options, ReturnType};
+use crate::common::{
+    EntryState, ReadDirState, ResultSender, check_and_expand_path, check_and_expand_paths,
+    create_channel, create_filter, detect_symlink_loops, filter_children, get_root_device,
+    get_root_path_len, mount_point_error, output_entry, prune_mount_points, relative_path,
+    root_error,
+};
+#[cfg(unix)]
+use crate::common::{group_name, user_name};
+use crate::def::histogram::{Histogram, increment, log2_bucket};
+use crate::def::raw_name::os_str_to_string;
+use crate::def::top_n::TopNTracker;
+use crate::def::{
+    DirUsage, EntryFilter, EntryType, Filter, HashMode, Options, PermMatch, ReturnType, ScanError,
+    SymlinkLoop, TimeRange, TopN, UsageTotals, default_age_buckets, default_multi_part_extensions,
+};
+use crate::gitignore::filter_ignored;
 
-fn count_thread(
-    options: Options,
-    filter: Option<Filter>,
-    tx: Sender<Statistics>,
-    stop: Arc<AtomicBool>,
+type CountState = (ReadDirState, EntryState<Option<Result<Metadata, Error>>>);
+type CountDirEntry = jwalk_meta::DirEntry<CountState>;
+
+/// Determines the extension of file names for the per-extension statistics.
+#[derive(Debug)]
+struct ExtensionMatcher {
+    case_sensitive: bool,
+    multi_part: Vec<String>,
+}
+
+impl ExtensionMatcher {
+    fn new(options: &Options) -> Self {
+        ExtensionMatcher {
+            case_sensitive: options.extension_case_sensitive,
+            multi_part: options
+                .multi_part_extensions
+                .iter()
+                .map(|ext| {
+                    let ext = ext.trim_start_matches('.');
+                    match options.extension_case_sensitive {
+                        true => ext.to_string(),
+                        false => ext.to_lowercase(),
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns the extension of a file name without the leading dot. The leading dot of
+    /// hidden files doesn't start an extension. Returns an empty string if there is none.
+    fn extension(&self, file_name: &OsStr) -> String {
+        let file_name = file_name.to_string_lossy();
+        let file_name = match self.case_sensitive {
+            true => file_name.into_owned(),
+            false => file_name.to_lowercase(),
+        };
+        let name = file_name.trim_start_matches('.');
+        for ext in self.multi_part.iter() {
+            if name.len() > ext.len() + 1
+                && name.ends_with(ext.as_str())
+                && name.as_bytes()[name.len() - ext.len() - 1] == b'.'
+            {
+                return ext.clone();
+            }
+        }
+        match name.rfind('.') {
+            Some(pos) => name[pos + 1..].to_string(),
+            None => String::new(),
+        }
+    }
+}
+
+/// Adds a file to the totals of its owner. The name of the owner is resolved only once,
+/// when the ID occurs first.
+#[cfg(unix)]
+#[inline]
+fn add_owner(
+    owners: &mut std::collections::BTreeMap<u32, crate::OwnerStats>,
+    id: u32,
+    size: u64,
+    usage: u64,
+    resolve: Option<fn(u32) -> Option<String>>,
 ) {
-    let mut statistics = Statistics::new();
+    let owner_stats = owners.entry(id).or_insert_with(|| crate::OwnerStats {
+        name: resolve.and_then(|resolve| resolve(id)),
+        ..Default::default()
+    });
+    owner_stats.files += 1;
+    owner_stats.size += size;
+    owner_stats.usage += usage;
+}
 
-    let dir_entry: jwalk_meta::DirEntry<((), Option<Result<Metadata, Error>>)> =
-        jwalk_meta::DirEntry::from_path(
-            0,
-            &options.root_path,
-            true,
-            true,
-            options.follow_links,
-            Arc::new(Vec::new()),
-        )
-        .unwrap();
+#[derive(Debug)]
+struct Counter {
+    statistics: Statistics,
+    file_indexes: HashSet<u64>,
+    extension_matcher: Option<ExtensionMatcher>,
+    #[cfg_attr(not(unix), allow(dead_code))]
+    owner_stats: bool,
+    #[cfg_attr(not(unix), allow(dead_code))]
+    resolve_names: bool,
+    histograms: bool,
+    /// Reference time for the age histograms.
+    now: SystemTime,
+}
 
-    if !dir_entry.file_type.is_dir() {
-        if dir_entry.file_type.is_symlink() {
-            statistics.slinks += 1;
-            statistics.usage += 4096;
-            statistics.size += 4096;
-        } else if dir_entry.file_type.is_file() {
+impl Counter {
+    fn new(options: &Options) -> Self {
+        let mut statistics = Statistics::new();
+        if options.histograms {
+            statistics.mtime_histogram = Histogram::new(&options.age_buckets);
+            statistics.atime_histogram = Histogram::new(&options.age_buckets);
+        }
+        Counter {
+            statistics,
+            file_indexes: HashSet::new(),
+            extension_matcher: match options.extension_stats {
+                true => Some(ExtensionMatcher::new(options)),
+                false => None,
+            },
+            owner_stats: options.owner_stats,
+            resolve_names: options.resolve_names,
+            histograms: options.histograms,
+            now: SystemTime::now(),
+        }
+    }
+
+    /// Adds an entry to the statistics. Returns `false` if the entry is a hardlink to
+    /// an already counted file. Such hardlinks are not added to the histograms.
+    #[inline]
+    fn add(&mut self, dir_entry: &CountDirEntry) -> bool {
+        let statistics = &mut self.statistics;
+        let file_type = dir_entry.file_type;
+        if file_type.is_file() {
             statistics.files += 1;
-            if let Some(ref metadata) = dir_entry.metadata {
-                let file_size = metadata.size;
-                let mut blocks = file_size >> 12;
-                if blocks << 12 < file_size {
-                    blocks += 1;
+            let file_size = dir_entry.metadata.as_ref().map_or(0, |m| m.size);
+            let file_usage = entry_usage(dir_entry);
+            statistics.usage += file_usage;
+            statistics.size += file_size;
+            if let Some(ref metadata) = dir_entry.metadata_ext {
+                #[cfg(unix)]
+                {
+                    if metadata.st_nlink > 1 {
+                        if self.file_indexes.contains(&metadata.st_ino) {
+                            statistics.hlinks += 1;
+                            statistics.files -= 1;
+                            return false;
+                        } else {
+                            self.file_indexes.insert(metadata.st_ino);
+                        }
+                    }
                 }
-                statistics.usage += blocks << 12;
-                statistics.size += file_size;
+                #[cfg(windows)]
+                {
+                    if let Some(nlink) = metadata.number_of_links {
+                        if nlink > 1 {
+                            if let Some(ino) = metadata.file_index {
+                                if self.file_indexes.contains(&ino) {
+                                    statistics.hlinks += 1;
+                                    statistics.files -= 1;
+                                    return false;
+                                } else {
+                                    self.file_indexes.insert(ino);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            if let Some(ref matcher) = self.extension_matcher {
+                let extension_stats = statistics
+                    .extensions
+                    .entry(matcher.extension(&dir_entry.file_name))
+                    .or_default();
+                extension_stats.count += 1;
+                extension_stats.size += file_size;
+                extension_stats.usage += file_usage;
             }
+            if self.histograms {
+                increment(&mut statistics.size_histogram, log2_bucket(file_size));
+                if let Some(ref metadata) = dir_entry.metadata {
+                    let age = |time: SystemTime| {
+                        self.now
+                            .duration_since(time)
+                            .map(|age| age.as_secs())
+                            .unwrap_or(0)
+                    };
+                    if let Some(modified) = metadata.modified {
+                        statistics.mtime_histogram.add(age(modified));
+                    }
+                    if let Some(accessed) = metadata.accessed {
+                        statistics.atime_histogram.add(age(accessed));
+                    }
+                }
+            }
+            #[cfg(unix)]
+            if self.owner_stats
+                && let Some(ref metadata) = dir_entry.metadata_ext
+            {
+                add_owner(
+                    &mut statistics.users,
+                    metadata.st_uid,
+                    file_size,
+                    file_usage,
+                    self.resolve_names.then_some(user_name),
+                );
+                add_owner(
+                    &mut statistics.groups,
+                    metadata.st_gid,
+                    file_size,
+                    file_usage,
+                    self.resolve_names.then_some(group_name),
+                );
+            }
+        } else if file_type.is_dir() {
+            statistics.dirs += 1;
+            statistics.usage += 4096;
+            statistics.size += 4096;
+        } else if file_type.is_symlink() {
+            statistics.slinks += 1;
+            statistics.usage += 4096;
+            statistics.size += 4096;
         } else {
             #[cfg(unix)]
             if let Some(ref metadata) = dir_entry.metadata_ext {
@@ -63,152 +245,401 @@ fn count_thread(
             statistics.usage += 4096;
             statistics.size += 4096;
         }
-        statistics.duration = 0.01;
-        let _ = tx.send(statistics);
-        return;
-    }
-
-    let mut dirs: i32 = 0;
-    let mut files: i32 = 0;
-    let mut slinks: i32 = 0;
-    let mut hlinks: i32 = 0;
-    let mut size: u64 = 0;
-    let mut usage: u64 = 0;
-    #[cfg(unix)]
-    let mut devices: i32 = 0;
-    #[cfg(unix)]
-    let mut pipes: i32 = 0;
-    let mut cnt: i32 = 0;
-    let start_time = Instant::now();
-    let mut update_time = start_time;
-    let mut file_indexes: HashSet<u64> = HashSet::new();
-    let root_path_len = get_root_path_len(&options.root_path);
-    let max_file_cnt = options.max_file_cnt as i32;
-    for result in WalkDirGeneric::<((), Option<Result<Metadata, Error>>)>::new(&options.root_path)
-        .skip_hidden(options.skip_hidden)
-        .sort(false)
-        .max_depth(options.max_depth)
-        .read_metadata(true)
-        .read_metadata_ext(options.return_type == ReturnType::Ext)
-        .process_read_dir(move |_, root_dir, _, children| {
-            if let Some(root_dir) = root_dir.to_str() {
-                if root_dir.len() + 1 < root_path_len {
-                    return;
-                }
-            } else {
+        if self.histograms {
+            increment(&mut statistics.depth_histogram, dir_entry.depth);
+        }
+        true
+    }
+}
+
+/// Returns the usage on disk of an entry as it is added to `Statistics::usage`: the size of
+/// files rounded up to blocks of 4096 bytes and one block for all other entries.
+#[inline]
+fn entry_usage(dir_entry: &CountDirEntry) -> u64 {
+    match dir_entry.file_type.is_file() {
+        true => dir_entry
+            .metadata
+            .as_ref()
+            .map_or(0, |m| m.size.div_ceil(4096) * 4096),
+        false => 4096,
+    }
+}
+
+/// Returns the totals of a single entry.
+#[inline]
+fn entry_totals(dir_entry: &CountDirEntry) -> UsageTotals {
+    UsageTotals {
+        files: dir_entry.file_type.is_file() as u64,
+        dirs: 0,
+        size: dir_entry.metadata.as_ref().map_or(0, |m| m.size),
+        usage: entry_usage(dir_entry),
+    }
+}
+
+/// Aggregates the disk usage of each directory. The walker yields the entries depth first,
+/// so a directory is finished as soon as an entry which is not a descendant is yielded.
+#[derive(Debug)]
+struct DirUsageTracker {
+    root_path_len: usize,
+    multi_root: bool,
+    stack: Vec<DirUsage>,
+    /// If `false` finished directories are only passed to the top-N tracker.
+    keep_finished: bool,
+    finished: Vec<DirUsage>,
+}
+
+impl DirUsageTracker {
+    fn new(multi_root: bool, keep_finished: bool) -> Self {
+        DirUsageTracker {
+            root_path_len: 0,
+            multi_root,
+            stack: Vec::new(),
+            keep_finished,
+            finished: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, dir_entry: &CountDirEntry, counted: bool, top: Option<&mut TopNTracker>) {
+        self.finish_dirs(dir_entry.depth, top);
+        let mut totals = match counted {
+            true => entry_totals(dir_entry),
+            false => UsageTotals::default(),
+        };
+        if !dir_entry.file_type.is_dir() {
+            if let Some(parent) = self.stack.last_mut() {
+                parent.direct.add(&totals);
+            }
+            return;
+        }
+        if let Some(parent) = self.stack.last_mut() {
+            parent.direct.dirs += 1;
+        }
+        totals.files = 0;
+        let (path, path_bytes) = self.entry_path(dir_entry);
+        self.stack.push(DirUsage {
+            path,
+            path_bytes,
+            depth: dir_entry.depth,
+            direct: totals,
+            recursive: UsageTotals::default(),
+        });
+    }
+
+    /// Returns the path of an entry relative to the root path. In multi-root mode the full path.
+    fn entry_path(&self, dir_entry: &CountDirEntry) -> (String, Option<Vec<u8>>) {
+        let path = dir_entry.path();
+        match self.multi_root {
+            true => os_str_to_string(path.as_os_str()),
+            false => os_str_to_string(relative_path(&path, self.root_path_len).as_os_str()),
+        }
+    }
+
+    /// Finishes all directories with a depth greater or equal than `depth`.
+    fn finish_dirs(&mut self, depth: usize, mut top: Option<&mut TopNTracker>) {
+        while self.stack.last().is_some_and(|dir| dir.depth >= depth) {
+            let mut dir_usage = self.stack.pop().unwrap();
+            let direct = dir_usage.direct;
+            dir_usage.recursive.add(&direct);
+            if let Some(parent) = self.stack.last_mut() {
+                parent.recursive.add(&dir_usage.recursive);
+            }
+            if let Some(ref mut top) = top {
+                let recursive = dir_usage.recursive;
+                top.add_dir(recursive.size, recursive.usage, || {
+                    (dir_usage.path.clone(), dir_usage.path_bytes.clone())
+                });
+            }
+            if self.keep_finished {
+                self.finished.push(dir_usage);
+            }
+        }
+    }
+}
+
+/// Iterator returned by `Count::iter`.
+///
+/// The file tree is walked on the calling thread. Intermediate statistics are yielded
+/// every 1000 entries or 10ms. The last item contains the final statistics.
+pub struct CountIter {
+    options: Options,
+    filter: Option<Filter>,
+    roots: std::vec::IntoIter<PathBuf>,
+    iter: Option<DirEntryIter<CountState>>,
+    counter: Counter,
+    du: Option<DirUsageTracker>,
+    top: Option<TopNTracker>,
+    mount_points: Arc<Mutex<Vec<String>>>,
+    symlink_loops: Arc<Mutex<Vec<SymlinkLoop>>>,
+    root_path_len: usize,
+    max_file_cnt: i32,
+    cnt: i32,
+    start_time: Instant,
+    update_time: Instant,
+    finished: bool,
+}
+
+impl CountIter {
+    fn new(options: &Options, filter: Option<Filter>) -> Self {
+        let start_time = Instant::now();
+        CountIter {
+            options: options.clone(),
+            filter,
+            roots: options.roots().into_iter(),
+            iter: None,
+            counter: Counter::new(options),
+            du: match options.du || options.top_n > 0 {
+                true => Some(DirUsageTracker::new(options.is_multi_root(), options.du)),
+                false => None,
+            },
+            top: match options.top_n {
+                0 => None,
+                n => Some(TopNTracker::new(n)),
+            },
+            mount_points: Arc::new(Mutex::new(Vec::new())),
+            symlink_loops: Arc::new(Mutex::new(Vec::new())),
+            root_path_len: 0,
+            max_file_cnt: options.max_file_cnt as i32,
+            cnt: 0,
+            start_time,
+            update_time: start_time,
+            finished: false,
+        }
+    }
+
+    fn start_root(&mut self, root_path: &Path) {
+        let dir_entry: CountDirEntry = match jwalk_meta::DirEntry::from_path(
+            0,
+            root_path,
+            true,
+            true,
+            self.options.follow_links,
+            Arc::new(Vec::new()),
+        ) {
+            Ok(dir_entry) => dir_entry,
+            Err(e) => {
+                self.counter
+                    .statistics
+                    .errors
+                    .push(root_error(root_path, &e));
                 return;
             }
-            filter_children(children, &filter, root_path_len);
-        })
-    {
-        if stop.load(Ordering::Relaxed) {
-            break;
+        };
+
+        if !dir_entry.file_type.is_dir() {
+            if self.counter.add(&dir_entry)
+                && let Some(ref mut top) = self.top
+            {
+                let totals = entry_totals(&dir_entry);
+                top.add_file(totals.size, totals.usage, || {
+                    os_str_to_string(root_path.as_os_str())
+                });
+            }
+            return;
         }
-        match &result {
-            Ok(v) => {
-                if v.depth == 0 {
-                    continue;
-                }
-                let file_type = v.file_type;
-                if file_type.is_file() {
-                    files += 1;
-                    if let Some(ref metadata) = v.metadata {
-                        let file_size = metadata.size;
-                        let mut blocks = file_size >> 12;
-                        if blocks << 12 < file_size {
-                            blocks += 1;
+
+        let root_path_len = get_root_path_len(root_path);
+        self.root_path_len = root_path_len;
+        if let Some(ref mut du) = self.du {
+            du.root_path_len = root_path_len;
+        }
+        let root_device = get_root_device(root_path, self.options.one_file_system);
+        let follow_links = self.options.follow_links;
+        let mount_points = self.mount_points.clone();
+        let symlink_loops = self.symlink_loops.clone();
+        let filter_needs_metadata_ext =
+            self.filter.as_ref().is_some_and(Filter::needs_metadata_ext);
+        let filter = self.filter.clone();
+        let gitignore = self.options.gitignore;
+        self.iter = Some(
+            WalkDirGeneric::<CountState>::new(root_path)
+                .skip_hidden(self.options.skip_hidden)
+                .follow_links(self.options.follow_links)
+                .sort(false)
+                .max_depth(self.options.max_depth)
+                .read_metadata(true)
+                .read_metadata_ext(
+                    self.options.return_type == ReturnType::Ext
+                        || self.options.du
+                        || self.options.top_n > 0
+                        || self.options.owner_stats
+                        || filter_needs_metadata_ext,
+                )
+                .process_read_dir(move |_, root_dir, state, children| {
+                    if root_dir.as_os_str().len() + 1 < root_path_len {
+                        return;
+                    }
+                    if gitignore {
+                        filter_ignored(children, root_dir, &mut state.ignores);
+                    }
+                    filter_children(children, &filter, root_path_len);
+                    let crossed = prune_mount_points(children, root_device);
+                    if !crossed.is_empty() {
+                        mount_points.lock().unwrap().extend(crossed);
+                    }
+                    if follow_links {
+                        let detected = detect_symlink_loops(root_dir, &mut state.dir_ids, children);
+                        if !detected.is_empty() {
+                            symlink_loops.lock().unwrap().extend(detected);
                         }
-                        usage += blocks << 12;
-                        size += file_size;
                     }
-                    if let Some(ref metadata) = v.metadata_ext {
-                        #[cfg(unix)]
-                        {
-                            if metadata.st_nlink > 1 {
-                                if file_indexes.contains(&metadata.st_ino) {
-                                    hlinks += 1;
-                                    files -= 1;
-                                } else {
-                                    file_indexes.insert(metadata.st_ino);
-                                }
+                })
+                .into_iter(),
+        );
+    }
+
+    fn update_statistics(&mut self) -> Statistics {
+        let statistics = &mut self.counter.statistics;
+        for mount_point in self.mount_points.lock().unwrap().drain(..) {
+            statistics.errors.push(mount_point_error(mount_point));
+        }
+        statistics
+            .symlink_loops
+            .append(&mut self.symlink_loops.lock().unwrap());
+        statistics.duration = self.start_time.elapsed().as_secs_f64();
+        statistics.clone()
+    }
+
+    /// Returns the largest files and directories counted so far. Only available if
+    /// `top_n` is enabled. A directory is reported as soon as it has been counted completely.
+    pub fn largest(&self) -> Option<TopN> {
+        self.top.as_ref().map(|top| top.report())
+    }
+
+    /// Returns the disk usage of the directories which were finished since the last call.
+    /// Only filled in du mode.
+    pub fn take_dir_usages(&mut self) -> Vec<DirUsage> {
+        match self.du {
+            Some(ref mut du) => std::mem::take(&mut du.finished),
+            None => Vec::new(),
+        }
+    }
+}
+
+impl Iterator for CountIter {
+    type Item = Statistics;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        loop {
+            let Some(ref mut iter) = self.iter else {
+                match self.roots.next() {
+                    Some(root_path) => {
+                        self.start_root(&root_path);
+                        continue;
+                    }
+                    None => break,
+                }
+            };
+            for result in iter.by_ref() {
+                match result {
+                    Ok(v) => {
+                        if v.depth == 0 {
+                            // Like in the statistics the root directory itself isn't counted
+                            if let Some(ref mut du) = self.du {
+                                du.add(&v, false, self.top.as_mut());
                             }
+                            continue;
+                        }
+                        let counted = output_entry(&v, &self.filter, self.root_path_len)
+                            && self.counter.add(&v);
+                        if let Some(ref mut du) = self.du {
+                            du.add(&v, counted, self.top.as_mut());
                         }
-                        #[cfg(windows)]
+                        if counted
+                            && v.file_type.is_file()
+                            && let Some(ref mut top) = self.top
                         {
-                            if let Some(nlink) = metadata.number_of_links {
-                                if nlink > 1 {
-                                    if let Some(ino) = metadata.file_index {
-                                        if file_indexes.contains(&ino) {
-                                            hlinks += 1;
-                                            files -= 1;
-                                        } else {
-                                            file_indexes.insert(ino);
-                                        }
-                                    }
-                                }
-                            }
+                            let totals = entry_totals(&v);
+                            let du = self.du.as_ref().unwrap();
+                            top.add_file(totals.size, totals.usage, || du.entry_path(&v));
                         }
-                    }
-                } else if file_type.is_dir() {
-                    dirs += 1;
-                    usage += 4096;
-                    size += 4096;
-                } else if file_type.is_symlink() {
-                    slinks += 1;
-                    usage += 4096;
-                    size += 4096;
-                } else {
-                    #[cfg(unix)]
-                    if let Some(ref metadata) = v.metadata_ext {
+                        if !v.file_type.is_dir()
+                            && self.max_file_cnt > 0
+                            && self.counter.statistics.files > self.max_file_cnt
                         {
-                            if metadata.st_rdev > 0 {
-                                devices += 1;
-                            } else if (metadata.st_mode & 4096) != 0 {
-                                pipes += 1;
-                            }
+                            self.roots = Vec::new().into_iter();
+                            break;
+                        }
+                        self.cnt += 1;
+                        if self.cnt >= 1000 || self.update_time.elapsed().as_millis() >= 10 {
+                            self.cnt = 0;
+                            self.update_time = Instant::now();
+                            return Some(self.update_statistics());
                         }
                     }
-                    usage += 4096;
-                    size += 4096;
-                }
-                cnt += 1;
-                if cnt >= 1000 || update_time.elapsed().as_millis() >= 10 {
-                    statistics.dirs = dirs;
-                    statistics.files = files;
-                    statistics.slinks = slinks;
-                    statistics.hlinks = hlinks;
-                    statistics.size = size;
-                    statistics.usage = usage;
-                    statistics.duration = start_time.elapsed().as_secs_f64();
-                    #[cfg(unix)]
-                    {
-                        statistics.devices = devices;
-                        statistics.pipes = pipes;
-                    }
-                    let _ = tx.send(statistics.clone());
-                    cnt = 0;
-                    update_time = Instant::now();
-                }
-                if !file_type.is_dir() && max_file_cnt > 0 && files > max_file_cnt {
-                    break;
+                    Err(e) => self.counter.statistics.errors.push(ScanError::from(&e)),
                 }
             }
-            Err(e) => statistics.errors.push(e.to_string()), // TODO: Need to fetch failed path from somewhere
-        }
-    }
-    statistics.dirs = dirs;
-    statistics.files = files;
-    statistics.slinks = slinks;
-    statistics.hlinks = hlinks;
-    statistics.size = size;
-    statistics.usage = usage;
-    statistics.duration = start_time.elapsed().as_secs_f64();
-    #[cfg(unix)]
-    {
-        statistics.devices = devices;
-        statistics.pipes = pipes;
-    }
-    let _ = tx.send(statistics);
+            if let Some(ref mut du) = self.du {
+                du.finish_dirs(0, self.top.as_mut());
+            }
+            self.iter = None;
+        }
+        self.finished = true;
+        Some(self.update_statistics())
+    }
+}
+
+/// Iterator returned by `Count::dir_usage_iter`.
+///
+/// Yields the disk usage of each directory as soon as the directory and all of its
+/// descendants have been counted. Subdirectories are yielded before their parents.
+pub struct DirUsageIter {
+    iter: CountIter,
+    pending: VecDeque<DirUsage>,
+    statistics: Statistics,
+}
+
+impl DirUsageIter {
+    /// Returns the statistics of the entries counted so far.
+    pub fn statistics(&self) -> &Statistics {
+        &self.statistics
+    }
+}
+
+impl Iterator for DirUsageIter {
+    type Item = DirUsage;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(dir_usage) = self.pending.pop_front() {
+                return Some(dir_usage);
+            }
+            self.statistics = self.iter.next()?;
+            self.pending.extend(self.iter.take_dir_usages());
+        }
+    }
+}
+
+#[derive(Debug)]
+enum CountResult {
+    Statistics(Statistics),
+    DirUsage(DirUsage),
+    Largest(TopN),
+}
+
+fn count_thread(
+    options: Options,
+    filter: Option<Filter>,
+    tx: ResultSender<CountResult>,
+    stop: Arc<AtomicBool>,
+) {
+    let mut iter = CountIter::new(&options, filter);
+    while let Some(statistics) = iter.next() {
+        for dir_usage in iter.take_dir_usages() {
+            if !tx.send(CountResult::DirUsage(dir_usage)) {
+                return;
+            }
+        }
+        if !tx.send(CountResult::Statistics(statistics)) || stop.load(Ordering::Relaxed) {
+            break;
+        }
+    }
+    if let Some(largest) = iter.largest() {
+        let _ = tx.send(CountResult::Largest(largest));
+    }
 }
 
 #[derive(Debug)]
@@ -217,20 +648,37 @@ pub struct Count {
     options: Options,
     // Results
     pub statistics: Statistics,
+    dir_usages: Vec<DirUsage>,
+    dir_usages_read: usize,
+    largest: Option<TopN>,
     duration: Arc<Mutex<f64>>,
     finished: Arc<AtomicBool>,
+    peak_queue_len: Arc<AtomicUsize>,
     // Internal
     thr: Option<thread::JoinHandle<()>>,
     stop: Arc<AtomicBool>,
-    rx: Option<Receiver<Statistics>>,
+    rx: Option<Receiver<CountResult>>,
 }
 
 impl Count {
     pub fn new<P: AsRef<Path>>(root_path: P) -> Result<Self, Error> {
-        Ok(Count {
+        Ok(Self::from_roots(check_and_expand_path(root_path)?, None))
+    }
+
+    /// Creates an instance which counts multiple file trees in one run. Overlapping root
+    /// paths are removed. The statistics of all file trees are combined.
+    pub fn new_multi<P: AsRef<Path>>(root_paths: &[P]) -> Result<Self, Error> {
+        let root_paths = check_and_expand_paths(root_paths)?;
+        Ok(Self::from_roots(root_paths[0].clone(), Some(root_paths)))
+    }
+
+    fn from_roots(root_path: PathBuf, root_paths: Option<Vec<PathBuf>>) -> Self {
+        Count {
             options: Options {
-                root_path: check_and_expand_path(root_path)?,
+                root_path,
+                root_paths,
                 sorted: false,
+                sort_by: None,
                 skip_hidden: false,
                 max_depth: usize::MAX,
                 max_file_cnt: usize::MAX,
@@ -238,17 +686,55 @@ impl Count {
                 dir_exclude: None,
                 file_include: None,
                 file_exclude: None,
+                dir_include_regex: None,
+                dir_exclude_regex: None,
+                file_include_regex: None,
+                file_exclude_regex: None,
+                min_size: None,
+                max_size: None,
+                mtime: None,
+                ctime: None,
+                atime: None,
+                entry_types: None,
+                perm: None,
+                uid: None,
+                gid: None,
+                nouser: false,
+                nogroup: false,
+                min_links: None,
+                max_links: None,
+                filter_expr: None,
+                entry_filter: None,
                 case_sensitive: false,
+                file_match_path: false,
                 follow_links: false,
+                one_file_system: false,
+                gitignore: false,
                 return_type: ReturnType::Base,
+                channel_capacity: 0,
+                du: false,
+                top_n: 0,
+                hash_algorithm: None,
+                hash_mode: HashMode::Full,
+                extension_stats: false,
+                extension_case_sensitive: false,
+                multi_part_extensions: default_multi_part_extensions(),
+                owner_stats: false,
+                resolve_names: false,
+                histograms: false,
+                age_buckets: default_age_buckets(),
             },
             statistics: Statistics::new(),
+            dir_usages: Vec::new(),
+            dir_usages_read: 0,
+            largest: None,
             duration: Arc::new(Mutex::new(0.0)),
             finished: Arc::new(AtomicBool::new(false)),
+            peak_queue_len: Arc::new(AtomicUsize::new(0)),
             thr: None,
             stop: Arc::new(AtomicBool::new(false)),
             rx: None,
-        })
+        }
     }
 
     /// Skip hidden entries. Enabled by default.
@@ -307,18 +793,157 @@ impl Count {
         self
     }
 
+    /// Set directory include filter with regular expressions
+    pub fn dir_include_regex(mut self, dir_include_regex: Option<Vec<String>>) -> Self {
+        self.options.dir_include_regex = dir_include_regex;
+        self
+    }
+
+    /// Set directory exclude filter with regular expressions
+    pub fn dir_exclude_regex(mut self, dir_exclude_regex: Option<Vec<String>>) -> Self {
+        self.options.dir_exclude_regex = dir_exclude_regex;
+        self
+    }
+
+    /// Set file include filter with regular expressions
+    pub fn file_include_regex(mut self, file_include_regex: Option<Vec<String>>) -> Self {
+        self.options.file_include_regex = file_include_regex;
+        self
+    }
+
+    /// Set file exclude filter with regular expressions
+    pub fn file_exclude_regex(mut self, file_exclude_regex: Option<Vec<String>>) -> Self {
+        self.options.file_exclude_regex = file_exclude_regex;
+        self
+    }
+
+    /// Set minimum size of files in bytes
+    pub fn min_size(mut self, min_size: Option<u64>) -> Self {
+        self.options.min_size = min_size;
+        self
+    }
+
+    /// Set maximum size of files in bytes
+    pub fn max_size(mut self, max_size: Option<u64>) -> Self {
+        self.options.max_size = max_size;
+        self
+    }
+
+    /// Set types of the entries to count. If `None` (default) all entries are counted.
+    /// Directories are descended into also if they are not counted.
+    pub fn entry_types(mut self, entry_types: Option<Vec<EntryType>>) -> Self {
+        self.options.entry_types = entry_types;
+        self
+    }
+
+    /// Set test of the permission bits like `find -perm`. Only supported on Unix.
+    pub fn perm(mut self, perm: Option<PermMatch>) -> Self {
+        self.options.perm = perm;
+        self
+    }
+
+    /// Set user ID of the owner. Only supported on Unix.
+    pub fn uid(mut self, uid: Option<u32>) -> Self {
+        self.options.uid = uid;
+        self
+    }
+
+    /// Set group ID of the owner. Only supported on Unix.
+    pub fn gid(mut self, gid: Option<u32>) -> Self {
+        self.options.gid = gid;
+        self
+    }
+
+    /// Only entries whose user ID has no user name are counted. Only supported on Unix.
+    pub fn nouser(mut self, nouser: bool) -> Self {
+        self.options.nouser = nouser;
+        self
+    }
+
+    /// Only entries whose group ID has no group name are counted. Only supported on Unix.
+    pub fn nogroup(mut self, nogroup: bool) -> Self {
+        self.options.nogroup = nogroup;
+        self
+    }
+
+    /// Set minimum number of hard links
+    pub fn min_links(mut self, min_links: Option<u64>) -> Self {
+        self.options.min_links = min_links;
+        self
+    }
+
+    /// Set maximum number of hard links
+    pub fn max_links(mut self, max_links: Option<u64>) -> Self {
+        self.options.max_links = max_links;
+        self
+    }
+
+    /// Set find-style filter expression, e.g. `name "*.log" and size > 10M`. Directories are
+    /// descended into also if they are not counted.
+    pub fn filter_expr(mut self, filter_expr: Option<String>) -> Self {
+        self.options.filter_expr = filter_expr;
+        self
+    }
+
+    /// Set custom filter, which decides for each entry if it is counted, skipped or pruned
+    /// together with its subtree. It is applied after all other filters.
+    pub fn entry_filter(mut self, entry_filter: Option<Arc<dyn EntryFilter>>) -> Self {
+        self.options.entry_filter = entry_filter;
+        self
+    }
+
+    /// Set range of the modification time of files
+    pub fn mtime(mut self, mtime: Option<TimeRange>) -> Self {
+        self.options.mtime = mtime;
+        self
+    }
+
+    /// Set range of the creation time of files
+    pub fn ctime(mut self, ctime: Option<TimeRange>) -> Self {
+        self.options.ctime = ctime;
+        self
+    }
+
+    /// Set range of the access time of files
+    pub fn atime(mut self, atime: Option<TimeRange>) -> Self {
+        self.options.atime = atime;
+        self
+    }
+
     /// Set case sensitive filename filtering
     pub fn case_sensitive(mut self, case_sensitive: bool) -> Self {
         self.options.case_sensitive = case_sensitive;
         self
     }
 
+    /// Match the file filters against the path relative to the root path instead of the file
+    /// name, e.g. `src/**/*.rs`
+    pub fn file_match_path(mut self, file_match_path: bool) -> Self {
+        self.options.file_match_path = file_match_path;
+        self
+    }
+
     /// Set follow symlinks
     pub fn follow_links(mut self, follow_links: bool) -> Self {
         self.options.follow_links = follow_links;
         self
     }
 
+    /// Don't descend into directories on other file systems than the root path.
+    /// Crossed mount points are reported as errors. Only supported on Unix.
+    pub fn one_file_system(mut self, one_file_system: bool) -> Self {
+        self.options.one_file_system = one_file_system;
+        self
+    }
+
+    /// Skip entries which are ignored by `.gitignore`, `.ignore` and `.git/info/exclude` files,
+    /// like git does. The ignore files of the parent directories up to the root of the git
+    /// repository are respected too.
+    pub fn gitignore(mut self, gitignore: bool) -> Self {
+        self.options.gitignore = gitignore;
+        self
+    }
+
     /// Set extended file type counting
     pub fn extended(mut self, extended: bool) -> Self {
         self.options.return_type = match extended {
@@ -328,6 +953,86 @@ impl Count {
         self
     }
 
+    /// Set capacity of the results channel. If `0` (default) the channel is unbounded.
+    ///
+    /// With a bounded channel the counting thread blocks while the channel is full.
+    pub fn channel_capacity(mut self, channel_capacity: usize) -> Self {
+        self.options.channel_capacity = channel_capacity;
+        self
+    }
+
+    /// Report the disk usage of each directory like `du` does: apparent size, usage on
+    /// disk and number of files and directories, both of the direct entries and recursive.
+    /// The results are available as soon as a directory has been counted completely.
+    ///
+    /// Extended metadata is read in this mode, so hardlinks are counted only once.
+    pub fn du(mut self, du: bool) -> Self {
+        self.options.du = du;
+        self
+    }
+
+    /// Keep the `top_n` largest files and directories by apparent size and by usage on disk.
+    /// Directories are ranked by the totals of all of their descendants. If `0` (default)
+    /// the report is disabled.
+    ///
+    /// Only bounded heaps are kept in memory, so this also works for huge file trees.
+    pub fn top_n(mut self, top_n: usize) -> Self {
+        self.options.top_n = top_n;
+        self
+    }
+
+    /// Collect the number of files, apparent size and usage on disk per file extension
+    /// in `Statistics::extensions`. Disabled by default.
+    pub fn extension_stats(mut self, extension_stats: bool) -> Self {
+        self.options.extension_stats = extension_stats;
+        self
+    }
+
+    /// Set case sensitive file extensions. If `false` (default) the extensions are folded
+    /// to lower case.
+    pub fn extension_case_sensitive(mut self, extension_case_sensitive: bool) -> Self {
+        self.options.extension_case_sensitive = extension_case_sensitive;
+        self
+    }
+
+    /// Set extensions which consist of multiple parts, e.g. `tar.gz`. Defaults to
+    /// `MULTI_PART_EXTENSIONS`.
+    pub fn multi_part_extensions(mut self, multi_part_extensions: Vec<String>) -> Self {
+        self.options.multi_part_extensions = multi_part_extensions;
+        self
+    }
+
+    /// Collect the number of files, apparent size and usage on disk per user and group ID
+    /// in `Statistics::users` and `Statistics::groups`. Disabled by default.
+    ///
+    /// Extended metadata is read in this mode, so hardlinks are counted only once.
+    /// Only supported on Unix.
+    pub fn owner_stats(mut self, owner_stats: bool) -> Self {
+        self.options.owner_stats = owner_stats;
+        self
+    }
+
+    /// Resolve the user and group IDs of `owner_stats` to names. Each ID is resolved
+    /// only once per run.
+    pub fn resolve_names(mut self, resolve_names: bool) -> Self {
+        self.options.resolve_names = resolve_names;
+        self
+    }
+
+    /// Collect histograms of the file sizes in log2 buckets, of the age of the modification
+    /// and access times of the files and of the depth of all entries. Disabled by default.
+    pub fn histograms(mut self, histograms: bool) -> Self {
+        self.options.histograms = histograms;
+        self
+    }
+
+    /// Set the upper bounds of the age buckets in seconds. An additional bucket counts all
+    /// older files. Defaults to `DEFAULT_AGE_BUCKETS` (<1d, <7d, <30d, <1y and older).
+    pub fn age_buckets(mut self, age_buckets: Vec<u64>) -> Self {
+        self.options.age_buckets = age_buckets;
+        self
+    }
+
     /// Same as method `extended`, but without moving the instance
     pub fn set_extended(&mut self, extended: bool) {
         self.options.return_type = match extended {
@@ -338,6 +1043,9 @@ impl Count {
 
     pub fn clear(&mut self) {
         self.statistics.clear();
+        self.dir_usages.clear();
+        self.dir_usages_read = 0;
+        self.largest = None;
         *self.duration.lock().unwrap() = 0.0;
     }
 
@@ -348,7 +1056,11 @@ impl Count {
         self.clear();
         let options = self.options.clone();
         let filter = create_filter(&options)?;
-        let (tx, rx) = unbounded();
+        let (tx, rx) = create_channel(
+            options.channel_capacity,
+            self.stop.clone(),
+            self.peak_queue_len.clone(),
+        );
         self.rx = Some(rx);
         self.stop.store(false, Ordering::Relaxed);
         let stop = self.stop.clone();
@@ -363,8 +1075,55 @@ impl Count {
         Ok(())
     }
 
+    /// Count the file tree on the calling thread instead of a background thread.
+    ///
+    /// The returned iterator yields intermediate statistics. The last item contains the final result.
+    pub fn iter(&self) -> Result<CountIter, Error> {
+        let filter = create_filter(&self.options)?;
+        Ok(CountIter::new(&self.options, filter))
+    }
+
+    /// Count the file tree in du mode on the calling thread and return the disk usage
+    /// of each directory.
+    pub fn dir_usage_iter(&self) -> Result<DirUsageIter, Error> {
+        let filter = create_filter(&self.options)?;
+        let mut options = self.options.clone();
+        options.du = true;
+        Ok(DirUsageIter {
+            iter: CountIter::new(&options, filter),
+            pending: VecDeque::new(),
+            statistics: Statistics::new(),
+        })
+    }
+
+    #[inline]
+    fn add_result(&mut self, result: CountResult) {
+        match result {
+            CountResult::Statistics(statistics) => self.statistics = statistics,
+            CountResult::DirUsage(dir_usage) => self.dir_usages.push(dir_usage),
+            CountResult::Largest(largest) => self.largest = Some(largest),
+        }
+    }
+
+    /// Receive results while the thread is running. Otherwise a thread which is blocked
+    /// by a full bounded channel would never finish.
+    fn receive_while_running(&mut self, thr: &thread::JoinHandle<()>) {
+        if self.options.channel_capacity == 0 {
+            return;
+        }
+        while !thr.is_finished() {
+            let Some(ref rx) = self.rx else {
+                return;
+            };
+            if let Ok(result) = rx.recv_timeout(Duration::from_millis(10)) {
+                self.add_result(result);
+            }
+        }
+    }
+
     pub fn join(&mut self) -> bool {
         if let Some(thr) = self.thr.take() {
+            self.receive_while_running(&thr);
             if let Err(_e) = thr.join() {
                 return false;
             }
@@ -376,6 +1135,7 @@ impl Count {
     pub fn stop(&mut self) -> bool {
         if let Some(thr) = self.thr.take() {
             self.stop.store(true, Ordering::Relaxed);
+            self.receive_while_running(&thr);
             if let Err(_e) = thr.join() {
                 return false;
             }
@@ -385,10 +1145,8 @@ impl Count {
     }
 
     fn receive_all(&mut self) -> Statistics {
-        if let Some(ref rx) = self.rx {
-            while let Ok(s) = rx.try_recv() {
-                self.statistics = s;
-            }
+        while let Some(result) = self.rx.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            self.add_result(result);
         }
         self.statistics.clone()
     }
@@ -420,6 +1178,32 @@ impl Count {
         !self.statistics.errors.is_empty()
     }
 
+    /// Returns the disk usage of the directories counted so far in du mode.
+    ///
+    /// If `only_new` is `true` only the directories which were not returned before are returned.
+    pub fn dir_usages(&mut self, only_new: bool) -> Vec<DirUsage> {
+        self.receive_all();
+        let start = match only_new {
+            true => self.dir_usages_read,
+            false => 0,
+        };
+        self.dir_usages_read = self.dir_usages.len();
+        self.dir_usages[start..].to_vec()
+    }
+
+    /// Returns the largest files and directories. Only available if `top_n` is enabled
+    /// and after the task has finished or was stopped.
+    pub fn largest(&mut self) -> Option<TopN> {
+        self.receive_all();
+        self.largest.clone()
+    }
+
+    /// Returns the highest number of results which were queued in the channel
+    /// during the last run.
+    pub fn peak_queue_len(&self) -> usize {
+        self.peak_queue_len.load(Ordering::Relaxed)
+    }
+
     pub fn duration(&mut self) -> f64 {
         *self.duration.lock().unwrap()
     }
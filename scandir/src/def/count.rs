@@ -1,8 +1,48 @@
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind};
+
 #[cfg(feature = "bincode")]
 use bincode::error::EncodeError;
 #[cfg(feature = "speedy")]
 use speedy::{Readable, Writable};
 
+use super::histogram::{Histogram, add_counts};
+use crate::{ScanError, SymlinkLoop};
+
+/// Totals of the files with the same extension.
+#[cfg_attr(feature = "speedy", derive(Readable, Writable))]
+#[cfg_attr(
+    any(feature = "bincode", feature = "json"),
+    derive(Deserialize, Serialize)
+)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ExtensionStats {
+    /// Number of files.
+    pub count: u64,
+    /// Apparent size in bytes.
+    pub size: u64,
+    /// Usage on disk in bytes.
+    pub usage: u64,
+}
+
+/// Totals of the files owned by the same user or group.
+#[cfg_attr(feature = "speedy", derive(Readable, Writable))]
+#[cfg_attr(
+    any(feature = "bincode", feature = "json"),
+    derive(Deserialize, Serialize)
+)]
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct OwnerStats {
+    /// Name of the user or group. Only set if `resolve_names` is enabled and the ID is known.
+    pub name: Option<String>,
+    /// Number of files.
+    pub files: u64,
+    /// Apparent size in bytes.
+    pub size: u64,
+    /// Usage on disk in bytes.
+    pub usage: u64,
+}
+
 #[cfg_attr(feature = "speedy", derive(Readable, Writable))]
 #[cfg_attr(
     any(feature = "bincode", feature = "json"),
@@ -18,7 +58,26 @@ pub struct Statistics {
     pub pipes: i32,
     pub size: u64,
     pub usage: u64,
-    pub errors: Vec<String>,
+    pub errors: Vec<ScanError>,
+    pub symlink_loops: Vec<SymlinkLoop>,
+    /// Totals per file extension. Files without extension are stored with an empty key.
+    /// Only filled if `extension_stats` is enabled.
+    pub extensions: BTreeMap<String, ExtensionStats>,
+    /// Totals per user ID. Only filled if `owner_stats` is enabled. Only supported on Unix.
+    pub users: BTreeMap<u32, OwnerStats>,
+    /// Totals per group ID. Only filled if `owner_stats` is enabled. Only supported on Unix.
+    pub groups: BTreeMap<u32, OwnerStats>,
+    /// Number of files per log2 size bucket. Bucket `0` counts the empty files and bucket `i`
+    /// the files with a size in `[2^(i-1), 2^i)`. Only filled if `histograms` is enabled.
+    pub size_histogram: Vec<u64>,
+    /// Number of files per age of the modification time in seconds. Only filled if
+    /// `histograms` is enabled.
+    pub mtime_histogram: Histogram,
+    /// Number of files per age of the access time in seconds. Only filled if
+    /// `histograms` is enabled.
+    pub atime_histogram: Histogram,
+    /// Number of entries per depth. Only filled if `histograms` is enabled.
+    pub depth_histogram: Vec<u64>,
     pub duration: f64,
 }
 
@@ -34,6 +93,14 @@ impl Statistics {
             size: 0,
             usage: 0,
             errors: Vec::new(),
+            symlink_loops: Vec::new(),
+            extensions: BTreeMap::new(),
+            users: BTreeMap::new(),
+            groups: BTreeMap::new(),
+            size_histogram: Vec::new(),
+            mtime_histogram: Histogram::default(),
+            atime_histogram: Histogram::default(),
+            depth_histogram: Vec::new(),
             duration: 0.0,
         }
     }
@@ -48,9 +115,66 @@ impl Statistics {
         self.size = 0;
         self.usage = 0;
         self.errors.clear();
+        self.symlink_loops.clear();
+        self.extensions.clear();
+        self.users.clear();
+        self.groups.clear();
+        self.size_histogram.clear();
+        self.mtime_histogram = Histogram::default();
+        self.atime_histogram = Histogram::default();
+        self.depth_histogram.clear();
         self.duration = 0.0;
     }
 
+    /// Adds the statistics of another, disjoint part of a file tree. Returns an `InvalidInput`
+    /// error and leaves the statistics unchanged if the age histograms have different bounds.
+    pub fn merge(&mut self, other: &Statistics) -> Result<(), Error> {
+        if !self.mtime_histogram.can_merge(&other.mtime_histogram)
+            || !self.atime_histogram.can_merge(&other.atime_histogram)
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Age histograms have different bounds",
+            ));
+        }
+        self.dirs += other.dirs;
+        self.files += other.files;
+        self.slinks += other.slinks;
+        self.hlinks += other.hlinks;
+        self.devices += other.devices;
+        self.pipes += other.pipes;
+        self.size += other.size;
+        self.usage += other.usage;
+        self.errors.extend_from_slice(&other.errors);
+        self.symlink_loops.extend_from_slice(&other.symlink_loops);
+        for (extension, other) in other.extensions.iter() {
+            let extension_stats = self.extensions.entry(extension.clone()).or_default();
+            extension_stats.count += other.count;
+            extension_stats.size += other.size;
+            extension_stats.usage += other.usage;
+        }
+        for (owners, others) in [
+            (&mut self.users, &other.users),
+            (&mut self.groups, &other.groups),
+        ] {
+            for (id, other) in others.iter() {
+                let owner_stats = owners.entry(*id).or_default();
+                if owner_stats.name.is_none() {
+                    owner_stats.name = other.name.clone();
+                }
+                owner_stats.files += other.files;
+                owner_stats.size += other.size;
+                owner_stats.usage += other.usage;
+            }
+        }
+        add_counts(&mut self.size_histogram, &other.size_histogram);
+        self.mtime_histogram.merge(&other.mtime_histogram);
+        self.atime_histogram.merge(&other.atime_histogram);
+        add_counts(&mut self.depth_histogram, &other.depth_histogram);
+        self.duration += other.duration;
+        Ok(())
+    }
+
     #[cfg(feature = "json")]
     pub fn to_json(&self) -> serde_json::Result<String> {
         serde_json::to_string(self)
@@ -0,0 +1,81 @@
+use std::ffi::OsString;
+
+#[cfg(feature = "bincode")]
+use bincode::error::EncodeError;
+#[cfg(feature = "speedy")]
+use speedy::{Readable, Writable};
+
+use super::raw_name::bytes_to_os_string;
+
+/// Totals of a directory.
+#[cfg_attr(feature = "speedy", derive(Readable, Writable))]
+#[cfg_attr(
+    any(feature = "bincode", feature = "json"),
+    derive(Deserialize, Serialize)
+)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct UsageTotals {
+    /// Number of files.
+    pub files: u64,
+    /// Number of directories.
+    pub dirs: u64,
+    /// Apparent size in bytes.
+    pub size: u64,
+    /// Usage on disk in bytes.
+    pub usage: u64,
+}
+
+impl UsageTotals {
+    pub fn add(&mut self, other: &UsageTotals) {
+        self.files += other.files;
+        self.dirs += other.dirs;
+        self.size += other.size;
+        self.usage += other.usage;
+    }
+}
+
+/// Disk usage of a directory as reported by `Count` in du mode.
+#[cfg_attr(feature = "speedy", derive(Readable, Writable))]
+#[cfg_attr(
+    any(feature = "bincode", feature = "json"),
+    derive(Deserialize, Serialize)
+)]
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DirUsage {
+    /// Path of the directory relative to the root path. In multi-root mode the full path.
+    pub path: String,
+    /// Raw bytes of the path if it isn't valid UTF-8. `path` then contains a lossy representation.
+    pub path_bytes: Option<Vec<u8>>,
+    /// Depth of the directory. The root path has depth `0`.
+    pub depth: usize,
+    /// Totals of the directory itself and its direct entries.
+    pub direct: UsageTotals,
+    /// Totals of the directory and all of its descendants.
+    pub recursive: UsageTotals,
+}
+
+impl DirUsage {
+    /// Returns the path without loss, also if it isn't valid UTF-8.
+    #[inline]
+    pub fn os_path(&self) -> OsString {
+        match self.path_bytes {
+            Some(ref bytes) => bytes_to_os_string(bytes),
+            None => OsString::from(&self.path),
+        }
+    }
+
+    #[cfg(feature = "speedy")]
+    pub fn to_speedy(&self) -> Result<Vec<u8>, speedy::Error> {
+        self.write_to_vec()
+    }
+
+    #[cfg(feature = "bincode")]
+    pub fn to_vec(&self) -> Result<Vec<u8>, EncodeError> {
+        bincode::serde::encode_to_vec(self, bincode::config::legacy())
+    }
+
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+}
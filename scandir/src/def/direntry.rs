@@ -1,3 +1,4 @@
+use std::ffi::OsString;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 #[cfg(feature = "bincode")]
@@ -5,6 +6,8 @@ use bincode::error::EncodeError;
 #[cfg(feature = "speedy")]
 use speedy::{Readable, Writable};
 
+use super::raw_name::bytes_to_os_string;
+
 #[cfg_attr(feature = "speedy", derive(Readable, Writable))]
 #[cfg_attr(
     any(feature = "bincode", feature = "json"),
@@ -13,6 +16,8 @@ use speedy::{Readable, Writable};
 #[derive(Debug, Clone, PartialEq, Default)]
 pub struct DirEntry {
     pub path: String,
+    /// Raw bytes of the path if it isn't valid UTF-8. `path` then contains a lossy representation.
+    pub path_bytes: Option<Vec<u8>>,
     pub is_symlink: bool,
     pub is_dir: bool,
     pub is_file: bool,
@@ -20,9 +25,20 @@ pub struct DirEntry {
     pub st_mtime: Option<SystemTime>,
     pub st_atime: Option<SystemTime>,
     pub st_size: u64,
+    /// Hex encoded digest of the file content. Only set if hashing is enabled.
+    pub digest: Option<String>,
 }
 
 impl DirEntry {
+    /// Returns the path without loss, also if it isn't valid UTF-8.
+    #[inline]
+    pub fn os_path(&self) -> OsString {
+        match self.path_bytes {
+            Some(ref bytes) => bytes_to_os_string(bytes),
+            None => OsString::from(&self.path),
+        }
+    }
+
     #[inline]
     pub fn ctime(&self) -> f64 {
         let duration = self
@@ -77,6 +93,8 @@ impl DirEntry {
 #[derive(Debug, Clone, PartialEq, Default)]
 pub struct DirEntryExt {
     pub path: String,
+    /// Raw bytes of the path if it isn't valid UTF-8. `path` then contains a lossy representation.
+    pub path_bytes: Option<Vec<u8>>,
     pub is_symlink: bool,
     pub is_dir: bool,
     pub is_file: bool,
@@ -106,9 +124,20 @@ pub struct DirEntryExt {
     pub st_dev: u64,
     /// Device number (for character and block devices on Unix).
     pub st_rdev: u64,
+    /// Hex encoded digest of the file content. Only set if hashing is enabled.
+    pub digest: Option<String>,
 }
 
 impl DirEntryExt {
+    /// Returns the path without loss, also if it isn't valid UTF-8.
+    #[inline]
+    pub fn os_path(&self) -> OsString {
+        match self.path_bytes {
+            Some(ref bytes) => bytes_to_os_string(bytes),
+            None => OsString::from(&self.path),
+        }
+    }
+
     #[inline]
     pub fn ctime(&self) -> f64 {
         let duration = self
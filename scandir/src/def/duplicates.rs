@@ -0,0 +1,72 @@
+use std::ffi::OsString;
+
+#[cfg(feature = "bincode")]
+use bincode::error::EncodeError;
+#[cfg(feature = "speedy")]
+use speedy::{Readable, Writable};
+
+use super::raw_name::{RawName, os_names};
+use crate::ErrorsType;
+
+/// Files with identical content.
+#[cfg_attr(feature = "speedy", derive(Readable, Writable))]
+#[cfg_attr(
+    any(feature = "bincode", feature = "json"),
+    derive(Deserialize, Serialize)
+)]
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DuplicateSet {
+    /// Size of each file in bytes.
+    pub size: u64,
+    /// Hex encoded digest of the file content.
+    pub digest: String,
+    /// Paths of the files. Hardlinks to the same file are only listed once.
+    pub paths: Vec<String>,
+    /// Paths in `paths` which aren't valid UTF-8.
+    pub raw_paths: Vec<RawName>,
+}
+
+impl DuplicateSet {
+    /// Returns the paths without loss, also if they aren't valid UTF-8.
+    pub fn os_paths(&self) -> Vec<OsString> {
+        os_names(&self.paths, &self.raw_paths)
+    }
+
+    /// Returns the number of bytes which could be freed by keeping only one of the files.
+    pub fn reclaimable(&self) -> u64 {
+        self.size * (self.paths.len().saturating_sub(1) as u64)
+    }
+}
+
+/// Report of the duplicate finder.
+#[cfg_attr(feature = "speedy", derive(Readable, Writable))]
+#[cfg_attr(
+    any(feature = "bincode", feature = "json"),
+    derive(Deserialize, Serialize)
+)]
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Duplicates {
+    /// Sets of duplicate files, sorted by reclaimable space in descending order.
+    pub sets: Vec<DuplicateSet>,
+    /// Number of bytes which could be freed by keeping only one file of each set.
+    pub reclaimable: u64,
+    /// Files which couldn't be hashed.
+    pub errors: ErrorsType,
+}
+
+impl Duplicates {
+    #[cfg(feature = "speedy")]
+    pub fn to_speedy(&self) -> Result<Vec<u8>, speedy::Error> {
+        self.write_to_vec()
+    }
+
+    #[cfg(feature = "bincode")]
+    pub fn to_vec(&self) -> Result<Vec<u8>, EncodeError> {
+        bincode::serde::encode_to_vec(self, bincode::config::legacy())
+    }
+
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+}
@@ -0,0 +1,64 @@
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs::FileType;
+use std::path::Path;
+
+use jwalk_meta::{MetaData, MetaDataExt};
+
+/// Decision of an `EntryFilter` about an entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterAction {
+    /// Return the entry and descend into it if it is a directory.
+    Keep,
+    /// Don't return the entry. Directories are still descended into.
+    Skip,
+    /// Don't return the entry and don't descend into it.
+    Prune,
+}
+
+/// Information about an entry which is passed to an `EntryFilter`.
+#[derive(Debug)]
+pub struct EntryInfo<'a> {
+    pub name: &'a OsStr,
+    /// Path relative to the root path.
+    pub relative_path: &'a Path,
+    pub file_type: FileType,
+    /// Depth relative to the root path.
+    pub depth: usize,
+    /// Only set if the metadata is read.
+    pub metadata: Option<&'a MetaData>,
+    /// Only set if the extended metadata is read.
+    pub metadata_ext: Option<&'a MetaDataExt>,
+}
+
+/// Custom filter for the entries of a file tree. It is called on the worker threads of the walker
+/// for every entry which passed all other filters. Closures taking an `&EntryInfo` and returning
+/// a `FilterAction` implement this trait.
+pub trait EntryFilter: Send + Sync {
+    fn filter_entry(&self, entry: &EntryInfo<'_>) -> FilterAction;
+
+    /// Returns `true` if the filter needs the metadata of the entries.
+    fn needs_metadata(&self) -> bool {
+        true
+    }
+
+    /// Returns `true` if the filter needs the extended metadata of the entries.
+    fn needs_metadata_ext(&self) -> bool {
+        false
+    }
+}
+
+impl<F> EntryFilter for F
+where
+    F: Fn(&EntryInfo<'_>) -> FilterAction + Send + Sync,
+{
+    fn filter_entry(&self, entry: &EntryInfo<'_>) -> FilterAction {
+        self(entry)
+    }
+}
+
+impl fmt::Debug for dyn EntryFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EntryFilter")
+    }
+}
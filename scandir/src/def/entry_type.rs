@@ -0,0 +1,83 @@
+use std::fmt;
+use std::fs::FileType;
+use std::io::{Error, ErrorKind};
+use std::str::FromStr;
+
+/// Type of a directory entry for filtering by type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryType {
+    File,
+    Dir,
+    Symlink,
+    /// Block or character device. Only relevant on Unix systems.
+    Device,
+    /// Named pipe. Only relevant on Unix systems.
+    Fifo,
+    /// Only relevant on Unix systems.
+    Socket,
+    /// Any other type.
+    Other,
+}
+
+impl EntryType {
+    pub fn from_file_type(file_type: &FileType) -> Self {
+        if file_type.is_symlink() {
+            return Self::Symlink;
+        }
+        if file_type.is_dir() {
+            return Self::Dir;
+        }
+        if file_type.is_file() {
+            return Self::File;
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::FileTypeExt;
+
+            if file_type.is_block_device() || file_type.is_char_device() {
+                return Self::Device;
+            }
+            if file_type.is_fifo() {
+                return Self::Fifo;
+            }
+            if file_type.is_socket() {
+                return Self::Socket;
+            }
+        }
+        Self::Other
+    }
+}
+
+impl fmt::Display for EntryType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::File => "file",
+            Self::Dir => "dir",
+            Self::Symlink => "symlink",
+            Self::Device => "device",
+            Self::Fifo => "fifo",
+            Self::Socket => "socket",
+            Self::Other => "other",
+        })
+    }
+}
+
+impl FromStr for EntryType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "file" | "f" => Ok(Self::File),
+            "dir" | "directory" | "d" => Ok(Self::Dir),
+            "symlink" | "link" | "l" => Ok(Self::Symlink),
+            "device" | "b" | "c" => Ok(Self::Device),
+            "fifo" | "pipe" | "p" => Ok(Self::Fifo),
+            "socket" | "s" => Ok(Self::Socket),
+            "other" => Ok(Self::Other),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Unknown entry type {s}"),
+            )),
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+#[cfg(feature = "speedy")]
+use speedy::{Readable, Writable};
+
+/// Phase of the scan in which an error occurred.
+#[cfg_attr(feature = "speedy", derive(Readable, Writable))]
+#[cfg_attr(
+    any(feature = "bincode", feature = "json"),
+    derive(Deserialize, Serialize)
+)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorPhase {
+    /// Reading the contents of a directory.
+    ReadDir,
+    /// Reading the metadata of an entry.
+    Stat,
+    /// Reading the target of a symlink.
+    ReadLink,
+    /// Compiling the filter patterns.
+    FilterCompile,
+    /// Hashing the content of a file.
+    Hash,
+}
+
+impl fmt::Display for ErrorPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::ReadDir => "read_dir",
+            Self::Stat => "stat",
+            Self::ReadLink => "readlink",
+            Self::FilterCompile => "filter-compile",
+            Self::Hash => "hash",
+        })
+    }
+}
+
+/// Serializable counterpart of the most relevant variants of `std::io::ErrorKind`.
+#[cfg_attr(feature = "speedy", derive(Readable, Writable))]
+#[cfg_attr(
+    any(feature = "bincode", feature = "json"),
+    derive(Deserialize, Serialize)
+)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanErrorKind {
+    NotFound,
+    PermissionDenied,
+    NotADirectory,
+    IsADirectory,
+    InvalidInput,
+    InvalidData,
+    Interrupted,
+    CrossesDevices,
+    FilesystemLoop,
+    Other,
+}
+
+impl From<io::ErrorKind> for ScanErrorKind {
+    fn from(kind: io::ErrorKind) -> Self {
+        match kind {
+            io::ErrorKind::NotFound => Self::NotFound,
+            io::ErrorKind::PermissionDenied => Self::PermissionDenied,
+            io::ErrorKind::NotADirectory => Self::NotADirectory,
+            io::ErrorKind::IsADirectory => Self::IsADirectory,
+            io::ErrorKind::InvalidInput => Self::InvalidInput,
+            io::ErrorKind::InvalidData => Self::InvalidData,
+            io::ErrorKind::Interrupted => Self::Interrupted,
+            io::ErrorKind::CrossesDevices => Self::CrossesDevices,
+            _ => Self::Other,
+        }
+    }
+}
+
+impl From<ScanErrorKind> for io::ErrorKind {
+    fn from(kind: ScanErrorKind) -> Self {
+        match kind {
+            ScanErrorKind::NotFound => io::ErrorKind::NotFound,
+            ScanErrorKind::PermissionDenied => io::ErrorKind::PermissionDenied,
+            ScanErrorKind::NotADirectory => io::ErrorKind::NotADirectory,
+            ScanErrorKind::IsADirectory => io::ErrorKind::IsADirectory,
+            ScanErrorKind::InvalidInput => io::ErrorKind::InvalidInput,
+            ScanErrorKind::InvalidData => io::ErrorKind::InvalidData,
+            ScanErrorKind::Interrupted => io::ErrorKind::Interrupted,
+            ScanErrorKind::CrossesDevices => io::ErrorKind::CrossesDevices,
+            ScanErrorKind::FilesystemLoop | ScanErrorKind::Other => io::ErrorKind::Other,
+        }
+    }
+}
+
+/// Error which occurred while scanning a file tree.
+#[cfg_attr(feature = "speedy", derive(Readable, Writable))]
+#[cfg_attr(
+    any(feature = "bincode", feature = "json"),
+    derive(Deserialize, Serialize)
+)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanError {
+    /// Path of the entry the error belongs to. Empty if the path is unknown.
+    pub path: String,
+    pub kind: ScanErrorKind,
+    /// Raw OS error code, if the error was reported by the operating system.
+    pub raw_os_error: Option<i32>,
+    pub phase: ErrorPhase,
+    pub message: String,
+}
+
+impl ScanError {
+    pub fn new<P: AsRef<Path>>(
+        path: P,
+        kind: ScanErrorKind,
+        phase: ErrorPhase,
+        message: impl Into<String>,
+    ) -> Self {
+        ScanError {
+            path: path.as_ref().to_string_lossy().to_string(),
+            kind,
+            raw_os_error: None,
+            phase,
+            message: message.into(),
+        }
+    }
+
+    pub fn from_io<P: AsRef<Path>>(path: P, phase: ErrorPhase, error: &io::Error) -> Self {
+        ScanError {
+            path: path.as_ref().to_string_lossy().to_string(),
+            kind: error.kind().into(),
+            raw_os_error: error.raw_os_error(),
+            phase,
+            message: error.to_string(),
+        }
+    }
+
+    /// Returns the `std::io::ErrorKind` of the error.
+    pub fn io_kind(&self) -> io::ErrorKind {
+        self.kind.into()
+    }
+}
+
+impl From<&jwalk_meta::Error> for ScanError {
+    fn from(error: &jwalk_meta::Error) -> Self {
+        let path = error.path().unwrap_or_else(|| Path::new(""));
+        if let Some(ancestor) = error.loop_ancestor() {
+            return ScanError::new(
+                path,
+                ScanErrorKind::FilesystemLoop,
+                ErrorPhase::ReadLink,
+                format!("Points to ancestor {}", ancestor.display()),
+            );
+        }
+        // jwalk reports failures of reading a directory with depth 0. Errors of the
+        // entries of a directory have the depth of the entry.
+        let phase = match error.path() {
+            Some(_) if error.depth() > 0 => ErrorPhase::Stat,
+            _ => ErrorPhase::ReadDir,
+        };
+        match error.io_error() {
+            Some(e) => ScanError::from_io(path, phase, e),
+            None => ScanError::new(path, ScanErrorKind::Other, phase, error.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{} failed: {}", self.phase, self.message)
+        } else {
+            write!(
+                f,
+                "{} failed for {}: {}",
+                self.phase, self.path, self.message
+            )
+        }
+    }
+}
+
+impl std::error::Error for ScanError {}
+
+impl From<ScanError> for io::Error {
+    fn from(error: ScanError) -> Self {
+        io::Error::new(error.io_kind(), error)
+    }
+}
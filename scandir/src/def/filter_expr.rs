@@ -0,0 +1,405 @@
+use std::io::{Error, ErrorKind};
+use std::iter::Peekable;
+use std::str::FromStr;
+use std::time::Duration;
+
+use globset::GlobMatcher;
+use regex::Regex;
+
+use super::{EntryType, PermMatch};
+use crate::common::{compile_glob, group_id, user_id};
+
+/// Comparison operator of a filter expression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compare {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+impl Compare {
+    #[inline]
+    pub fn compare<T: PartialOrd>(&self, value: T, other: T) -> bool {
+        match self {
+            Self::Lt => value < other,
+            Self::Le => value <= other,
+            Self::Gt => value > other,
+            Self::Ge => value >= other,
+            Self::Eq => value == other,
+            Self::Ne => value != other,
+        }
+    }
+}
+
+/// Timestamp which is compared by a filter expression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeField {
+    Modified,
+    Created,
+    Accessed,
+}
+
+/// Compiled find-style filter expression, e.g.
+/// `(name "*.log" and size > 10M) or (type l and not exists-target)`.
+#[derive(Debug, Clone)]
+pub enum FilterExpr {
+    And(Box<FilterExpr>, Box<FilterExpr>),
+    Or(Box<FilterExpr>, Box<FilterExpr>),
+    Not(Box<FilterExpr>),
+    True,
+    False,
+    /// Glob pattern for the file name.
+    Name(GlobMatcher),
+    /// Glob pattern for the path relative to the root path with `/` as separator.
+    Path(GlobMatcher),
+    /// Regular expression for the path relative to the root path.
+    Regex(Regex),
+    Type(Vec<EntryType>),
+    /// Size in bytes.
+    Size(Compare, u64),
+    /// Age of a timestamp relative to the start of the scan.
+    Age(TimeField, Compare, Duration),
+    Depth(Compare, usize),
+    Links(Compare, u64),
+    Perm(PermMatch),
+    Uid(u32),
+    Gid(u32),
+    NoUser,
+    NoGroup,
+    /// Empty files and directories.
+    Empty,
+    /// Entries which aren't broken symlinks.
+    ExistsTarget,
+}
+
+impl FilterExpr {
+    /// Returns `true` if the expression needs the metadata of the entries.
+    pub fn needs_metadata(&self) -> bool {
+        match self {
+            Self::And(left, right) | Self::Or(left, right) => {
+                left.needs_metadata() || right.needs_metadata()
+            }
+            Self::Not(expr) => expr.needs_metadata(),
+            Self::Size(..) | Self::Age(..) | Self::Empty => true,
+            _ => self.needs_metadata_ext(),
+        }
+    }
+
+    /// Returns `true` if the expression needs the extended metadata of the entries.
+    pub fn needs_metadata_ext(&self) -> bool {
+        match self {
+            Self::And(left, right) | Self::Or(left, right) => {
+                left.needs_metadata_ext() || right.needs_metadata_ext()
+            }
+            Self::Not(expr) => expr.needs_metadata_ext(),
+            Self::Links(..)
+            | Self::Perm(_)
+            | Self::Uid(_)
+            | Self::Gid(_)
+            | Self::NoUser
+            | Self::NoGroup => true,
+            _ => false,
+        }
+    }
+}
+
+#[inline]
+fn parse_error(message: impl Into<String>) -> Error {
+    Error::new(ErrorKind::InvalidInput, message.into())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    /// Unquoted word.
+    Word(String),
+    /// Quoted string, which is never an operator.
+    Quoted(String),
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, Error> {
+    let mut tokens = Vec::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            '"' | '\'' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some('\\') => match chars.next() {
+                            Some(escaped) => value.push(escaped),
+                            None => return Err(parse_error("Unterminated string")),
+                        },
+                        Some(q) if q == c => break,
+                        Some(other) => value.push(other),
+                        None => return Err(parse_error("Unterminated string")),
+                    }
+                }
+                tokens.push(Token::Quoted(value));
+            }
+            _ => {
+                let mut value = String::from(c);
+                while let Some(&next) = chars.peek() {
+                    if next.is_whitespace() || next == '(' || next == ')' {
+                        break;
+                    }
+                    value.push(next);
+                    chars.next();
+                }
+                tokens.push(Token::Word(value));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Parses a size with optional unit `k`, `M`, `G` or `T` (powers of 1024).
+fn parse_size(s: &str) -> Result<u64, Error> {
+    let s = s.trim_end_matches(['b', 'B']);
+    let (number, factor) = match s.char_indices().last() {
+        Some((i, 'k' | 'K')) => (&s[..i], 1 << 10),
+        Some((i, 'm' | 'M')) => (&s[..i], 1 << 20),
+        Some((i, 'g' | 'G')) => (&s[..i], 1 << 30),
+        Some((i, 't' | 'T')) => (&s[..i], 1 << 40),
+        _ => (s, 1),
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(factor))
+        .ok_or_else(|| parse_error(format!("Invalid size {s}")))
+}
+
+/// Parses a duration with unit `s`, `m`, `h`, `d` (default) or `w`.
+fn parse_duration(s: &str) -> Result<Duration, Error> {
+    let (number, factor) = match s.char_indices().last() {
+        Some((i, 's')) => (&s[..i], 1),
+        Some((i, 'm')) => (&s[..i], 60),
+        Some((i, 'h')) => (&s[..i], 3600),
+        Some((i, 'd')) => (&s[..i], 86400),
+        Some((i, 'w')) => (&s[..i], 7 * 86400),
+        _ => (s, 86400),
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(factor))
+        .map(Duration::from_secs)
+        .ok_or_else(|| parse_error(format!("Invalid duration {s}")))
+}
+
+struct Parser<I: Iterator<Item = Token>> {
+    tokens: Peekable<I>,
+}
+
+impl<I: Iterator<Item = Token>> Parser<I> {
+    fn peek_word(&mut self) -> Option<&str> {
+        match self.tokens.peek() {
+            Some(Token::Word(word)) => Some(word.as_str()),
+            _ => None,
+        }
+    }
+
+    fn argument(&mut self, test: &str) -> Result<String, Error> {
+        match self.tokens.next() {
+            Some(Token::Word(value)) | Some(Token::Quoted(value)) => Ok(value),
+            _ => Err(parse_error(format!("Missing argument of {test}"))),
+        }
+    }
+
+    /// Parses the operator and value of a comparison. The operator can be a separate word, a
+    /// prefix of the value or a `+` or `-` prefix like in find. Without operator `=` is used.
+    fn comparison(&mut self, test: &str) -> Result<(Compare, String), Error> {
+        let argument = self.argument(test)?;
+        let operators = [
+            ("<=", Compare::Le),
+            (">=", Compare::Ge),
+            ("==", Compare::Eq),
+            ("!=", Compare::Ne),
+            ("<", Compare::Lt),
+            (">", Compare::Gt),
+            ("=", Compare::Eq),
+            ("+", Compare::Gt),
+            ("-", Compare::Lt),
+        ];
+        for (operator, compare) in operators {
+            if let Some(value) = argument.strip_prefix(operator) {
+                return match value.is_empty() {
+                    true => Ok((compare, self.argument(test)?)),
+                    false => Ok((compare, value.to_string())),
+                };
+            }
+        }
+        Ok((Compare::Eq, argument))
+    }
+
+    fn or(&mut self) -> Result<FilterExpr, Error> {
+        let mut expr = self.and()?;
+        while matches!(self.peek_word(), Some("or" | "-o" | "||")) {
+            self.tokens.next();
+            expr = FilterExpr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<FilterExpr, Error> {
+        let mut expr = self.not()?;
+        loop {
+            match self.tokens.peek() {
+                None | Some(Token::Close) => break,
+                Some(Token::Word(word)) if matches!(word.as_str(), "or" | "-o" | "||") => break,
+                Some(Token::Word(word)) if matches!(word.as_str(), "and" | "-a" | "&&") => {
+                    self.tokens.next();
+                }
+                // Like in find, tests without operator are combined with `and`
+                _ => {}
+            }
+            expr = FilterExpr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<FilterExpr, Error> {
+        if matches!(self.peek_word(), Some("not" | "!" | "-not")) {
+            self.tokens.next();
+            return Ok(FilterExpr::Not(Box::new(self.not()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<FilterExpr, Error> {
+        let test = match self.tokens.next() {
+            Some(Token::Open) => {
+                let expr = self.or()?;
+                return match self.tokens.next() {
+                    Some(Token::Close) => Ok(expr),
+                    _ => Err(parse_error("Missing )")),
+                };
+            }
+            Some(Token::Word(word)) => word,
+            Some(Token::Close) => return Err(parse_error("Unexpected )")),
+            Some(Token::Quoted(value)) => {
+                return Err(parse_error(format!("Unexpected string \"{value}\"")));
+            }
+            None => return Err(parse_error("Unexpected end of expression")),
+        };
+        // Like in find, tests may start with `-`
+        let name = test.trim_start_matches('-');
+        let expr = match name {
+            "true" => FilterExpr::True,
+            "false" => FilterExpr::False,
+            "name" | "iname" | "path" | "ipath" => {
+                let argument = self.argument(&test)?;
+                let glob = compile_glob(&argument, !name.starts_with('i'))
+                    .map_err(|e| parse_error(format!("{test} {argument}: {e}")))?
+                    .compile_matcher();
+                match name.ends_with("name") {
+                    true => FilterExpr::Name(glob),
+                    false => FilterExpr::Path(glob),
+                }
+            }
+            "regex" => {
+                let argument = self.argument(&test)?;
+                let regex = Regex::new(&argument)
+                    .map_err(|e| parse_error(format!("{test} {argument}: {e}")))?;
+                FilterExpr::Regex(regex)
+            }
+            "type" => FilterExpr::Type(
+                self.argument(&test)?
+                    .split(',')
+                    .map(EntryType::from_str)
+                    .collect::<Result<Vec<_>, _>>()?,
+            ),
+            "size" => {
+                let (compare, value) = self.comparison(&test)?;
+                FilterExpr::Size(compare, parse_size(&value)?)
+            }
+            "mtime" | "ctime" | "atime" => {
+                let field = match name {
+                    "mtime" => TimeField::Modified,
+                    "ctime" => TimeField::Created,
+                    _ => TimeField::Accessed,
+                };
+                let (compare, value) = self.comparison(&test)?;
+                FilterExpr::Age(field, compare, parse_duration(&value)?)
+            }
+            "depth" => {
+                let (compare, value) = self.comparison(&test)?;
+                let depth = value
+                    .parse()
+                    .map_err(|_| parse_error(format!("Invalid depth {value}")))?;
+                FilterExpr::Depth(compare, depth)
+            }
+            "links" => {
+                let (compare, value) = self.comparison(&test)?;
+                let links = value
+                    .parse()
+                    .map_err(|_| parse_error(format!("Invalid number of links {value}")))?;
+                FilterExpr::Links(compare, links)
+            }
+            "perm" => FilterExpr::Perm(self.argument(&test)?.parse()?),
+            "uid" => {
+                let value = self.argument(&test)?;
+                FilterExpr::Uid(
+                    value
+                        .parse()
+                        .map_err(|_| parse_error(format!("Invalid user ID {value}")))?,
+                )
+            }
+            "gid" => {
+                let value = self.argument(&test)?;
+                FilterExpr::Gid(
+                    value
+                        .parse()
+                        .map_err(|_| parse_error(format!("Invalid group ID {value}")))?,
+                )
+            }
+            // Names are resolved once while parsing. Like find numeric IDs are allowed too.
+            "user" => {
+                let value = self.argument(&test)?;
+                FilterExpr::Uid(
+                    user_id(&value)
+                        .or_else(|| value.parse().ok())
+                        .ok_or_else(|| parse_error(format!("Unknown user {value}")))?,
+                )
+            }
+            "group" => {
+                let value = self.argument(&test)?;
+                FilterExpr::Gid(
+                    group_id(&value)
+                        .or_else(|| value.parse().ok())
+                        .ok_or_else(|| parse_error(format!("Unknown group {value}")))?,
+                )
+            }
+            "nouser" => FilterExpr::NoUser,
+            "nogroup" => FilterExpr::NoGroup,
+            "empty" => FilterExpr::Empty,
+            "exists-target" => FilterExpr::ExistsTarget,
+            _ => return Err(parse_error(format!("Unknown test {test}"))),
+        };
+        Ok(expr)
+    }
+}
+
+impl FromStr for FilterExpr {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?.into_iter().peekable(),
+        };
+        let expr = parser.or()?;
+        match parser.tokens.next() {
+            None => Ok(expr),
+            Some(Token::Close) => Err(parse_error("Unexpected )")),
+            Some(token) => Err(parse_error(format!("Unexpected {token:?}"))),
+        }
+    }
+}
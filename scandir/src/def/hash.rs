@@ -0,0 +1,121 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{Error, ErrorKind, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::str::FromStr;
+
+use sha2::Digest;
+
+/// Algorithm used for hashing the content of files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    /// 128 bit XXH3. Very fast, but not cryptographically secure.
+    Xxh3,
+    Blake3,
+    Sha256,
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Xxh3 => "xxh3",
+            Self::Blake3 => "blake3",
+            Self::Sha256 => "sha256",
+        })
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "xxh3" => Ok(Self::Xxh3),
+            "blake3" => Ok(Self::Blake3),
+            "sha256" | "sha-256" => Ok(Self::Sha256),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Unknown hash algorithm {s}"),
+            )),
+        }
+    }
+}
+
+/// Part of the file content which is hashed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashMode {
+    /// Hash the whole content.
+    Full,
+    /// Hash only the first and the last N bytes.
+    Partial(u64),
+}
+
+enum Hasher {
+    Xxh3(Box<xxhash_rust::xxh3::Xxh3>),
+    Blake3(Box<blake3::Hasher>),
+    Sha256(sha2::Sha256),
+}
+
+impl Hasher {
+    fn new(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Xxh3 => Self::Xxh3(Box::default()),
+            HashAlgorithm::Blake3 => Self::Blake3(Box::default()),
+            HashAlgorithm::Sha256 => Self::Sha256(sha2::Sha256::new()),
+        }
+    }
+
+    #[inline]
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Xxh3(h) => h.update(data),
+            Self::Blake3(h) => {
+                h.update(data);
+            }
+            Self::Sha256(h) => h.update(data),
+        }
+    }
+
+    fn finalize(self) -> String {
+        match self {
+            Self::Xxh3(h) => format!("{:032x}", h.digest128()),
+            Self::Blake3(h) => h.finalize().to_hex().to_string(),
+            Self::Sha256(h) => h.finalize().iter().map(|b| format!("{b:02x}")).collect(),
+        }
+    }
+}
+
+/// Hashes `len` bytes of `file` or until the end of the file is reached.
+fn hash_reader(hasher: &mut Hasher, file: &mut File, len: u64) -> Result<(), Error> {
+    let mut buffer = [0u8; 65536];
+    let mut reader = file.take(len);
+    loop {
+        let n = match reader.read(&mut buffer) {
+            Ok(0) => return Ok(()),
+            Ok(n) => n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        hasher.update(&buffer[..n]);
+    }
+}
+
+/// Returns the hex encoded digest of the content of a file.
+pub fn hash_file(path: &Path, algorithm: HashAlgorithm, mode: HashMode) -> Result<String, Error> {
+    let mut file = File::open(path)?;
+    let mut hasher = Hasher::new(algorithm);
+    match mode {
+        HashMode::Full => hash_reader(&mut hasher, &mut file, u64::MAX)?,
+        HashMode::Partial(n) => {
+            let size = file.metadata()?.len();
+            hash_reader(&mut hasher, &mut file, n)?;
+            if size > n {
+                // The first and the last part may overlap for small files
+                let start = n.max(size.saturating_sub(n));
+                file.seek(SeekFrom::Start(start))?;
+                hash_reader(&mut hasher, &mut file, n)?;
+            }
+        }
+    }
+    Ok(hasher.finalize())
+}
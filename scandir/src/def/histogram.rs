@@ -0,0 +1,100 @@
+#[cfg(feature = "speedy")]
+use speedy::{Readable, Writable};
+
+/// Seconds of one day.
+pub const DAY: u64 = 86400;
+
+/// Upper bounds of the age buckets which are used by default: <1d, <7d, <30d, <1y and older.
+pub const DEFAULT_AGE_BUCKETS: [u64; 4] = [DAY, 7 * DAY, 30 * DAY, 365 * DAY];
+
+/// Returns `DEFAULT_AGE_BUCKETS` as vector.
+pub fn default_age_buckets() -> Vec<u64> {
+    DEFAULT_AGE_BUCKETS.to_vec()
+}
+
+/// Histogram with buckets defined by their upper bounds. Bucket `i` counts the values which are
+/// less than `bounds[i]`, but not less than `bounds[i - 1]`. The last bucket counts all values
+/// which are greater or equal than the last bound.
+#[cfg_attr(feature = "speedy", derive(Readable, Writable))]
+#[cfg_attr(
+    any(feature = "bincode", feature = "json"),
+    derive(Deserialize, Serialize)
+)]
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Histogram {
+    /// Ascending upper bounds of the buckets.
+    pub bounds: Vec<u64>,
+    /// Number of values per bucket. Has one more element than `bounds`.
+    pub counts: Vec<u64>,
+}
+
+impl Histogram {
+    pub fn new(bounds: &[u64]) -> Self {
+        let mut bounds = bounds.to_vec();
+        bounds.sort_unstable();
+        bounds.dedup();
+        Histogram {
+            counts: vec![0; bounds.len() + 1],
+            bounds,
+        }
+    }
+
+    /// Adds a value. The counts are extended if needed, e.g. for the default histogram, which
+    /// has no buckets.
+    #[inline]
+    pub fn add(&mut self, value: u64) {
+        let index = self.bounds.partition_point(|bound| *bound <= value);
+        increment(&mut self.counts, index);
+    }
+
+    /// Returns `true` if no value was added.
+    pub fn is_empty(&self) -> bool {
+        self.counts.iter().all(|count| *count == 0)
+    }
+
+    /// Returns `true` if the counts of another histogram can be added, which is the case if
+    /// the bounds are equal or one of the histograms has no buckets.
+    pub fn can_merge(&self, other: &Histogram) -> bool {
+        self.counts.is_empty() || other.counts.is_empty() || self.bounds == other.bounds
+    }
+
+    /// Adds the counts of another histogram. Returns `false` and leaves the histogram
+    /// unchanged if the bounds differ.
+    pub fn merge(&mut self, other: &Histogram) -> bool {
+        if !self.can_merge(other) {
+            return false;
+        }
+        if self.counts.is_empty() {
+            *self = other.clone();
+        } else if !other.counts.is_empty() {
+            add_counts(&mut self.counts, &other.counts);
+        }
+        true
+    }
+}
+
+/// Returns the index of the log2 bucket of a size. Bucket `0` is for empty files and
+/// bucket `i` for sizes in `[2^(i-1), 2^i)`.
+#[inline]
+pub fn log2_bucket(size: u64) -> usize {
+    (u64::BITS - size.leading_zeros()) as usize
+}
+
+/// Increments the count at `index`. The vector is extended if needed.
+#[inline]
+pub fn increment(counts: &mut Vec<u64>, index: usize) {
+    if counts.len() <= index {
+        counts.resize(index + 1, 0);
+    }
+    counts[index] += 1;
+}
+
+/// Adds the counts of `other` element-wise. The vector is extended if needed.
+pub fn add_counts(counts: &mut Vec<u64>, other: &[u64]) {
+    if counts.len() < other.len() {
+        counts.resize(other.len(), 0);
+    }
+    for (count, other) in counts.iter_mut().zip(other.iter()) {
+        *count += other;
+    }
+}
@@ -0,0 +1,36 @@
+use globset::{Candidate, GlobSet};
+use regex::RegexSet;
+
+/// Glob patterns and regular expressions of an include or exclude filter, compiled once into
+/// combined matchers. Globs use fast paths for literals, extensions, prefixes and suffixes and
+/// a single automaton for all other patterns. Matching doesn't allocate.
+#[derive(Debug, Clone)]
+pub struct Matcher {
+    globs: GlobSet,
+    regexes: RegexSet,
+}
+
+impl Matcher {
+    pub fn new(globs: GlobSet, regexes: RegexSet) -> Self {
+        Self { globs, regexes }
+    }
+
+    /// Returns `true` if neither glob patterns nor regular expressions are set.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.globs.is_empty() && self.regexes.is_empty()
+    }
+
+    /// Returns `true` if `key` matches any glob pattern or regular expression.
+    #[inline]
+    pub fn is_match(&self, key: &str) -> bool {
+        (!self.globs.is_empty() && self.globs.is_match_candidate(&Candidate::from_bytes(key)))
+            || (!self.regexes.is_empty() && self.regexes.is_match(key))
+    }
+}
+
+impl Default for Matcher {
+    fn default() -> Self {
+        Self::new(GlobSet::empty(), RegexSet::empty())
+    }
+}
@@ -1,26 +1,138 @@
-use glob_sl::{MatchOptions, Pattern};
+use std::sync::Arc;
+use std::time::SystemTime;
 
-pub type ErrorsType = Vec<(String, String)>; // Tuple with file path and error message
+pub mod error;
+pub use error::{ErrorPhase, ScanError, ScanErrorKind};
+
+pub type ErrorsType = Vec<ScanError>;
 
 pub mod count;
-pub use count::Statistics;
+pub use count::{ExtensionStats, OwnerStats, Statistics};
+pub mod dir_usage;
+pub use dir_usage::{DirUsage, UsageTotals};
 pub mod direntry;
+pub mod entry_filter;
+pub use entry_filter::{EntryFilter, EntryInfo, FilterAction};
+pub mod entry_type;
+pub use entry_type::EntryType;
+pub mod duplicates;
+pub use duplicates::{DuplicateSet, Duplicates};
+pub mod filter_expr;
+pub use filter_expr::{Compare, FilterExpr, TimeField};
+pub mod hash;
+pub use hash::{HashAlgorithm, HashMode};
+pub mod histogram;
+pub use histogram::{DEFAULT_AGE_BUCKETS, Histogram, default_age_buckets};
+pub mod matcher;
+pub use matcher::Matcher;
 pub mod options;
+pub mod owner_cache;
+pub use owner_cache::OwnerCache;
+pub mod perm;
+pub use perm::PermMatch;
 pub mod walk;
 pub use direntry::{DirEntry, DirEntryExt};
-pub use options::Options;
+pub use options::{MULTI_PART_EXTENSIONS, Options, default_multi_part_extensions};
+pub mod raw_name;
+pub use raw_name::RawName;
 pub mod scandir;
 pub use scandir::ScandirResult;
+pub mod sort;
+pub use sort::{SortBy, SortFields, SortKey, SortMetadata, natural_cmp};
+pub mod symlink_loop;
+pub use symlink_loop::SymlinkLoop;
+pub mod time_range;
+pub use time_range::{TimeBound, TimeRange};
 pub mod toc;
 pub use toc::Toc;
+pub mod top_n;
+pub use top_n::{TopEntry, TopN};
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct Filter {
-    pub dir_include: Vec<Pattern>,
-    pub dir_exclude: Vec<Pattern>,
-    pub file_include: Vec<Pattern>,
-    pub file_exclude: Vec<Pattern>,
-    pub options: Option<MatchOptions>,
+    /// Glob patterns and regular expressions of directories to include.
+    pub dir_include: Matcher,
+    /// Glob patterns and regular expressions of directories to exclude.
+    pub dir_exclude: Matcher,
+    /// Glob patterns and regular expressions of files to include.
+    pub file_include: Matcher,
+    /// Glob patterns and regular expressions of files to exclude.
+    pub file_exclude: Matcher,
+    /// Minimum size of files in bytes.
+    pub min_size: Option<u64>,
+    /// Maximum size of files in bytes.
+    pub max_size: Option<u64>,
+    /// Range of the modification time.
+    pub mtime: Option<TimeRange>,
+    /// Range of the creation time.
+    pub ctime: Option<TimeRange>,
+    /// Range of the access time.
+    pub atime: Option<TimeRange>,
+    /// Types of the entries to return. Directories of other types are still descended into.
+    pub entry_types: Option<Vec<EntryType>>,
+    /// Test of the permission bits.
+    pub perm: Option<PermMatch>,
+    /// User ID of the owner.
+    pub uid: Option<u32>,
+    /// Group ID of the owner.
+    pub gid: Option<u32>,
+    /// Only entries whose user ID has no user name.
+    pub nouser: bool,
+    /// Only entries whose group ID has no group name.
+    pub nogroup: bool,
+    /// Cached lookups of the user and group IDs for `nouser` and `nogroup`.
+    pub owners: OwnerCache,
+    /// Minimum number of hard links.
+    pub min_links: Option<u64>,
+    /// Maximum number of hard links.
+    pub max_links: Option<u64>,
+    /// Compiled filter expression.
+    pub filter_expr: Option<FilterExpr>,
+    /// Custom filter which is applied after all other filters.
+    pub entry_filter: Option<Arc<dyn EntryFilter>>,
+    /// Match the file filters against the path relative to the root path.
+    pub file_match_path: bool,
+    /// Reference time for the ages of the time ranges.
+    pub now: SystemTime,
+}
+
+impl Filter {
+    /// Returns `true` if the filter needs the metadata of the entries.
+    pub fn needs_metadata(&self) -> bool {
+        self.min_size.is_some()
+            || self.max_size.is_some()
+            || self.mtime.is_some()
+            || self.ctime.is_some()
+            || self.atime.is_some()
+            || self.needs_metadata_ext()
+            || self
+                .filter_expr
+                .as_ref()
+                .is_some_and(FilterExpr::needs_metadata)
+            || self
+                .entry_filter
+                .as_ref()
+                .is_some_and(|entry_filter| entry_filter.needs_metadata())
+    }
+
+    /// Returns `true` if the filter needs the extended metadata of the entries.
+    pub fn needs_metadata_ext(&self) -> bool {
+        self.perm.is_some()
+            || self.uid.is_some()
+            || self.gid.is_some()
+            || self.nouser
+            || self.nogroup
+            || self.min_links.is_some()
+            || self.max_links.is_some()
+            || self
+                .filter_expr
+                .as_ref()
+                .is_some_and(FilterExpr::needs_metadata_ext)
+            || self
+                .entry_filter
+                .as_ref()
+                .is_some_and(|entry_filter| entry_filter.needs_metadata_ext())
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
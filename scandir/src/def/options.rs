@@ -1,11 +1,19 @@
 use std::path::PathBuf;
+use std::sync::Arc;
 
-use super::ReturnType;
+use super::{
+    EntryFilter, EntryType, HashAlgorithm, HashMode, PermMatch, ReturnType, SortBy, TimeRange,
+    default_age_buckets,
+};
 
 #[derive(Debug, Clone)]
 pub struct Options {
     pub root_path: PathBuf,
+    /// Root paths in multi-root mode. If set, `root_path` is the first of them.
+    pub root_paths: Option<Vec<PathBuf>>,
     pub sorted: bool,
+    /// Sort the entries of each directory by this key. Overrides `sorted`.
+    pub sort_by: Option<SortBy>,
     pub skip_hidden: bool,
     pub max_depth: usize,
     pub max_file_cnt: usize,
@@ -13,9 +21,90 @@ pub struct Options {
     pub dir_exclude: Option<Vec<String>>,
     pub file_include: Option<Vec<String>>,
     pub file_exclude: Option<Vec<String>>,
+    /// Regular expressions for directories to include. Matched against the relative path.
+    pub dir_include_regex: Option<Vec<String>>,
+    /// Regular expressions for directories to exclude. Matched against the relative path.
+    pub dir_exclude_regex: Option<Vec<String>>,
+    /// Regular expressions for files to include. Matched against the file name.
+    pub file_include_regex: Option<Vec<String>>,
+    /// Regular expressions for files to exclude. Matched against the file name.
+    pub file_exclude_regex: Option<Vec<String>>,
+    /// Minimum size of files in bytes.
+    pub min_size: Option<u64>,
+    /// Maximum size of files in bytes.
+    pub max_size: Option<u64>,
+    /// Range of the modification time.
+    pub mtime: Option<TimeRange>,
+    /// Range of the creation time.
+    pub ctime: Option<TimeRange>,
+    /// Range of the access time.
+    pub atime: Option<TimeRange>,
+    /// Types of the entries to return. Directories of other types are still descended into.
+    pub entry_types: Option<Vec<EntryType>>,
+    /// Test of the permission bits. Only supported on Unix.
+    pub perm: Option<PermMatch>,
+    /// User ID of the owner. Only supported on Unix.
+    pub uid: Option<u32>,
+    /// Group ID of the owner. Only supported on Unix.
+    pub gid: Option<u32>,
+    /// Only entries whose user ID has no user name. Only supported on Unix.
+    pub nouser: bool,
+    /// Only entries whose group ID has no group name. Only supported on Unix.
+    pub nogroup: bool,
+    /// Minimum number of hard links.
+    pub min_links: Option<u64>,
+    /// Maximum number of hard links.
+    pub max_links: Option<u64>,
+    /// Find-style filter expression, e.g. `name "*.log" and size > 10M`.
+    pub filter_expr: Option<String>,
+    /// Custom filter which is applied after all other filters.
+    pub entry_filter: Option<Arc<dyn EntryFilter>>,
     pub case_sensitive: bool,
+    /// Match the file filters against the path relative to the root path instead of the file name.
+    pub file_match_path: bool,
     pub follow_links: bool,
+    /// Skip entries which are ignored by `.gitignore`, `.ignore` and `.git/info/exclude` files.
+    pub gitignore: bool,
+    /// Don't descend into directories on other file systems than the root path.
+    pub one_file_system: bool,
     pub return_type: ReturnType,
+    /// Capacity of the results channel. `0` means unbounded.
+    pub channel_capacity: usize,
+    /// Report the disk usage of each directory. Only used by `Count`.
+    pub du: bool,
+    /// Number of largest files and directories to report. `0` disables the report.
+    /// Only used by `Count` and `Scandir`.
+    pub top_n: usize,
+    /// Algorithm for hashing the content of files. `None` disables hashing.
+    /// Only used by `Scandir`.
+    pub hash_algorithm: Option<HashAlgorithm>,
+    /// Part of the file content which is hashed.
+    pub hash_mode: HashMode,
+    /// Collect statistics per file extension. Only used by `Count`.
+    pub extension_stats: bool,
+    /// If `false` the extensions are folded to lower case.
+    pub extension_case_sensitive: bool,
+    /// Extensions which consist of multiple parts, e.g. `tar.gz`.
+    pub multi_part_extensions: Vec<String>,
+    /// Collect statistics per user and group ID. Only used by `Count`.
+    pub owner_stats: bool,
+    /// Resolve user and group IDs to names.
+    pub resolve_names: bool,
+    /// Collect size, age and depth histograms. Only used by `Count`.
+    pub histograms: bool,
+    /// Upper bounds of the age buckets in seconds.
+    pub age_buckets: Vec<u64>,
+}
+
+/// Multi-part extensions which are recognized by default.
+pub const MULTI_PART_EXTENSIONS: [&str; 4] = ["tar.gz", "tar.bz2", "tar.xz", "tar.zst"];
+
+/// Returns `MULTI_PART_EXTENSIONS` as owned strings.
+pub fn default_multi_part_extensions() -> Vec<String> {
+    MULTI_PART_EXTENSIONS
+        .iter()
+        .map(|e| e.to_string())
+        .collect()
 }
 
 impl Options {
@@ -36,7 +125,9 @@ impl Options {
     ) -> Self {
         Self {
             root_path,
+            root_paths: None,
             sorted,
+            sort_by: None,
             skip_hidden,
             max_depth,
             max_file_cnt,
@@ -44,9 +135,64 @@ impl Options {
             dir_exclude,
             file_include,
             file_exclude,
+            dir_include_regex: None,
+            dir_exclude_regex: None,
+            file_include_regex: None,
+            file_exclude_regex: None,
+            min_size: None,
+            max_size: None,
+            mtime: None,
+            ctime: None,
+            atime: None,
+            entry_types: None,
+            perm: None,
+            uid: None,
+            gid: None,
+            nouser: false,
+            nogroup: false,
+            min_links: None,
+            max_links: None,
+            filter_expr: None,
+            entry_filter: None,
             case_sensitive,
+            file_match_path: false,
             follow_links,
+            one_file_system: false,
+            gitignore: false,
             return_type: return_type.unwrap_or(ReturnType::Base),
+            channel_capacity: 0,
+            du: false,
+            top_n: 0,
+            hash_algorithm: None,
+            hash_mode: HashMode::Full,
+            extension_stats: false,
+            extension_case_sensitive: false,
+            multi_part_extensions: default_multi_part_extensions(),
+            owner_stats: false,
+            resolve_names: false,
+            histograms: false,
+            age_buckets: default_age_buckets(),
+        }
+    }
+
+    /// Returns all root paths to scan.
+    pub fn roots(&self) -> Vec<PathBuf> {
+        match self.root_paths {
+            Some(ref root_paths) => root_paths.clone(),
+            None => vec![self.root_path.clone()],
         }
     }
+
+    /// Returns `true` if sorting needs the metadata of the entries.
+    #[inline]
+    pub fn sort_needs_metadata(&self) -> bool {
+        self.sort_by
+            .is_some_and(|sort_by| sort_by.key.needs_metadata())
+    }
+
+    /// Returns `true` if multiple file trees are scanned in one run.
+    #[inline]
+    pub fn is_multi_root(&self) -> bool {
+        self.root_paths.is_some()
+    }
 }
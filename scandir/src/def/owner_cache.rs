@@ -0,0 +1,37 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::common::{gid_exists, uid_exists};
+
+/// Cache of the user and group lookups of the `nouser` and `nogroup` filters. The cache is shared
+/// by all clones, so that each ID is only looked up once per scan.
+#[derive(Debug, Clone, Default)]
+pub struct OwnerCache {
+    users: Arc<Mutex<HashMap<u32, bool>>>,
+    groups: Arc<Mutex<HashMap<u32, bool>>>,
+}
+
+impl OwnerCache {
+    /// Returns `false` if the user ID has no entry in the user database.
+    #[inline]
+    pub fn uid_exists(&self, uid: u32) -> bool {
+        lookup(&self.users, uid, uid_exists)
+    }
+
+    /// Returns `false` if the group ID has no entry in the group database.
+    #[inline]
+    pub fn gid_exists(&self, gid: u32) -> bool {
+        lookup(&self.groups, gid, gid_exists)
+    }
+}
+
+#[inline]
+fn lookup(cache: &Mutex<HashMap<u32, bool>>, id: u32, exists: fn(u32) -> bool) -> bool {
+    if let Some(exists) = cache.lock().unwrap().get(&id) {
+        return *exists;
+    }
+    // The lock isn't held during the lookup, which may be slow for network user databases
+    let exists = exists(id);
+    cache.lock().unwrap().insert(id, exists);
+    exists
+}
@@ -0,0 +1,47 @@
+use std::io::{Error, ErrorKind};
+use std::str::FromStr;
+
+/// Test of the permission bits like `find -perm`. Only supported on Unix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PermMatch {
+    /// Permission bits are exactly the given mode, e.g. `644`.
+    Exact(u32),
+    /// All bits of the given mode are set, e.g. `-002` for world writable entries.
+    All(u32),
+    /// Any bit of the given mode is set, e.g. `/6000` for setuid or setgid entries.
+    /// A mode of `0` matches all entries.
+    Any(u32),
+}
+
+impl PermMatch {
+    /// Returns `true` if the permission bits of `mode` match. The file type bits are ignored.
+    #[inline]
+    pub fn matches(&self, mode: u32) -> bool {
+        let mode = mode & 0o7777;
+        match *self {
+            Self::Exact(bits) => mode == bits,
+            Self::All(bits) => mode & bits == bits,
+            Self::Any(bits) => bits == 0 || mode & bits != 0,
+        }
+    }
+}
+
+impl FromStr for PermMatch {
+    type Err = Error;
+
+    /// Parses an octal mode with an optional prefix `-` (all bits) or `/` (any bit).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (variant, mode): (fn(u32) -> Self, &str) = match s.as_bytes().first() {
+            Some(b'-') => (Self::All, &s[1..]),
+            Some(b'/') => (Self::Any, &s[1..]),
+            _ => (Self::Exact, s),
+        };
+        match u32::from_str_radix(mode, 8) {
+            Ok(bits) if !mode.starts_with('+') && bits <= 0o7777 => Ok(variant(bits)),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid permission mode {s}"),
+            )),
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use std::ffi::{OsStr, OsString};
+
+#[cfg(feature = "speedy")]
+use speedy::{Readable, Writable};
+
+/// Name of an entry which isn't valid UTF-8. The list of names it belongs to contains
+/// a lossy representation of the name.
+#[cfg_attr(feature = "speedy", derive(Readable, Writable))]
+#[cfg_attr(
+    any(feature = "bincode", feature = "json"),
+    derive(Deserialize, Serialize)
+)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawName {
+    /// Index of the name in the list of names.
+    pub index: usize,
+    /// Raw bytes of the name.
+    pub bytes: Vec<u8>,
+}
+
+impl RawName {
+    pub fn os_name(&self) -> OsString {
+        bytes_to_os_string(&self.bytes)
+    }
+}
+
+/// Converts an `OsStr` to a string. If it isn't valid UTF-8 a lossy representation
+/// and the raw bytes are returned.
+#[inline]
+pub fn os_str_to_string(s: &OsStr) -> (String, Option<Vec<u8>>) {
+    match s.to_str() {
+        Some(s) => (s.to_owned(), None),
+        None => (
+            s.to_string_lossy().to_string(),
+            Some(s.as_encoded_bytes().to_vec()),
+        ),
+    }
+}
+
+/// Converts raw bytes back to an `OsString`. Only lossless on Unix.
+#[inline]
+pub fn bytes_to_os_string(bytes: &[u8]) -> OsString {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+
+        OsStr::from_bytes(bytes).to_os_string()
+    }
+    #[cfg(not(unix))]
+    {
+        OsString::from(String::from_utf8_lossy(bytes).to_string())
+    }
+}
+
+/// Adds a name to a list of names. If the name isn't valid UTF-8 a lossy representation is
+/// added and the raw bytes are stored in `raw_names`.
+#[inline]
+pub fn push_name(names: &mut Vec<String>, raw_names: &mut Vec<RawName>, name: &OsStr) {
+    let (name, bytes) = os_str_to_string(name);
+    if let Some(bytes) = bytes {
+        raw_names.push(RawName {
+            index: names.len(),
+            bytes,
+        });
+    }
+    names.push(name);
+}
+
+/// Returns a list of names without loss.
+pub fn os_names(names: &[String], raw_names: &[RawName]) -> Vec<OsString> {
+    let mut os_names: Vec<OsString> = names.iter().map(OsString::from).collect();
+    for raw_name in raw_names {
+        if let Some(os_name) = os_names.get_mut(raw_name.index) {
+            *os_name = raw_name.os_name();
+        }
+    }
+    os_names
+}
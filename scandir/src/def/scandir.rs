@@ -1,10 +1,13 @@
+use std::borrow::Cow;
+use std::ffi::OsString;
+
 #[cfg(feature = "bincode")]
 use bincode::error::EncodeError;
 #[cfg(feature = "speedy")]
 use speedy::{Readable, Writable};
 
-use crate::ErrorsType;
 use crate::direntry::{DirEntry, DirEntryExt};
+use crate::{ErrorsType, ScanError, SortBy, SortFields, SymlinkLoop};
 
 #[cfg_attr(feature = "speedy", derive(Readable, Writable))]
 #[cfg_attr(
@@ -15,7 +18,8 @@ use crate::direntry::{DirEntry, DirEntryExt};
 pub enum ScandirResult {
     DirEntry(DirEntry),
     DirEntryExt(DirEntryExt),
-    Error((String, String)),
+    Error(ScanError),
+    SymlinkLoop(SymlinkLoop),
 }
 
 impl ScandirResult {
@@ -24,12 +28,24 @@ impl ScandirResult {
         match self {
             Self::DirEntry(e) => &e.path,
             Self::DirEntryExt(e) => &e.path,
-            Self::Error(e) => &e.0,
+            Self::Error(e) => &e.path,
+            Self::SymlinkLoop(e) => &e.path,
+        }
+    }
+
+    /// Returns the path of an entry without loss, also if it isn't valid UTF-8.
+    #[inline]
+    pub fn os_path(&self) -> OsString {
+        match self {
+            Self::DirEntry(e) => e.os_path(),
+            Self::DirEntryExt(e) => e.os_path(),
+            Self::Error(e) => OsString::from(&e.path),
+            Self::SymlinkLoop(e) => OsString::from(&e.path),
         }
     }
 
     #[inline]
-    pub fn error(&self) -> Option<&(String, String)> {
+    pub fn error(&self) -> Option<&ScanError> {
         match self {
             Self::Error(e) => Some(e),
             _ => None,
@@ -41,7 +57,7 @@ impl ScandirResult {
         match self {
             Self::DirEntry(e) => e.is_dir,
             Self::DirEntryExt(e) => e.is_dir,
-            Self::Error(_) => false,
+            _ => false,
         }
     }
 
@@ -50,7 +66,7 @@ impl ScandirResult {
         match self {
             Self::DirEntry(e) => e.is_file,
             Self::DirEntryExt(e) => e.is_file,
-            Self::Error(_) => false,
+            _ => false,
         }
     }
 
@@ -59,7 +75,7 @@ impl ScandirResult {
         match self {
             Self::DirEntry(e) => e.is_symlink,
             Self::DirEntryExt(e) => e.is_symlink,
-            Self::Error(_) => false,
+            _ => false,
         }
     }
 
@@ -68,7 +84,7 @@ impl ScandirResult {
         match self {
             Self::DirEntry(e) => e.ctime(),
             Self::DirEntryExt(e) => e.ctime(),
-            Self::Error(_) => 0.0,
+            _ => 0.0,
         }
     }
 
@@ -77,7 +93,7 @@ impl ScandirResult {
         match self {
             Self::DirEntry(e) => e.mtime(),
             Self::DirEntryExt(e) => e.mtime(),
-            Self::Error(_) => 0.0,
+            _ => 0.0,
         }
     }
 
@@ -86,7 +102,7 @@ impl ScandirResult {
         match self {
             Self::DirEntry(e) => e.atime(),
             Self::DirEntryExt(e) => e.atime(),
-            Self::Error(_) => 0.0,
+            _ => 0.0,
         }
     }
 
@@ -95,7 +111,34 @@ impl ScandirResult {
         match self {
             Self::DirEntry(e) => e.st_size,
             Self::DirEntryExt(e) => e.st_size,
-            Self::Error(_) => 0,
+            _ => 0,
+        }
+    }
+
+    /// Returns the digest of the file content if hashing is enabled.
+    #[inline]
+    pub fn digest(&self) -> Option<&String> {
+        match self {
+            Self::DirEntry(e) => e.digest.as_ref(),
+            Self::DirEntryExt(e) => e.digest.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Returns the values which are compared when sorting. The name is the path.
+    #[inline]
+    pub fn sort_fields(&self) -> SortFields<'_> {
+        let (size, mtime, ctime, atime) = match self {
+            Self::DirEntry(e) => (e.st_size, e.st_mtime, e.st_ctime, e.st_atime),
+            Self::DirEntryExt(e) => (e.st_size, e.st_mtime, e.st_ctime, e.st_atime),
+            _ => (0, None, None, None),
+        };
+        SortFields {
+            name: Cow::Borrowed(self.path()),
+            size,
+            mtime,
+            ctime,
+            atime,
         }
     }
 
@@ -132,6 +175,7 @@ impl ScandirResult {
 pub struct ScandirResults {
     pub results: Vec<ScandirResult>,
     pub errors: ErrorsType,
+    pub symlink_loops: Vec<SymlinkLoop>,
 }
 
 impl ScandirResults {
@@ -139,27 +183,47 @@ impl ScandirResults {
         ScandirResults {
             results: Vec::new(),
             errors: Vec::new(),
+            symlink_loops: Vec::new(),
         }
     }
 
     pub fn clear(&mut self) {
         self.results.clear();
         self.errors.clear();
+        self.symlink_loops.clear();
     }
 
     #[inline]
     pub fn is_empty(&self) -> bool {
-        self.results.is_empty() && self.errors.is_empty()
+        self.results.is_empty() && self.errors.is_empty() && self.symlink_loops.is_empty()
     }
 
     #[inline]
     pub fn len(&self) -> usize {
-        self.results.len() + self.errors.len()
+        self.results.len() + self.errors.len() + self.symlink_loops.len()
+    }
+
+    /// Adds a result received from the scanning thread. Errors and symlink loops are
+    /// stored separately.
+    #[inline]
+    pub fn push(&mut self, result: ScandirResult) {
+        match result {
+            ScandirResult::Error(e) => self.errors.push(e),
+            ScandirResult::SymlinkLoop(e) => self.symlink_loops.push(e),
+            _ => self.results.push(result),
+        }
+    }
+
+    /// Sorts the results of all directories. Names are compared by the paths of the entries.
+    pub fn sort(&mut self, sort_by: SortBy) {
+        self.results
+            .sort_by(|a, b| sort_by.compare(&a.sort_fields(), &b.sort_fields()));
     }
 
     pub fn extend(&mut self, results: &ScandirResults) {
         self.results.extend_from_slice(&results.results);
         self.errors.extend_from_slice(&results.errors);
+        self.symlink_loops.extend_from_slice(&results.symlink_loops);
     }
 
     #[cfg(feature = "speedy")]
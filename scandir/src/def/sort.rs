@@ -0,0 +1,197 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::SystemTime;
+
+/// Key for sorting entries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    /// Name, compared byte by byte.
+    Name,
+    /// Name in natural (version) order, e.g. `file2` before `file10`.
+    Natural,
+    /// Extension, then name.
+    Extension,
+    /// Size in bytes.
+    Size,
+    /// Modification time.
+    Mtime,
+    /// Creation time.
+    Ctime,
+    /// Access time.
+    Atime,
+}
+
+impl SortKey {
+    /// Returns `true` if the key needs the metadata of the entries.
+    #[inline]
+    pub fn needs_metadata(&self) -> bool {
+        matches!(self, Self::Size | Self::Mtime | Self::Ctime | Self::Atime)
+    }
+}
+
+/// Sort order of entries. Entries with equal keys are sorted by name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortBy {
+    pub key: SortKey,
+    pub descending: bool,
+}
+
+/// Values of an entry which are compared when sorting. Missing metadata is treated as size `0`
+/// and times before all other times.
+#[derive(Debug, Clone, Default)]
+pub struct SortFields<'a> {
+    pub name: Cow<'a, str>,
+    pub size: u64,
+    pub mtime: Option<SystemTime>,
+    pub ctime: Option<SystemTime>,
+    pub atime: Option<SystemTime>,
+}
+
+impl<'a> SortFields<'a> {
+    /// Returns the fields of an entry from its name and metadata.
+    pub fn from_metadata(name: Cow<'a, str>, metadata: Option<&fs::Metadata>) -> Self {
+        match metadata {
+            Some(metadata) => Self {
+                name,
+                size: metadata.len(),
+                mtime: metadata.modified().ok(),
+                ctime: metadata.created().ok(),
+                atime: metadata.accessed().ok(),
+            },
+            None => Self {
+                name,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Metadata of an entry which is compared when sorting. `Walk` stores it in the TOCs if the
+/// key of `sort_by` needs metadata, so that `Toc::sort` doesn't have to read it again.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SortMetadata {
+    pub size: u64,
+    pub mtime: Option<SystemTime>,
+    pub ctime: Option<SystemTime>,
+    pub atime: Option<SystemTime>,
+}
+
+impl SortMetadata {
+    /// Returns the fields of an entry from its name and the stored metadata.
+    #[inline]
+    pub fn sort_fields<'a>(&self, name: Cow<'a, str>) -> SortFields<'a> {
+        SortFields {
+            name,
+            size: self.size,
+            mtime: self.mtime,
+            ctime: self.ctime,
+            atime: self.atime,
+        }
+    }
+}
+
+impl SortBy {
+    pub fn new(key: SortKey, descending: bool) -> Self {
+        Self { key, descending }
+    }
+
+    /// Compares two entries.
+    #[inline]
+    pub fn compare(&self, a: &SortFields<'_>, b: &SortFields<'_>) -> Ordering {
+        let ordering = match self.key {
+            SortKey::Name => Ordering::Equal,
+            SortKey::Natural => natural_cmp(&a.name, &b.name),
+            SortKey::Extension => extension(&a.name).cmp(extension(&b.name)),
+            SortKey::Size => a.size.cmp(&b.size),
+            SortKey::Mtime => a.mtime.cmp(&b.mtime),
+            SortKey::Ctime => a.ctime.cmp(&b.ctime),
+            SortKey::Atime => a.atime.cmp(&b.atime),
+        }
+        .then_with(|| a.name.cmp(&b.name));
+        match self.descending {
+            true => ordering.reverse(),
+            false => ordering,
+        }
+    }
+}
+
+impl FromStr for SortBy {
+    type Err = Error;
+
+    /// Parses a sort key with an optional prefix `-` for descending order, e.g. `-size`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, descending) = match s.strip_prefix('-') {
+            Some(key) => (key, true),
+            None => (s.strip_prefix('+').unwrap_or(s), false),
+        };
+        let key = match key {
+            "name" => SortKey::Name,
+            "natural" | "version" => SortKey::Natural,
+            "ext" | "extension" => SortKey::Extension,
+            "size" => SortKey::Size,
+            "mtime" => SortKey::Mtime,
+            "ctime" => SortKey::Ctime,
+            "atime" => SortKey::Atime,
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Invalid sort key {s}"),
+                ));
+            }
+        };
+        Ok(Self::new(key, descending))
+    }
+}
+
+/// Returns the extension of a name or an empty string.
+#[inline]
+fn extension(name: &str) -> &str {
+    Path::new(name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+}
+
+/// Compares two names in natural order. Runs of digits are compared by their numeric value,
+/// so that `file2` is sorted before `file10`.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i].is_ascii_digit() && b[j].is_ascii_digit() {
+            let (start_a, start_b) = (i, j);
+            while i < a.len() && a[i].is_ascii_digit() {
+                i += 1;
+            }
+            while j < b.len() && b[j].is_ascii_digit() {
+                j += 1;
+            }
+            let number_a = trim_leading_zeros(&a[start_a..i]);
+            let number_b = trim_leading_zeros(&b[start_b..j]);
+            let ordering = number_a
+                .len()
+                .cmp(&number_b.len())
+                .then_with(|| number_a.cmp(number_b));
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        } else {
+            if a[i] != b[j] {
+                return a[i].cmp(&b[j]);
+            }
+            i += 1;
+            j += 1;
+        }
+    }
+    (a.len() - i).cmp(&(b.len() - j))
+}
+
+#[inline]
+fn trim_leading_zeros(digits: &[u8]) -> &[u8] {
+    let zeros = digits.iter().take_while(|&&digit| digit == b'0').count();
+    &digits[zeros..]
+}
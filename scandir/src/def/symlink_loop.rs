@@ -0,0 +1,17 @@
+#[cfg(feature = "speedy")]
+use speedy::{Readable, Writable};
+
+/// A symlink which points to one of its ancestor directories. The scan does not descend
+/// into such symlinks.
+#[cfg_attr(feature = "speedy", derive(Readable, Writable))]
+#[cfg_attr(
+    any(feature = "bincode", feature = "json"),
+    derive(Deserialize, Serialize)
+)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SymlinkLoop {
+    /// Path of the symlink.
+    pub path: String,
+    /// Target of the symlink as read from the file system.
+    pub target: String,
+}
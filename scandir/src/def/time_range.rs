@@ -0,0 +1,57 @@
+use std::time::{Duration, SystemTime};
+
+/// Bound of a `TimeRange`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeBound {
+    /// Absolute point in time.
+    At(SystemTime),
+    /// Age relative to the start of the scan.
+    Age(Duration),
+}
+
+impl TimeBound {
+    /// Returns the point in time of the bound. Ages are relative to `now`.
+    #[inline]
+    pub fn resolve(&self, now: SystemTime) -> SystemTime {
+        match self {
+            TimeBound::At(time) => *time,
+            TimeBound::Age(age) => now.checked_sub(*age).unwrap_or(SystemTime::UNIX_EPOCH),
+        }
+    }
+}
+
+/// Range of timestamps for filtering entries. Both bounds are inclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct TimeRange {
+    /// Timestamps must not be before this bound, e.g. "modified in the last hour".
+    pub newer: Option<TimeBound>,
+    /// Timestamps must not be after this bound, e.g. "older than 90 days".
+    pub older: Option<TimeBound>,
+}
+
+impl TimeRange {
+    pub fn new(newer: Option<TimeBound>, older: Option<TimeBound>) -> Self {
+        TimeRange { newer, older }
+    }
+
+    pub fn newer_than(bound: TimeBound) -> Self {
+        TimeRange {
+            newer: Some(bound),
+            older: None,
+        }
+    }
+
+    pub fn older_than(bound: TimeBound) -> Self {
+        TimeRange {
+            newer: None,
+            older: Some(bound),
+        }
+    }
+
+    /// Returns `true` if `time` is within the range. Ages are relative to `now`.
+    #[inline]
+    pub fn contains(&self, time: SystemTime, now: SystemTime) -> bool {
+        self.newer.is_none_or(|newer| time >= newer.resolve(now))
+            && self.older.is_none_or(|older| time <= older.resolve(now))
+    }
+}
@@ -1,10 +1,17 @@
-use std::path::PathBuf;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
 
 #[cfg(feature = "bincode")]
 use bincode::error::EncodeError;
 #[cfg(feature = "speedy")]
 use speedy::{Readable, Writable};
 
+use super::raw_name::{RawName, bytes_to_os_string, os_names, push_name};
+use crate::{ScanError, SortBy, SortFields, SortMetadata, SymlinkLoop};
+
 #[cfg_attr(feature = "speedy", derive(Readable, Writable))]
 #[cfg_attr(
     any(feature = "bincode", feature = "json"),
@@ -16,7 +23,23 @@ pub struct Toc {
     pub files: Vec<String>,
     pub symlinks: Vec<String>,
     pub other: Vec<String>,
-    pub errors: Vec<String>,
+    pub errors: Vec<ScanError>,
+    pub symlink_loops: Vec<SymlinkLoop>,
+    /// Raw bytes of the key of the TOC if it isn't valid UTF-8.
+    pub raw_dir: Option<Vec<u8>>,
+    /// Names in `dirs` which aren't valid UTF-8.
+    pub raw_dirs: Vec<RawName>,
+    /// Names in `files` which aren't valid UTF-8.
+    pub raw_files: Vec<RawName>,
+    /// Names in `symlinks` which aren't valid UTF-8.
+    pub raw_symlinks: Vec<RawName>,
+    /// Names in `other` which aren't valid UTF-8.
+    pub raw_other: Vec<RawName>,
+    /// Metadata for sorting per name. Only filled by `Walk` if the key of `sort_by` needs
+    /// metadata. Not serialized.
+    #[cfg_attr(feature = "speedy", speedy(skip))]
+    #[cfg_attr(any(feature = "bincode", feature = "json"), serde(skip))]
+    pub sort_metadata: HashMap<OsString, SortMetadata>,
 }
 
 impl Toc {
@@ -27,6 +50,13 @@ impl Toc {
             symlinks: Vec::new(),
             other: Vec::new(),
             errors: Vec::new(),
+            symlink_loops: Vec::new(),
+            raw_dir: None,
+            raw_dirs: Vec::new(),
+            raw_files: Vec::new(),
+            raw_symlinks: Vec::new(),
+            raw_other: Vec::new(),
+            sort_metadata: HashMap::new(),
         }
     }
 
@@ -36,6 +66,13 @@ impl Toc {
         self.symlinks.clear();
         self.other.clear();
         self.errors.clear();
+        self.symlink_loops.clear();
+        self.raw_dir = None;
+        self.raw_dirs.clear();
+        self.raw_files.clear();
+        self.raw_symlinks.clear();
+        self.raw_other.clear();
+        self.sort_metadata.clear();
     }
 
     pub fn dirs(&self) -> Vec<String> {
@@ -54,54 +91,98 @@ impl Toc {
         self.other.clone()
     }
 
-    pub fn errors(&self) -> Vec<String> {
+    /// Returns the key `dir` of the TOC without loss.
+    pub fn os_dir(&self, dir: &str) -> OsString {
+        match self.raw_dir {
+            Some(ref bytes) => bytes_to_os_string(bytes),
+            None => OsString::from(dir),
+        }
+    }
+
+    pub fn os_dirs(&self) -> Vec<OsString> {
+        os_names(&self.dirs, &self.raw_dirs)
+    }
+
+    pub fn os_files(&self) -> Vec<OsString> {
+        os_names(&self.files, &self.raw_files)
+    }
+
+    pub fn os_symlinks(&self) -> Vec<OsString> {
+        os_names(&self.symlinks, &self.raw_symlinks)
+    }
+
+    pub fn os_other(&self) -> Vec<OsString> {
+        os_names(&self.other, &self.raw_other)
+    }
+
+    pub fn errors(&self) -> Vec<ScanError> {
         self.errors.clone()
     }
 
+    pub fn symlink_loops(&self) -> Vec<SymlinkLoop> {
+        self.symlink_loops.clone()
+    }
+
     pub fn is_empty(&self) -> bool {
         self.dirs.is_empty()
             && self.files.is_empty()
             && self.symlinks.is_empty()
             && self.other.is_empty()
             && self.errors.is_empty()
+            && self.symlink_loops.is_empty()
+    }
+
+    /// Sorts the lists of names. If the sort key needs metadata, the metadata stored in
+    /// `sort_metadata` is used. For entries without stored metadata it is read again from the
+    /// paths of the entries relative to `root_path`. Symlinks aren't followed.
+    pub fn sort(&mut self, sort_by: SortBy, root_path: &Path) {
+        for (names, raw_names) in [
+            (&mut self.dirs, &mut self.raw_dirs),
+            (&mut self.files, &mut self.raw_files),
+            (&mut self.symlinks, &mut self.raw_symlinks),
+            (&mut self.other, &mut self.raw_other),
+        ] {
+            sort_names(names, raw_names, sort_by, root_path, &self.sort_metadata);
+        }
     }
 
     pub fn extend(&mut self, root_dir: &str, other: &Toc) {
-        self.dirs.extend_from_slice(
-            &other
-                .dirs
-                .iter()
-                .map(|x| PathBuf::from(root_dir).join(x).to_str().unwrap().to_owned())
-                .collect::<Vec<String>>(),
-        );
-        self.files.extend_from_slice(
-            &other
-                .files
-                .iter()
-                .map(|x| PathBuf::from(root_dir).join(x).to_str().unwrap().to_owned())
-                .collect::<Vec<String>>(),
-        );
-        self.symlinks.extend_from_slice(
-            &other
-                .symlinks
-                .iter()
-                .map(|x| PathBuf::from(root_dir).join(x).to_str().unwrap().to_owned())
-                .collect::<Vec<String>>(),
-        );
-        self.other.extend_from_slice(
-            &other
-                .other
-                .iter()
-                .map(|x| PathBuf::from(root_dir).join(x).to_str().unwrap().to_owned())
-                .collect::<Vec<String>>(),
-        );
-        self.errors.extend_from_slice(
-            &other
-                .errors
-                .iter()
-                .map(|x| PathBuf::from(root_dir).join(x).to_str().unwrap().to_owned())
-                .collect::<Vec<String>>(),
-        );
+        let root_dir = PathBuf::from(other.os_dir(root_dir));
+        for name in other.os_dirs() {
+            push_name(
+                &mut self.dirs,
+                &mut self.raw_dirs,
+                root_dir.join(name).as_os_str(),
+            );
+        }
+        for name in other.os_files() {
+            push_name(
+                &mut self.files,
+                &mut self.raw_files,
+                root_dir.join(name).as_os_str(),
+            );
+        }
+        for name in other.os_symlinks() {
+            push_name(
+                &mut self.symlinks,
+                &mut self.raw_symlinks,
+                root_dir.join(name).as_os_str(),
+            );
+        }
+        for name in other.os_other() {
+            push_name(
+                &mut self.other,
+                &mut self.raw_other,
+                root_dir.join(name).as_os_str(),
+            );
+        }
+        for (name, metadata) in &other.sort_metadata {
+            self.sort_metadata
+                .insert(root_dir.join(name).into_os_string(), *metadata);
+        }
+        // Errors already contain the affected path
+        self.errors.extend_from_slice(&other.errors);
+        self.symlink_loops.extend_from_slice(&other.symlink_loops);
     }
 
     #[cfg(feature = "speedy")]
@@ -120,6 +201,39 @@ impl Toc {
     }
 }
 
+/// Sorts a list of names and updates the indexes of the names which aren't valid UTF-8.
+fn sort_names(
+    names: &mut Vec<String>,
+    raw_names: &mut Vec<RawName>,
+    sort_by: SortBy,
+    root_path: &Path,
+    sort_metadata: &HashMap<OsString, SortMetadata>,
+) {
+    let mut entries = os_names(names, raw_names)
+        .into_iter()
+        .map(|os_name| {
+            let name = Cow::Owned(os_name.to_string_lossy().to_string());
+            let fields = match sort_metadata.get(&os_name) {
+                Some(metadata) => metadata.sort_fields(name),
+                None => {
+                    let metadata = match sort_by.key.needs_metadata() {
+                        true => fs::symlink_metadata(root_path.join(&os_name)).ok(),
+                        false => None,
+                    };
+                    SortFields::from_metadata(name, metadata.as_ref())
+                }
+            };
+            (os_name, fields)
+        })
+        .collect::<Vec<_>>();
+    entries.sort_by(|a, b| sort_by.compare(&a.1, &b.1));
+    names.clear();
+    raw_names.clear();
+    for (os_name, _) in entries {
+        push_name(names, raw_names, &os_name);
+    }
+}
+
 impl Default for Toc {
     fn default() -> Self {
         Self::new()
@@ -0,0 +1,224 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::ffi::OsString;
+
+#[cfg(feature = "bincode")]
+use bincode::error::EncodeError;
+#[cfg(feature = "speedy")]
+use speedy::{Readable, Writable};
+
+use super::raw_name::bytes_to_os_string;
+
+/// A file or directory in the report of the largest entries.
+#[cfg_attr(feature = "speedy", derive(Readable, Writable))]
+#[cfg_attr(
+    any(feature = "bincode", feature = "json"),
+    derive(Deserialize, Serialize)
+)]
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TopEntry {
+    /// Path relative to the root path. In multi-root mode the full path.
+    pub path: String,
+    /// Raw bytes of the path if it isn't valid UTF-8. `path` then contains a lossy representation.
+    pub path_bytes: Option<Vec<u8>>,
+    /// Apparent size in bytes. For directories including all descendants.
+    pub size: u64,
+    /// Usage on disk in bytes. For directories including all descendants.
+    pub usage: u64,
+}
+
+impl TopEntry {
+    /// Returns the path without loss, also if it isn't valid UTF-8.
+    #[inline]
+    pub fn os_path(&self) -> OsString {
+        match self.path_bytes {
+            Some(ref bytes) => bytes_to_os_string(bytes),
+            None => OsString::from(&self.path),
+        }
+    }
+}
+
+/// The largest files and directories found while scanning. All lists are sorted
+/// in descending order.
+#[cfg_attr(feature = "speedy", derive(Readable, Writable))]
+#[cfg_attr(
+    any(feature = "bincode", feature = "json"),
+    derive(Deserialize, Serialize)
+)]
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TopN {
+    /// Largest files by apparent size.
+    pub files_by_size: Vec<TopEntry>,
+    /// Largest files by usage on disk.
+    pub files_by_usage: Vec<TopEntry>,
+    /// Largest directories by apparent size of all descendants.
+    pub dirs_by_size: Vec<TopEntry>,
+    /// Largest directories by usage on disk of all descendants.
+    pub dirs_by_usage: Vec<TopEntry>,
+}
+
+impl TopN {
+    #[cfg(feature = "speedy")]
+    pub fn to_speedy(&self) -> Result<Vec<u8>, speedy::Error> {
+        self.write_to_vec()
+    }
+
+    #[cfg(feature = "bincode")]
+    pub fn to_vec(&self) -> Result<Vec<u8>, EncodeError> {
+        bincode::serde::encode_to_vec(self, bincode::config::legacy())
+    }
+
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct HeapItem {
+    key: u64,
+    entry: TopEntry,
+}
+
+impl PartialEq for HeapItem {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl Eq for HeapItem {}
+
+impl PartialOrd for HeapItem {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for HeapItem {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+/// Min-heap which keeps the `n` items with the largest keys.
+#[derive(Debug, Clone)]
+struct BoundedHeap {
+    n: usize,
+    heap: BinaryHeap<Reverse<HeapItem>>,
+}
+
+impl BoundedHeap {
+    fn new(n: usize) -> Self {
+        BoundedHeap {
+            n,
+            heap: BinaryHeap::with_capacity(n + 1),
+        }
+    }
+
+    /// Returns `true` if an item with `key` would be kept.
+    #[inline]
+    fn accepts(&self, key: u64) -> bool {
+        if self.heap.len() < self.n {
+            return true;
+        }
+        self.heap.peek().is_some_and(|Reverse(min)| key > min.key)
+    }
+
+    #[inline]
+    fn push(&mut self, key: u64, entry: TopEntry) {
+        self.heap.push(Reverse(HeapItem { key, entry }));
+        if self.heap.len() > self.n {
+            self.heap.pop();
+        }
+    }
+
+    fn sorted(&self) -> Vec<TopEntry> {
+        let mut items: Vec<&HeapItem> = self.heap.iter().map(|Reverse(item)| item).collect();
+        items.sort_by_key(|item| Reverse(item.key));
+        items.into_iter().map(|item| item.entry.clone()).collect()
+    }
+}
+
+/// Keeps the `n` largest files and directories without storing all entries.
+#[derive(Debug, Clone)]
+pub(crate) struct TopNTracker {
+    files_by_size: BoundedHeap,
+    files_by_usage: BoundedHeap,
+    dirs_by_size: BoundedHeap,
+    dirs_by_usage: BoundedHeap,
+}
+
+impl TopNTracker {
+    pub(crate) fn new(n: usize) -> Self {
+        TopNTracker {
+            files_by_size: BoundedHeap::new(n),
+            files_by_usage: BoundedHeap::new(n),
+            dirs_by_size: BoundedHeap::new(n),
+            dirs_by_usage: BoundedHeap::new(n),
+        }
+    }
+
+    /// Adds an entry to the heaps. The path is only created if the entry is kept.
+    #[inline]
+    fn add<F>(by_size: &mut BoundedHeap, by_usage: &mut BoundedHeap, size: u64, usage: u64, path: F)
+    where
+        F: FnOnce() -> (String, Option<Vec<u8>>),
+    {
+        let keep_size = by_size.accepts(size);
+        let keep_usage = by_usage.accepts(usage);
+        if !keep_size && !keep_usage {
+            return;
+        }
+        let (path, path_bytes) = path();
+        let entry = TopEntry {
+            path,
+            path_bytes,
+            size,
+            usage,
+        };
+        if keep_size && keep_usage {
+            by_size.push(size, entry.clone());
+        } else if keep_size {
+            by_size.push(size, entry);
+            return;
+        }
+        by_usage.push(usage, entry);
+    }
+
+    #[inline]
+    pub(crate) fn add_file<F>(&mut self, size: u64, usage: u64, path: F)
+    where
+        F: FnOnce() -> (String, Option<Vec<u8>>),
+    {
+        Self::add(
+            &mut self.files_by_size,
+            &mut self.files_by_usage,
+            size,
+            usage,
+            path,
+        );
+    }
+
+    #[inline]
+    pub(crate) fn add_dir<F>(&mut self, size: u64, usage: u64, path: F)
+    where
+        F: FnOnce() -> (String, Option<Vec<u8>>),
+    {
+        Self::add(
+            &mut self.dirs_by_size,
+            &mut self.dirs_by_usage,
+            size,
+            usage,
+            path,
+        );
+    }
+
+    pub(crate) fn report(&self) -> TopN {
+        TopN {
+            files_by_size: self.files_by_size.sorted(),
+            files_by_usage: self.files_by_usage.sorted(),
+            dirs_by_size: self.dirs_by_size.sorted(),
+            dirs_by_usage: self.dirs_by_usage.sorted(),
+        }
+    }
+}
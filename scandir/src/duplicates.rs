@@ -0,0 +1,201 @@
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::io::Error;
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+
+use crate::def::hash::{HashAlgorithm, HashMode, hash_file};
+use crate::def::raw_name::push_name;
+use crate::def::{DuplicateSet, Duplicates, ErrorPhase, ErrorsType, ScanError, ScandirResult};
+
+#[derive(Debug)]
+struct Candidate {
+    /// Path as returned by the scan.
+    path: OsString,
+    /// Path used for reading the file.
+    full_path: PathBuf,
+    /// Digest of the whole file content if it was already created while scanning.
+    digest: Option<String>,
+}
+
+#[derive(Debug)]
+struct Group {
+    size: u64,
+    digest: String,
+    files: Vec<Candidate>,
+}
+
+/// Size of the files of a group and the results of hashing them.
+type HashedGroup = (u64, Vec<(Candidate, Result<String, Error>)>);
+
+/// Returns device and inode number of a file. Hardlinks share the same ids.
+fn file_id(entry: &ScandirResult, full_path: &Path) -> Option<(u64, u64)> {
+    if let Some(ext) = entry.ext()
+        && ext.st_ino != 0
+    {
+        return Some((ext.st_dev, ext.st_ino));
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        std::fs::metadata(full_path)
+            .ok()
+            .map(|metadata| (metadata.dev(), metadata.ino()))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = full_path;
+        None
+    }
+}
+
+/// Splits each group by the digests returned by `digest`. The files are hashed in parallel.
+/// Groups with only one file are dropped.
+fn split_groups<F>(groups: Vec<Group>, errors: &mut ErrorsType, digest: F) -> Vec<Group>
+where
+    F: Fn(&Candidate, u64) -> Result<String, Error> + Sync,
+{
+    let hashed: Vec<HashedGroup> = groups
+        .into_par_iter()
+        .map(|group| {
+            let size = group.size;
+            let files = group
+                .files
+                .into_par_iter()
+                .map(|candidate| {
+                    let result = digest(&candidate, size);
+                    (candidate, result)
+                })
+                .collect();
+            (size, files)
+        })
+        .collect();
+    let mut split = Vec::new();
+    for (size, files) in hashed {
+        let mut by_digest: HashMap<String, Vec<Candidate>> = HashMap::new();
+        for (candidate, result) in files {
+            match result {
+                Ok(digest) => by_digest.entry(digest).or_default().push(candidate),
+                Err(e) => errors.push(ScanError::from_io(
+                    &candidate.full_path,
+                    ErrorPhase::Hash,
+                    &e,
+                )),
+            }
+        }
+        split.extend(
+            by_digest
+                .into_iter()
+                .filter(|(_, files)| files.len() > 1)
+                .map(|(digest, files)| Group {
+                    size,
+                    digest,
+                    files,
+                }),
+        );
+    }
+    split
+}
+
+/// Finds files with identical content in the results of a scan.
+///
+/// The files are grouped by size first, then by the hash of the first and the last
+/// `partial_size` bytes and finally by the hash of the whole content. Only files with
+/// a matching size are read. Hardlinks to the same file are not reported as duplicates.
+/// Empty files are ignored. If `partial_size` is `0` the partial hashing step is skipped.
+///
+/// The paths of the results are relative to `root_path`. If `reuse_digests` is `true`
+/// the digests stored in the results are used. They must have been created with
+/// `algorithm` and `HashMode::Full`.
+pub fn find_duplicates(
+    root_path: &Path,
+    results: &[ScandirResult],
+    algorithm: HashAlgorithm,
+    partial_size: u64,
+    reuse_digests: bool,
+) -> Duplicates {
+    let mut by_size: HashMap<u64, Vec<&ScandirResult>> = HashMap::new();
+    for entry in results.iter() {
+        if entry.is_file() && entry.size() > 0 {
+            by_size.entry(entry.size()).or_default().push(entry);
+        }
+    }
+    let mut groups = Vec::new();
+    for (size, entries) in by_size {
+        if entries.len() < 2 {
+            continue;
+        }
+        let mut file_ids = HashSet::new();
+        let files: Vec<Candidate> = entries
+            .into_iter()
+            .filter_map(|entry| {
+                let path = entry.os_path();
+                let full_path = root_path.join(&path);
+                if let Some(file_id) = file_id(entry, &full_path)
+                    && !file_ids.insert(file_id)
+                {
+                    return None;
+                }
+                Some(Candidate {
+                    path,
+                    full_path,
+                    digest: entry.digest().filter(|_| reuse_digests).cloned(),
+                })
+            })
+            .collect();
+        if files.len() > 1 {
+            groups.push(Group {
+                size,
+                digest: String::new(),
+                files,
+            });
+        }
+    }
+
+    let mut errors = Vec::new();
+    if partial_size > 0 {
+        groups = split_groups(groups, &mut errors, |candidate, size| {
+            // Small files and files with a known digest are compared by the full hash only
+            if size <= partial_size * 2 || candidate.digest.is_some() {
+                return Ok(String::new());
+            }
+            hash_file(
+                &candidate.full_path,
+                algorithm,
+                HashMode::Partial(partial_size),
+            )
+        });
+    }
+    groups = split_groups(groups, &mut errors, |candidate, _| match candidate.digest {
+        Some(ref digest) => Ok(digest.clone()),
+        None => hash_file(&candidate.full_path, algorithm, HashMode::Full),
+    });
+
+    let mut sets: Vec<DuplicateSet> = groups
+        .into_iter()
+        .map(|mut group| {
+            group.files.sort_by(|a, b| a.path.cmp(&b.path));
+            let mut set = DuplicateSet {
+                size: group.size,
+                digest: group.digest,
+                ..Default::default()
+            };
+            for candidate in group.files.iter() {
+                push_name(&mut set.paths, &mut set.raw_paths, &candidate.path);
+            }
+            set
+        })
+        .collect();
+    sets.sort_by(|a, b| {
+        b.reclaimable()
+            .cmp(&a.reclaimable())
+            .then_with(|| a.paths.cmp(&b.paths))
+    });
+    Duplicates {
+        reclaimable: sets.iter().map(|set| set.reclaimable()).sum(),
+        sets,
+        errors,
+    }
+}
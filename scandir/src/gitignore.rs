@@ -0,0 +1,102 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use ignore::Match;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use jwalk_meta::ClientState;
+
+/// Directory of a git repository, which is never returned in gitignore mode. In worktrees and
+/// submodules `.git` is a file.
+const GIT_DIR: &str = ".git";
+
+/// Ignore files of a directory in ascending order of precedence.
+const IGNORE_FILES: [&str; 2] = [".gitignore", ".ignore"];
+
+/// Gitignore matchers of a directory and of its ancestors, the innermost directory last.
+/// Used as read dir state of the walker, so that every directory only reads its own ignore files.
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreStack {
+    matchers: Arc<Vec<Arc<Gitignore>>>,
+    initialized: bool,
+}
+
+impl IgnoreStack {
+    /// Adds the ignore files of the directory being read. For the first directory the ignore
+    /// files of its ancestors up to the root of the git repository are added too.
+    pub fn enter(&mut self, dir_path: &Path) {
+        let mut added = Vec::new();
+        if !self.initialized {
+            self.initialized = true;
+            let ancestors = dir_path.ancestors().skip(1).collect::<Vec<_>>();
+            let repo_root = match is_repo_root(dir_path) {
+                true => None,
+                false => ancestors.iter().position(|path| is_repo_root(path)),
+            };
+            if let Some(repo_root) = repo_root {
+                for ancestor in ancestors[..=repo_root].iter().rev() {
+                    added.extend(read_ignore_files(ancestor));
+                }
+            }
+        }
+        added.extend(read_ignore_files(dir_path));
+        if added.is_empty() {
+            return;
+        }
+        let mut matchers = self.matchers.to_vec();
+        matchers.extend(added.into_iter().map(Arc::new));
+        self.matchers = Arc::new(matchers);
+    }
+
+    /// Returns `true` if a path is ignored. The innermost matching pattern decides, so that
+    /// negated patterns of subdirectories re-include paths.
+    #[inline]
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        for matcher in self.matchers.iter().rev() {
+            match matcher.matched(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+        false
+    }
+}
+
+#[inline]
+fn is_repo_root(path: &Path) -> bool {
+    path.join(GIT_DIR).exists()
+}
+
+/// Reads `.git/info/exclude`, `.gitignore` and `.ignore` of a directory. Files which can't be
+/// read and invalid patterns are skipped, as git does.
+fn read_ignore_files(dir_path: &Path) -> Option<Gitignore> {
+    let mut builder = GitignoreBuilder::new(dir_path);
+    let exclude = dir_path.join(GIT_DIR).join("info").join("exclude");
+    if exclude.is_file() {
+        let _ = builder.add(exclude);
+    }
+    for name in IGNORE_FILES {
+        let _ = builder.add(dir_path.join(name));
+    }
+    builder.build().ok().filter(|matcher| !matcher.is_empty())
+}
+
+/// Reads the ignore files of the directory being read and removes the ignored entries from
+/// `children`. Ignored directories are not descended into. Like git, `.git` is always ignored,
+/// also if hidden entries are not skipped.
+#[inline]
+pub fn filter_ignored<C: ClientState>(
+    children: &mut Vec<Result<jwalk_meta::DirEntry<C>, jwalk_meta::Error>>,
+    dir_path: &Path,
+    ignores: &mut IgnoreStack,
+) {
+    ignores.enter(dir_path);
+    children.retain(|dir_entry_result| match dir_entry_result {
+        Ok(dir_entry) => {
+            dir_entry.file_name != GIT_DIR
+                && (ignores.matchers.is_empty()
+                    || !ignores.is_ignored(&dir_entry.path(), dir_entry.file_type.is_dir()))
+        }
+        Err(_) => true,
+    });
+}
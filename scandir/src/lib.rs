@@ -8,10 +8,11 @@
 //! - `Count` for determining statistics of a directory.
 //! - `Walk` for getting names of directory entries.
 //! - `Scandir` for getting detailed stats of directory entries.
+//!
+//! `find_duplicates` finds files with identical content in the results of `Scandir`.
 
 #![cfg_attr(windows, feature(windows_by_handle))]
 
-extern crate glob_sl;
 #[cfg_attr(any(feature = "bincode", feature = "json"), macro_use)]
 #[cfg(any(feature = "bincode", feature = "json"))]
 extern crate serde_derive;
@@ -20,8 +21,11 @@ pub mod def;
 pub use def::*;
 pub mod common;
 pub mod count;
+pub mod gitignore;
 pub use count::*;
 pub mod walk;
 pub use walk::*;
 pub mod scandir;
 pub use scandir::*;
+pub mod duplicates;
+pub use duplicates::*;
@@ -1,22 +1,38 @@
-use std::collections::HashSet;
-use std::fs::Metadata;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::io::{Error, ErrorKind};
 use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::{Instant, SystemTime};
+use std::time::{Duration, Instant, SystemTime};
 
 #[cfg(feature = "bincode")]
 use bincode::error::EncodeError;
-use flume::{Receiver, Sender, unbounded};
+use flume::Receiver;
 
-use jwalk_meta::WalkDirGeneric;
+use jwalk_meta::{DirEntryIter, WalkDirGeneric};
 
 use crate::Statistics;
-use crate::common::{check_and_expand_path, create_filter, filter_children, get_root_path_len};
+use crate::common::{
+    EntryState, ReadDirState, ResultSender, check_and_expand_path, check_and_expand_paths,
+    create_channel, create_filter, detect_symlink_loops, filter_children, get_root_device,
+    get_root_path_len, hash_children, mount_point_error, output_entry, prune_mount_points,
+    relative_path, root_error, sort_children,
+};
+use crate::def::hash::hash_file;
+use crate::def::raw_name::os_str_to_string;
 use crate::def::scandir::ScandirResults;
-use crate::def::{DirEntry, DirEntryExt, ErrorsType, Filter, Options, ReturnType, ScandirResult};
+use crate::def::top_n::TopNTracker;
+use crate::def::{
+    DirEntry, DirEntryExt, Duplicates, EntryFilter, EntryType, ErrorPhase, ErrorsType, Filter,
+    HashAlgorithm, HashMode, Options, PermMatch, ReturnType, ScanError, ScandirResult, SortBy,
+    SymlinkLoop, TimeRange, TopN, UsageTotals, default_age_buckets, default_multi_part_extensions,
+};
+use crate::duplicates::find_duplicates;
+use crate::gitignore::filter_ignored;
+
+/// The client state of an entry contains the digest of the file content if hashing is enabled.
+type ScandirDirEntry = jwalk_meta::DirEntry<(ReadDirState, EntryState<Option<String>>)>;
 
 #[derive(Debug, Clone)]
 pub enum Stats {
@@ -29,7 +45,7 @@ pub enum Stats {
 fn create_entry(
     root_path_len: usize,
     return_type: &ReturnType,
-    dir_entry: &jwalk_meta::DirEntry<((), Option<Result<Metadata, Error>>)>,
+    dir_entry: &ScandirDirEntry,
 ) -> ScandirResult {
     let file_type = dir_entry.file_type;
     let mut st_ctime: Option<SystemTime> = None;
@@ -98,16 +114,13 @@ fn create_entry(
         }
     }
     let is_file = file_type.is_file();
-    let path_str = dir_entry.parent_path.to_str().unwrap();
-    let mut path = if path_str.len() > root_path_len {
-        PathBuf::from(&path_str[root_path_len..])
-    } else {
-        PathBuf::new()
-    };
+    let mut path = relative_path(&dir_entry.parent_path, root_path_len);
     path.push(&dir_entry.file_name);
+    let (path, path_bytes) = os_str_to_string(path.as_os_str());
     let entry: ScandirResult = match return_type {
         ReturnType::Base => ScandirResult::DirEntry(DirEntry {
-            path: path.to_str().unwrap().to_string(),
+            path,
+            path_bytes,
             is_symlink: file_type.is_symlink(),
             is_dir: file_type.is_dir(),
             is_file,
@@ -115,9 +128,11 @@ fn create_entry(
             st_mtime,
             st_atime,
             st_size,
+            digest: dir_entry.client_state.data.clone(),
         }),
         ReturnType::Ext => ScandirResult::DirEntryExt(DirEntryExt {
-            path: path.to_str().unwrap().to_string(),
+            path,
+            path_bytes,
             is_symlink: file_type.is_symlink(),
             is_dir: file_type.is_dir(),
             is_file,
@@ -134,77 +149,407 @@ fn create_entry(
             st_uid,
             st_gid,
             st_rdev,
+            digest: dir_entry.client_state.data.clone(),
         }),
     };
     entry
 }
 
+/// Hashes the content of a root path which is a file.
+fn hash_root_file(dir_entry: &mut ScandirDirEntry, options: &Options) -> Result<(), ScanError> {
+    let Some(algorithm) = options.hash_algorithm else {
+        return Ok(());
+    };
+    if !dir_entry.file_type.is_file() {
+        return Ok(());
+    }
+    let path = dir_entry.path();
+    let digest = hash_file(&path, algorithm, options.hash_mode)
+        .map_err(|e| ScanError::from_io(&path, ErrorPhase::Hash, &e))?;
+    dir_entry.client_state.data = Some(digest);
+    Ok(())
+}
+
 fn entries_thread(
     options: Options,
     filter: Option<Filter>,
-    tx: Sender<ScandirResult>,
+    tx: ResultSender<ScandirResult>,
     stop: Arc<AtomicBool>,
 ) {
-    let root_path_len = get_root_path_len(&options.root_path);
-    let return_type = options.return_type.clone();
+    let max_file_cnt = options.max_file_cnt;
+    let mut file_cnt = 0;
 
-    let dir_entry = jwalk_meta::DirEntry::from_path(
-        0,
-        &options.root_path,
-        true,
-        true,
-        options.follow_links,
-        Arc::new(Vec::new()),
-    )
-    .unwrap();
+    for root_path in options.roots() {
+        if stop.load(Ordering::Relaxed) {
+            break;
+        }
+        let root_path_len = get_root_path_len(&root_path);
+        // In multi-root mode the paths of the entries include the root path
+        let path_offset = match options.is_multi_root() {
+            true => 0,
+            false => root_path_len,
+        };
+        let return_type = options.return_type.clone();
 
-    if !dir_entry.file_type.is_dir() {
-        let _ = tx.send(create_entry(root_path_len, &return_type, &dir_entry));
-        return;
-    }
+        let mut dir_entry = match jwalk_meta::DirEntry::from_path(
+            0,
+            &root_path,
+            true,
+            true,
+            options.follow_links,
+            Arc::new(Vec::new()),
+        ) {
+            Ok(dir_entry) => dir_entry,
+            Err(e) => {
+                let _ = tx.send(ScandirResult::Error(root_error(&root_path, &e)));
+                continue;
+            }
+        };
 
-    let max_file_cnt = options.max_file_cnt;
-    let mut file_cnt = 0;
+        if !dir_entry.file_type.is_dir() {
+            if let Err(e) = hash_root_file(&mut dir_entry, &options) {
+                let _ = tx.send(ScandirResult::Error(e));
+            }
+            let _ = tx.send(create_entry(path_offset, &return_type, &dir_entry));
+            continue;
+        }
 
-    for result in WalkDirGeneric::new(&options.root_path)
-        .skip_hidden(options.skip_hidden)
-        .follow_links(options.follow_links)
-        .sort(options.sorted)
-        .max_depth(options.max_depth)
-        .read_metadata(true)
-        .read_metadata_ext(options.return_type == ReturnType::Ext)
-        .process_read_dir(move |_, root_dir, _, children| {
-            if let Some(root_dir) = root_dir.to_str() {
-                if root_dir.len() + 1 < root_path_len {
+        let root_device = get_root_device(&root_path, options.one_file_system);
+        let follow_links = options.follow_links;
+        let gitignore = options.gitignore;
+        let sort_by = options.sort_by;
+        let hash_algorithm = options.hash_algorithm;
+        let hash_mode = options.hash_mode;
+        let filter_needs_metadata_ext = filter.as_ref().is_some_and(Filter::needs_metadata_ext);
+        let filter = filter.clone();
+        let dir_tx = tx.clone();
+        for result in WalkDirGeneric::<(ReadDirState, EntryState<Option<String>>)>::new(&root_path)
+            .skip_hidden(options.skip_hidden)
+            .follow_links(options.follow_links)
+            .sort(options.sorted && options.sort_by.is_none())
+            .max_depth(options.max_depth)
+            .read_metadata(true)
+            .read_metadata_ext(options.return_type == ReturnType::Ext || filter_needs_metadata_ext)
+            .process_read_dir(move |_, root_dir, state, children| {
+                if root_dir.as_os_str().len() + 1 < root_path_len {
                     return;
                 }
-            } else {
+                if gitignore {
+                    filter_ignored(children, root_dir, &mut state.ignores);
+                }
+                filter_children(children, &filter, root_path_len);
+                sort_children(children, sort_by);
+                for mount_point in prune_mount_points(children, root_device) {
+                    let _ = dir_tx.send(ScandirResult::Error(mount_point_error(mount_point)));
+                }
+                if follow_links {
+                    let detected = detect_symlink_loops(root_dir, &mut state.dir_ids, children);
+                    for symlink_loop in detected {
+                        let _ = dir_tx.send(ScandirResult::SymlinkLoop(symlink_loop));
+                    }
+                }
+                if let Some(algorithm) = hash_algorithm {
+                    for error in hash_children(children, algorithm, hash_mode) {
+                        let _ = dir_tx.send(ScandirResult::Error(error));
+                    }
+                }
+                #[allow(clippy::needless_return)]
+                children.iter_mut().for_each(|dir_entry_result| {
+                    if let Ok(dir_entry) = dir_entry_result
+                        && output_entry(dir_entry, &filter, root_path_len)
+                        && !dir_tx.send(create_entry(path_offset, &return_type, dir_entry))
+                    {
+                        return;
+                    }
+                });
+            })
+        {
+            if stop.load(Ordering::Relaxed) {
                 return;
             }
-            filter_children(children, &filter, root_path_len);
-            #[allow(clippy::needless_return)]
-            children.iter_mut().for_each(|dir_entry_result| {
-                if let Ok(dir_entry) = dir_entry_result
-                    && tx
-                        .send(create_entry(root_path_len, &return_type, dir_entry))
-                        .is_err()
-                {
-                    return;
+            match result {
+                Ok(dir_entry) => {
+                    if !dir_entry.file_type.is_dir() {
+                        file_cnt += 1;
+                        if max_file_cnt > 0 && file_cnt > max_file_cnt {
+                            return;
+                        }
+                    }
                 }
-            });
-        })
-    {
-        if stop.load(Ordering::Relaxed) {
-            break;
+                Err(e) => {
+                    let _ = tx.send(ScandirResult::Error(ScanError::from(&e)));
+                }
+            }
+        }
+    }
+}
+
+/// Iterator returned by `Scandir::iter`.
+///
+/// The file tree is walked on the calling thread and entries are created lazily
+/// when requested.
+pub struct ScandirIter {
+    options: Options,
+    filter: Option<Filter>,
+    roots: std::vec::IntoIter<PathBuf>,
+    #[allow(clippy::type_complexity)]
+    iter: Option<DirEntryIter<(ReadDirState, EntryState<Option<String>>)>>,
+    root_entry: Option<ScandirResult>,
+    mount_points: Arc<Mutex<VecDeque<String>>>,
+    symlink_loops: Arc<Mutex<VecDeque<SymlinkLoop>>>,
+    hash_errors: Arc<Mutex<VecDeque<ScanError>>>,
+    root_path_len: usize,
+    path_offset: usize,
+    file_cnt: usize,
+}
+
+impl ScandirIter {
+    fn new(options: &Options, filter: Option<Filter>) -> Self {
+        ScandirIter {
+            options: options.clone(),
+            filter,
+            roots: options.roots().into_iter(),
+            iter: None,
+            root_entry: None,
+            mount_points: Arc::new(Mutex::new(VecDeque::new())),
+            symlink_loops: Arc::new(Mutex::new(VecDeque::new())),
+            hash_errors: Arc::new(Mutex::new(VecDeque::new())),
+            root_path_len: 0,
+            path_offset: 0,
+            file_cnt: 0,
+        }
+    }
+
+    fn start_root(&mut self, root_path: &Path) {
+        let root_path_len = get_root_path_len(root_path);
+        self.root_path_len = root_path_len;
+        self.path_offset = match self.options.is_multi_root() {
+            true => 0,
+            false => root_path_len,
+        };
+
+        let mut dir_entry = match jwalk_meta::DirEntry::from_path(
+            0,
+            root_path,
+            true,
+            true,
+            self.options.follow_links,
+            Arc::new(Vec::new()),
+        ) {
+            Ok(dir_entry) => dir_entry,
+            Err(e) => {
+                self.root_entry = Some(ScandirResult::Error(root_error(root_path, &e)));
+                return;
+            }
+        };
+
+        if !dir_entry.file_type.is_dir() {
+            if let Err(e) = hash_root_file(&mut dir_entry, &self.options) {
+                self.hash_errors.lock().unwrap().push_back(e);
+            }
+            self.root_entry = Some(create_entry(
+                self.path_offset,
+                &self.options.return_type,
+                &dir_entry,
+            ));
+            return;
+        }
+
+        let root_device = get_root_device(root_path, self.options.one_file_system);
+        let follow_links = self.options.follow_links;
+        let gitignore = self.options.gitignore;
+        let sort_by = self.options.sort_by;
+        let mount_points = self.mount_points.clone();
+        let symlink_loops = self.symlink_loops.clone();
+        let hash_algorithm = self.options.hash_algorithm;
+        let hash_mode = self.options.hash_mode;
+        let hash_errors = self.hash_errors.clone();
+        let filter_needs_metadata_ext =
+            self.filter.as_ref().is_some_and(Filter::needs_metadata_ext);
+        let filter = self.filter.clone();
+        self.iter = Some(
+            WalkDirGeneric::<(ReadDirState, EntryState<Option<String>>)>::new(root_path)
+                .skip_hidden(self.options.skip_hidden)
+                .follow_links(self.options.follow_links)
+                .sort(self.options.sorted && self.options.sort_by.is_none())
+                .max_depth(self.options.max_depth)
+                .read_metadata(true)
+                .read_metadata_ext(
+                    self.options.return_type == ReturnType::Ext || filter_needs_metadata_ext,
+                )
+                .process_read_dir(move |_, root_dir, state, children| {
+                    if root_dir.as_os_str().len() + 1 < root_path_len {
+                        return;
+                    }
+                    if gitignore {
+                        filter_ignored(children, root_dir, &mut state.ignores);
+                    }
+                    filter_children(children, &filter, root_path_len);
+                    sort_children(children, sort_by);
+                    let crossed = prune_mount_points(children, root_device);
+                    if !crossed.is_empty() {
+                        mount_points.lock().unwrap().extend(crossed);
+                    }
+                    if follow_links {
+                        let detected = detect_symlink_loops(root_dir, &mut state.dir_ids, children);
+                        if !detected.is_empty() {
+                            symlink_loops.lock().unwrap().extend(detected);
+                        }
+                    }
+                    if let Some(algorithm) = hash_algorithm {
+                        let errors = hash_children(children, algorithm, hash_mode);
+                        if !errors.is_empty() {
+                            hash_errors.lock().unwrap().extend(errors);
+                        }
+                    }
+                })
+                .into_iter(),
+        );
+    }
+}
+
+impl Iterator for ScandirIter {
+    type Item = ScandirResult;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.root_entry.take() {
+                return Some(entry);
+            }
+            if let Some(mount_point) = self.mount_points.lock().unwrap().pop_front() {
+                return Some(ScandirResult::Error(mount_point_error(mount_point)));
+            }
+            if let Some(symlink_loop) = self.symlink_loops.lock().unwrap().pop_front() {
+                return Some(ScandirResult::SymlinkLoop(symlink_loop));
+            }
+            if let Some(error) = self.hash_errors.lock().unwrap().pop_front() {
+                return Some(ScandirResult::Error(error));
+            }
+            let Some(ref mut iter) = self.iter else {
+                let root_path = self.roots.next()?;
+                self.start_root(&root_path);
+                continue;
+            };
+            for result in iter.by_ref() {
+                match result {
+                    Ok(dir_entry) => {
+                        if dir_entry.depth == 0 {
+                            continue;
+                        }
+                        if !dir_entry.file_type.is_dir() {
+                            self.file_cnt += 1;
+                            let max_file_cnt = self.options.max_file_cnt;
+                            if max_file_cnt > 0 && self.file_cnt > max_file_cnt {
+                                self.iter = None;
+                                self.roots = Vec::new().into_iter();
+                                return None;
+                            }
+                        }
+                        if !output_entry(&dir_entry, &self.filter, self.root_path_len) {
+                            continue;
+                        }
+                        return Some(create_entry(
+                            self.path_offset,
+                            &self.options.return_type,
+                            &dir_entry,
+                        ));
+                    }
+                    Err(e) => {
+                        return Some(ScandirResult::Error(ScanError::from(&e)));
+                    }
+                }
+            }
+            self.iter = None;
         }
-        if let Ok(dir_entry) = result
-            && !dir_entry.file_type.is_dir()
+    }
+}
+
+/// Keeps the largest files and directories of the received entries. The entries of
+/// different directories arrive interleaved, so the totals of each directory are kept
+/// and summed up when the report is created.
+#[derive(Debug)]
+struct LargestTracker {
+    top: TopNTracker,
+    dirs: HashMap<PathBuf, UsageTotals>,
+    file_indexes: HashSet<(u64, u64)>,
+}
+
+impl LargestTracker {
+    fn new(options: &Options) -> Self {
+        // The root directories aren't returned as entries and, like in `Count`, the root
+        // directories themselves aren't counted
+        let dirs = options
+            .roots()
+            .into_iter()
+            .filter(|root_path| root_path.is_dir())
+            .map(|root_path| match options.is_multi_root() {
+                true => (root_path, UsageTotals::default()),
+                false => (PathBuf::new(), UsageTotals::default()),
+            })
+            .collect();
+        LargestTracker {
+            top: TopNTracker::new(options.top_n),
+            dirs,
+            file_indexes: HashSet::new(),
+        }
+    }
+
+    fn add(&mut self, entry: &ScandirResult) {
+        let is_dir = entry.is_dir();
+        if !is_dir && !entry.is_file() {
+            return;
+        }
+        if let Some(ext) = entry.ext()
+            && ext.st_nlink > 1
+            && !is_dir
+            && !self.file_indexes.insert((ext.st_dev, ext.st_ino))
         {
-            file_cnt += 1;
-            if max_file_cnt > 0 && file_cnt > max_file_cnt {
-                break;
+            return;
+        }
+        let size = entry.size();
+        // The usage is calculated like in `Statistics`
+        let usage = match is_dir {
+            true => 4096,
+            false => size.div_ceil(4096) * 4096,
+        };
+        // Directories are added to their parent when the report is created
+        let path = PathBuf::from(entry.os_path());
+        let dir_path = match is_dir {
+            true => path.as_path(),
+            false => path.parent().unwrap_or(Path::new("")),
+        };
+        let totals = self.dirs.entry(dir_path.to_path_buf()).or_default();
+        totals.size += size;
+        totals.usage += usage;
+        if !is_dir {
+            self.top
+                .add_file(size, usage, || os_str_to_string(path.as_os_str()));
+        }
+    }
+
+    fn report(&self) -> TopN {
+        let mut recursive: HashMap<&Path, UsageTotals> = self
+            .dirs
+            .iter()
+            .map(|(path, totals)| (path.as_path(), *totals))
+            .collect();
+        let mut paths: Vec<&Path> = recursive.keys().copied().collect();
+        paths.sort_by_key(|path| std::cmp::Reverse(path.components().count()));
+        for path in paths.iter() {
+            let totals = recursive[path];
+            if let Some(parent) = path.parent()
+                && let Some(parent_totals) = recursive.get_mut(parent)
+            {
+                parent_totals.add(&totals);
             }
         }
+        let mut top = self.top.clone();
+        for (path, totals) in recursive {
+            top.add_dir(totals.size, totals.usage, || {
+                os_str_to_string(path.as_os_str())
+            });
+        }
+        top.report()
     }
 }
 
@@ -218,7 +563,10 @@ pub struct Scandir {
     entries: ScandirResults,
     duration: Arc<Mutex<f64>>,
     finished: Arc<AtomicBool>,
+    peak_queue_len: Arc<AtomicUsize>,
     // Internal
+    pending: ScandirResults,
+    largest: Option<LargestTracker>,
     thr: Option<thread::JoinHandle<()>>,
     stop: Arc<AtomicBool>,
     rx: Option<Receiver<ScandirResult>>,
@@ -226,10 +574,35 @@ pub struct Scandir {
 
 impl Scandir {
     pub fn new<P: AsRef<Path>>(root_path: P, store: Option<bool>) -> Result<Self, Error> {
-        Ok(Scandir {
+        Ok(Self::from_roots(
+            check_and_expand_path(root_path)?,
+            None,
+            store,
+        ))
+    }
+
+    /// Creates an instance which scans multiple file trees in one run. Overlapping root
+    /// paths are removed. The paths of the entries include the root path they belong to.
+    pub fn new_multi<P: AsRef<Path>>(root_paths: &[P], store: Option<bool>) -> Result<Self, Error> {
+        let root_paths = check_and_expand_paths(root_paths)?;
+        Ok(Self::from_roots(
+            root_paths[0].clone(),
+            Some(root_paths),
+            store,
+        ))
+    }
+
+    fn from_roots(
+        root_path: PathBuf,
+        root_paths: Option<Vec<PathBuf>>,
+        store: Option<bool>,
+    ) -> Self {
+        Scandir {
             options: Options {
-                root_path: check_and_expand_path(root_path)?,
+                root_path,
+                root_paths,
                 sorted: false,
+                sort_by: None,
                 skip_hidden: false,
                 max_depth: usize::MAX,
                 max_file_cnt: usize::MAX,
@@ -237,18 +610,55 @@ impl Scandir {
                 dir_exclude: None,
                 file_include: None,
                 file_exclude: None,
+                dir_include_regex: None,
+                dir_exclude_regex: None,
+                file_include_regex: None,
+                file_exclude_regex: None,
+                min_size: None,
+                max_size: None,
+                mtime: None,
+                ctime: None,
+                atime: None,
+                entry_types: None,
+                perm: None,
+                uid: None,
+                gid: None,
+                nouser: false,
+                nogroup: false,
+                min_links: None,
+                max_links: None,
+                filter_expr: None,
+                entry_filter: None,
                 case_sensitive: false,
+                file_match_path: false,
                 follow_links: false,
+                one_file_system: false,
+                gitignore: false,
                 return_type: ReturnType::Base,
+                channel_capacity: 0,
+                du: false,
+                top_n: 0,
+                hash_algorithm: None,
+                hash_mode: HashMode::Full,
+                extension_stats: false,
+                extension_case_sensitive: false,
+                multi_part_extensions: default_multi_part_extensions(),
+                owner_stats: false,
+                resolve_names: false,
+                histograms: false,
+                age_buckets: default_age_buckets(),
             },
             store: store.unwrap_or(true),
             entries: ScandirResults::new(),
             duration: Arc::new(Mutex::new(0.0)),
             finished: Arc::new(AtomicBool::new(false)),
+            peak_queue_len: Arc::new(AtomicUsize::new(0)),
+            pending: ScandirResults::new(),
+            largest: None,
             thr: None,
             stop: Arc::new(AtomicBool::new(false)),
             rx: None,
-        })
+        }
     }
 
     /// Return results in sorted order.
@@ -257,6 +667,13 @@ impl Scandir {
         self
     }
 
+    /// Sort the entries of each directory by `sort_by`, e.g. by size or in natural order.
+    /// Overrides `sorted`. The metadata is read if the sort key needs it.
+    pub fn sort_by(mut self, sort_by: Option<SortBy>) -> Self {
+        self.options.sort_by = sort_by;
+        self
+    }
+
     /// Skip hidden entries. Enabled by default.
     pub fn skip_hidden(mut self, skip_hidden: bool) -> Self {
         self.options.skip_hidden = skip_hidden;
@@ -313,18 +730,157 @@ impl Scandir {
         self
     }
 
+    /// Set directory include filter with regular expressions
+    pub fn dir_include_regex(mut self, dir_include_regex: Option<Vec<String>>) -> Self {
+        self.options.dir_include_regex = dir_include_regex;
+        self
+    }
+
+    /// Set directory exclude filter with regular expressions
+    pub fn dir_exclude_regex(mut self, dir_exclude_regex: Option<Vec<String>>) -> Self {
+        self.options.dir_exclude_regex = dir_exclude_regex;
+        self
+    }
+
+    /// Set file include filter with regular expressions
+    pub fn file_include_regex(mut self, file_include_regex: Option<Vec<String>>) -> Self {
+        self.options.file_include_regex = file_include_regex;
+        self
+    }
+
+    /// Set file exclude filter with regular expressions
+    pub fn file_exclude_regex(mut self, file_exclude_regex: Option<Vec<String>>) -> Self {
+        self.options.file_exclude_regex = file_exclude_regex;
+        self
+    }
+
+    /// Set minimum size of files in bytes
+    pub fn min_size(mut self, min_size: Option<u64>) -> Self {
+        self.options.min_size = min_size;
+        self
+    }
+
+    /// Set maximum size of files in bytes
+    pub fn max_size(mut self, max_size: Option<u64>) -> Self {
+        self.options.max_size = max_size;
+        self
+    }
+
+    /// Set types of the entries to return. If `None` (default) all entries are returned.
+    /// Directories are descended into also if they are not returned.
+    pub fn entry_types(mut self, entry_types: Option<Vec<EntryType>>) -> Self {
+        self.options.entry_types = entry_types;
+        self
+    }
+
+    /// Set test of the permission bits like `find -perm`. Only supported on Unix.
+    pub fn perm(mut self, perm: Option<PermMatch>) -> Self {
+        self.options.perm = perm;
+        self
+    }
+
+    /// Set user ID of the owner. Only supported on Unix.
+    pub fn uid(mut self, uid: Option<u32>) -> Self {
+        self.options.uid = uid;
+        self
+    }
+
+    /// Set group ID of the owner. Only supported on Unix.
+    pub fn gid(mut self, gid: Option<u32>) -> Self {
+        self.options.gid = gid;
+        self
+    }
+
+    /// Only entries whose user ID has no user name are returned. Only supported on Unix.
+    pub fn nouser(mut self, nouser: bool) -> Self {
+        self.options.nouser = nouser;
+        self
+    }
+
+    /// Only entries whose group ID has no group name are returned. Only supported on Unix.
+    pub fn nogroup(mut self, nogroup: bool) -> Self {
+        self.options.nogroup = nogroup;
+        self
+    }
+
+    /// Set minimum number of hard links
+    pub fn min_links(mut self, min_links: Option<u64>) -> Self {
+        self.options.min_links = min_links;
+        self
+    }
+
+    /// Set maximum number of hard links
+    pub fn max_links(mut self, max_links: Option<u64>) -> Self {
+        self.options.max_links = max_links;
+        self
+    }
+
+    /// Set find-style filter expression, e.g. `name "*.log" and size > 10M`. Directories are
+    /// descended into also if they are not returned.
+    pub fn filter_expr(mut self, filter_expr: Option<String>) -> Self {
+        self.options.filter_expr = filter_expr;
+        self
+    }
+
+    /// Set custom filter, which decides for each entry if it is returned, skipped or pruned
+    /// together with its subtree. It is applied after all other filters.
+    pub fn entry_filter(mut self, entry_filter: Option<Arc<dyn EntryFilter>>) -> Self {
+        self.options.entry_filter = entry_filter;
+        self
+    }
+
+    /// Set range of the modification time of files
+    pub fn mtime(mut self, mtime: Option<TimeRange>) -> Self {
+        self.options.mtime = mtime;
+        self
+    }
+
+    /// Set range of the creation time of files
+    pub fn ctime(mut self, ctime: Option<TimeRange>) -> Self {
+        self.options.ctime = ctime;
+        self
+    }
+
+    /// Set range of the access time of files
+    pub fn atime(mut self, atime: Option<TimeRange>) -> Self {
+        self.options.atime = atime;
+        self
+    }
+
     /// Set case sensitive filename filtering
     pub fn case_sensitive(mut self, case_sensitive: bool) -> Self {
         self.options.case_sensitive = case_sensitive;
         self
     }
 
+    /// Match the file filters against the path relative to the root path instead of the file
+    /// name, e.g. `src/**/*.rs`
+    pub fn file_match_path(mut self, file_match_path: bool) -> Self {
+        self.options.file_match_path = file_match_path;
+        self
+    }
+
     /// Set follow symlinks
     pub fn follow_links(mut self, follow_links: bool) -> Self {
         self.options.follow_links = follow_links;
         self
     }
 
+    /// Don't descend into directories on other file systems than the root path.
+    /// Crossed mount points are reported as errors. Only supported on Unix.
+    pub fn one_file_system(mut self, one_file_system: bool) -> Self {
+        self.options.one_file_system = one_file_system;
+        self
+    }
+
+    /// Skip entries which are ignored by `.gitignore`, `.ignore` and `.git/info/exclude` files,
+    /// like git does. The ignore files of the parent directories up to the root of the git
+    /// repository are respected too.
+    pub fn gitignore(mut self, gitignore: bool) -> Self {
+        self.options.gitignore = gitignore;
+        self
+    }
+
     /// Set extended file type parsing
     pub fn return_type(mut self, return_type: ReturnType) -> Self {
         self.options.return_type = return_type;
@@ -340,6 +896,17 @@ impl Scandir {
         self
     }
 
+    /// Set capacity of the results channel. If `0` (default) the channel is unbounded.
+    ///
+    /// With a bounded channel the scanner blocks while the channel is full, so memory usage
+    /// stays limited when the results are fetched with `results` slower than they are produced.
+    /// `join`, `stop` and `collect` receive all results while waiting for the scanner and
+    /// keep them in memory like with an unbounded channel.
+    pub fn channel_capacity(mut self, channel_capacity: usize) -> Self {
+        self.options.channel_capacity = channel_capacity;
+        self
+    }
+
     /// Same as method `extended`, but without moving the instance
     pub fn set_extended(&mut self, extended: bool) {
         self.options.return_type = match extended {
@@ -348,8 +915,39 @@ impl Scandir {
         };
     }
 
+    /// Hash the content of all files with `algorithm`. If `None` (default) hashing is disabled.
+    ///
+    /// The files are hashed on the rayon pool of the walker and the hex encoded digest is
+    /// stored in the entries. Files which can't be read are reported as errors.
+    pub fn hash(mut self, algorithm: Option<HashAlgorithm>) -> Self {
+        self.options.hash_algorithm = algorithm;
+        self
+    }
+
+    /// Set which part of the file content is hashed. Default is `HashMode::Full`.
+    pub fn hash_mode(mut self, hash_mode: HashMode) -> Self {
+        self.options.hash_mode = hash_mode;
+        self
+    }
+
+    /// Keep the `top_n` largest files and directories by apparent size and by usage on disk.
+    /// Directories are ranked by the totals of all of their descendants. If `0` (default)
+    /// the report is disabled.
+    ///
+    /// Works also if results are not stored. Usage on disk and hardlinks are only taken
+    /// into account with return type `Ext`.
+    pub fn top_n(mut self, top_n: usize) -> Self {
+        self.options.top_n = top_n;
+        self
+    }
+
     pub fn clear(&mut self) {
         self.entries.clear();
+        self.pending.clear();
+        self.largest = match self.options.top_n {
+            0 => None,
+            _ => Some(LargestTracker::new(&self.options)),
+        };
         *self.duration.lock().unwrap() = 0.0;
     }
 
@@ -366,7 +964,11 @@ impl Scandir {
         self.clear();
         let options = self.options.clone();
         let filter = create_filter(&options)?;
-        let (tx, rx) = unbounded();
+        let (tx, rx) = create_channel(
+            options.channel_capacity,
+            self.stop.clone(),
+            self.peak_queue_len.clone(),
+        );
         self.rx = Some(rx);
         self.stop.store(false, Ordering::Relaxed);
         let stop = self.stop.clone();
@@ -381,8 +983,53 @@ impl Scandir {
         Ok(())
     }
 
+    /// Walk the file tree on the calling thread instead of a background thread.
+    ///
+    /// The returned iterator yields the entries lazily. Results are not stored.
+    pub fn iter(&self) -> Result<ScandirIter, Error> {
+        if self.options.return_type > ReturnType::Ext {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Parameter return_type has invalid value",
+            ));
+        }
+        let filter = create_filter(&self.options)?;
+        Ok(ScandirIter::new(&self.options, filter))
+    }
+
+    /// Receive results while the thread is running. Otherwise a thread which is blocked
+    /// by a full bounded channel would never finish.
+    fn receive_while_running(&mut self, thr: &thread::JoinHandle<()>) {
+        if self.options.channel_capacity == 0 {
+            return;
+        }
+        if let Some(ref rx) = self.rx {
+            while !thr.is_finished() {
+                if let Ok(entry) = rx.recv_timeout(Duration::from_millis(10)) {
+                    if let Some(ref mut largest) = self.largest {
+                        largest.add(&entry);
+                    }
+                    self.pending.push(entry);
+                }
+            }
+        }
+    }
+
+    /// Move all received results to the pending results.
+    fn receive_pending(&mut self) {
+        if let Some(ref rx) = self.rx {
+            while let Ok(entry) = rx.try_recv() {
+                if let Some(ref mut largest) = self.largest {
+                    largest.add(&entry);
+                }
+                self.pending.push(entry);
+            }
+        }
+    }
+
     pub fn join(&mut self) -> bool {
         if let Some(thr) = self.thr.take() {
+            self.receive_while_running(&thr);
             if let Err(_e) = thr.join() {
                 return false;
             }
@@ -394,6 +1041,7 @@ impl Scandir {
     pub fn stop(&mut self) -> bool {
         if let Some(thr) = self.thr.take() {
             self.stop.store(true, Ordering::Relaxed);
+            self.receive_while_running(&thr);
             if let Err(_e) = thr.join() {
                 return false;
             }
@@ -413,6 +1061,9 @@ impl Scandir {
     }
 
     pub fn has_results(&mut self, only_new: bool) -> bool {
+        if !self.pending.is_empty() {
+            return true;
+        }
         if let Some(ref rx) = self.rx
             && !rx.is_empty()
         {
@@ -425,30 +1076,23 @@ impl Scandir {
     }
 
     pub fn results_cnt(&mut self, only_new: bool) -> usize {
+        let pending_cnt = self.pending.len();
         if let Some(ref rx) = self.rx {
             if only_new {
-                rx.len()
+                pending_cnt + rx.len()
             } else {
-                self.entries.len() + rx.len()
+                self.entries.len() + pending_cnt + rx.len()
             }
         } else if only_new {
-            0
+            pending_cnt
         } else {
-            self.entries.len()
+            self.entries.len() + pending_cnt
         }
     }
 
     pub fn results(&mut self, only_new: bool) -> ScandirResults {
-        let mut results = ScandirResults::new();
-        if let Some(ref rx) = self.rx {
-            while let Ok(entry) = rx.try_recv() {
-                if let ScandirResult::Error(e) = entry {
-                    results.errors.push(e);
-                } else {
-                    results.results.push(entry);
-                }
-            }
-        }
+        self.receive_pending();
+        let results = std::mem::take(&mut self.pending);
         if self.store {
             self.entries.extend(&results);
         }
@@ -459,6 +1103,9 @@ impl Scandir {
     }
 
     pub fn has_entries(&mut self, only_new: bool) -> bool {
+        if !self.pending.is_empty() {
+            return true;
+        }
         if let Some(ref rx) = self.rx
             && !rx.is_empty()
         {
@@ -471,13 +1118,14 @@ impl Scandir {
     }
 
     pub fn entries_cnt(&mut self, only_new: bool) -> usize {
+        let pending_cnt = self.pending.len();
         if let Some(ref rx) = self.rx {
             if only_new {
-                return rx.len();
+                return pending_cnt + rx.len();
             }
-            self.entries.len() + rx.len()
+            self.entries.len() + pending_cnt + rx.len()
         } else {
-            self.entries.len()
+            self.entries.len() + pending_cnt
         }
     }
 
@@ -497,6 +1145,11 @@ impl Scandir {
         self.results(only_new).errors
     }
 
+    /// Returns the detected symlink loops. Loops are only detected if `follow_links` is enabled.
+    pub fn symlink_loops(&mut self, only_new: bool) -> Vec<SymlinkLoop> {
+        self.results(only_new).symlink_loops
+    }
+
     #[cfg(feature = "speedy")]
     pub fn to_speedy(&self) -> Result<Vec<u8>, speedy::Error> {
         self.entries.to_speedy()
@@ -556,6 +1209,46 @@ impl Scandir {
         statistics
     }
 
+    /// Finds files with identical content in the stored results. See `find_duplicates`.
+    ///
+    /// Digests which were created while scanning with the same algorithm are reused.
+    /// Returns an error if results are not stored.
+    pub fn duplicates(
+        &mut self,
+        algorithm: HashAlgorithm,
+        partial_size: u64,
+    ) -> Result<Duplicates, Error> {
+        if !self.store {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Duplicates can only be found if results are stored",
+            ));
+        }
+        self.results(true);
+        let reuse_digests = self.options.hash_algorithm == Some(algorithm)
+            && self.options.hash_mode == HashMode::Full;
+        Ok(find_duplicates(
+            &self.options.root_path,
+            &self.entries.results,
+            algorithm,
+            partial_size,
+            reuse_digests,
+        ))
+    }
+
+    /// Returns the largest files and directories received so far. Only available if `top_n`
+    /// is enabled. Directory totals are complete after the task has finished.
+    pub fn largest(&mut self) -> Option<TopN> {
+        self.receive_pending();
+        self.largest.as_ref().map(|largest| largest.report())
+    }
+
+    /// Returns the highest number of results which were queued in the channel
+    /// during the last scan.
+    pub fn peak_queue_len(&self) -> usize {
+        self.peak_queue_len.load(Ordering::Relaxed)
+    }
+
     pub fn duration(&mut self) -> f64 {
         *self.duration.lock().unwrap()
     }
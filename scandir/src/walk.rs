@@ -1,112 +1,353 @@
+use std::collections::VecDeque;
 use std::fmt::Debug;
-use std::fs::{self, Metadata};
 use std::io::Error;
-use std::path::Path;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 #[cfg(feature = "bincode")]
 use bincode::error::EncodeError;
-use flume::{Receiver, Sender, unbounded};
-use jwalk_meta::WalkDirGeneric;
+use flume::Receiver;
+use jwalk_meta::{ClientState, DirEntryIter, WalkDirGeneric};
 #[cfg(feature = "speedy")]
 use speedy::Writable;
 
-use crate::common::{check_and_expand_path, create_filter, filter_children, get_root_path_len};
+use crate::common::{
+    EntryState, ReadDirState, ResultSender, check_and_expand_path, check_and_expand_paths,
+    create_channel, create_filter, detect_symlink_loops, filter_children, get_root_device,
+    get_root_path_len, mount_point_error, output_entry, prune_mount_points, relative_path,
+    root_error, sort_children,
+};
+use crate::def::raw_name::{os_str_to_string, push_name};
 use crate::def::*;
+use crate::gitignore::filter_ignored;
 
+/// Adds an entry to a TOC. If `store_sort_metadata` is set, the metadata which was read for
+/// sorting is stored too.
 #[inline]
-fn update_toc(
-    dir_entry: &jwalk_meta::DirEntry<((), Option<Result<fs::Metadata, Error>>)>,
+fn update_toc<C: ClientState>(
+    dir_entry: &jwalk_meta::DirEntry<C>,
     toc: &mut Toc,
+    store_sort_metadata: bool,
 ) {
     let file_type = dir_entry.file_type;
-    let key = dir_entry.file_name.clone().into_string().unwrap();
+    let key = &dir_entry.file_name;
+    if store_sort_metadata && let Some(ref metadata) = dir_entry.metadata {
+        toc.sort_metadata.insert(
+            key.clone(),
+            SortMetadata {
+                size: metadata.size,
+                mtime: metadata.modified,
+                ctime: metadata.created,
+                atime: metadata.accessed,
+            },
+        );
+    }
     if file_type.is_symlink() {
-        toc.symlinks.push(key);
+        push_name(&mut toc.symlinks, &mut toc.raw_symlinks, key);
     } else if file_type.is_dir() {
-        toc.dirs.push(key);
+        push_name(&mut toc.dirs, &mut toc.raw_dirs, key);
     } else if file_type.is_file() {
-        toc.files.push(key);
+        push_name(&mut toc.files, &mut toc.raw_files, key);
+    } else {
+        push_name(&mut toc.other, &mut toc.raw_other, key);
+    }
+}
+
+/// Returns the key of a directory. In multi-root mode the key is the full path of the
+/// directory, otherwise the path relative to the root path. If the key isn't valid UTF-8
+/// the raw bytes are returned additionally.
+#[inline]
+fn toc_key(dir_path: &Path, root_path_len: usize, multi_root: bool) -> (String, Option<Vec<u8>>) {
+    if multi_root {
+        os_str_to_string(dir_path.as_os_str())
     } else {
-        toc.other.push(key);
+        os_str_to_string(relative_path(dir_path, root_path_len).as_os_str())
+    }
+}
+
+#[inline]
+fn add_mount_point_errors<C: ClientState>(
+    children: &mut [Result<jwalk_meta::DirEntry<C>, jwalk_meta::Error>],
+    root_device: Option<u64>,
+    toc: &mut Toc,
+) {
+    for mount_point in prune_mount_points(children, root_device) {
+        toc.errors.push(mount_point_error(mount_point));
     }
 }
 
+/// Creates a TOC for an error of the walker. The key is the directory which couldn't be read
+/// or the parent directory of the entry whose metadata couldn't be read.
+fn error_toc(e: &jwalk_meta::Error, root_path_len: usize, multi_root: bool) -> (String, Toc) {
+    let error = ScanError::from(e);
+    let path = Path::new(&error.path);
+    let dir = match error.phase {
+        ErrorPhase::ReadDir => Some(path),
+        _ => path.parent(),
+    };
+    let (key, raw_dir) = dir
+        .filter(|dir| dir.as_os_str().len() + 1 >= root_path_len)
+        .map(|dir| toc_key(dir, root_path_len, multi_root))
+        .unwrap_or_default();
+    let mut toc = Toc::new();
+    toc.raw_dir = raw_dir;
+    toc.errors.push(error);
+    (key, toc)
+}
+
+/// Creates the TOC of a root path which is not a directory or which can't be read.
+fn root_toc(root_path: &Path, follow_links: bool, multi_root: bool) -> Option<(String, Toc)> {
+    let (key, raw_dir) = match multi_root {
+        true => root_path
+            .parent()
+            .map(|p| os_str_to_string(p.as_os_str()))
+            .unwrap_or_default(),
+        false => ("".to_owned(), None),
+    };
+    let mut toc = Toc::new();
+    toc.raw_dir = raw_dir;
+    match jwalk_meta::DirEntry::<((), ())>::from_path(
+        0,
+        root_path,
+        true,
+        true,
+        follow_links,
+        Arc::new(Vec::new()),
+    ) {
+        Ok(dir_entry) => {
+            if dir_entry.file_type.is_dir() {
+                return None;
+            }
+            update_toc(&dir_entry, &mut toc, false);
+        }
+        Err(e) => {
+            toc.errors.push(root_error(root_path, &e));
+        }
+    }
+    Some((key, toc))
+}
+
 pub fn toc_thread(
     options: Options,
     filter: Option<Filter>,
-    tx: Sender<(String, Toc)>,
+    tx: ResultSender<(String, Toc)>,
     stop: Arc<AtomicBool>,
 ) {
-    let root_path_len = get_root_path_len(&options.root_path);
-
-    let dir_entry: jwalk_meta::DirEntry<((), Option<Result<Metadata, Error>>)> =
-        jwalk_meta::DirEntry::from_path(
-            0,
-            &options.root_path,
-            true,
-            true,
-            options.follow_links,
-            Arc::new(Vec::new()),
-        )
-        .unwrap();
-
-    if !dir_entry.file_type.is_dir() {
-        let mut toc = Toc::new();
-
-        update_toc(&dir_entry, &mut toc);
-        let _ = tx.send(("".to_owned(), toc));
-        return;
-    }
-
+    let multi_root = options.is_multi_root();
     let max_file_cnt = options.max_file_cnt;
     let mut file_cnt = 0;
-    for result in WalkDirGeneric::new(&options.root_path)
-        .skip_hidden(options.skip_hidden)
-        .follow_links(options.follow_links)
-        .sort(options.sorted)
-        .max_depth(options.max_depth)
-        .process_read_dir(move |_, root_dir, _, children| {
-            let root_dir = root_dir.to_str();
-            if root_dir.is_none() {
-                return;
-            }
-            let root_dir = root_dir.unwrap();
-            if root_dir.len() + 1 < root_path_len {
-                return;
-            }
-            filter_children(children, &filter, root_path_len);
-            if children.is_empty() {
+
+    for root_path in options.roots() {
+        if stop.load(Ordering::Relaxed) {
+            break;
+        }
+        if let Some(entry) = root_toc(&root_path, options.follow_links, multi_root) {
+            let _ = tx.send(entry);
+            continue;
+        }
+
+        let root_path_len = get_root_path_len(&root_path);
+        let root_device = get_root_device(&root_path, options.one_file_system);
+        let follow_links = options.follow_links;
+        let gitignore = options.gitignore;
+        let sort_by = options.sort_by;
+        let store_sort_metadata = options.sort_needs_metadata();
+        let filter_needs_metadata = filter.as_ref().is_some_and(Filter::needs_metadata);
+        let filter_needs_metadata_ext = filter.as_ref().is_some_and(Filter::needs_metadata_ext);
+        let filter = filter.clone();
+        let dir_tx = tx.clone();
+        for result in WalkDirGeneric::<(ReadDirState, EntryState<()>)>::new(&root_path)
+            .skip_hidden(options.skip_hidden)
+            .follow_links(options.follow_links)
+            .sort(options.sorted && options.sort_by.is_none())
+            .max_depth(options.max_depth)
+            .read_metadata(filter_needs_metadata || options.sort_needs_metadata())
+            .read_metadata_ext(filter_needs_metadata_ext)
+            .process_read_dir(move |_, dir_path, state, children| {
+                if dir_path.as_os_str().len() + 1 < root_path_len {
+                    return;
+                }
+                if gitignore {
+                    filter_ignored(children, dir_path, &mut state.ignores);
+                }
+                filter_children(children, &filter, root_path_len);
+                sort_children(children, sort_by);
+                if children.is_empty() {
+                    return;
+                }
+                let mut toc = Toc::new();
+                add_mount_point_errors(children, root_device, &mut toc);
+                if follow_links {
+                    toc.symlink_loops =
+                        detect_symlink_loops(dir_path, &mut state.dir_ids, children);
+                }
+                children.iter_mut().for_each(|dir_entry_result| {
+                    if let Ok(dir_entry) = dir_entry_result
+                        && output_entry(dir_entry, &filter, root_path_len)
+                    {
+                        update_toc(dir_entry, &mut toc, store_sort_metadata);
+                    }
+                });
+                if !toc.is_empty() {
+                    let (key, raw_dir) = toc_key(dir_path, root_path_len, multi_root);
+                    toc.raw_dir = raw_dir;
+                    let _ = dir_tx.send((key, toc));
+                }
+            })
+        {
+            if stop.load(Ordering::Relaxed) {
                 return;
             }
-            let mut toc = Toc::new();
-            children.iter_mut().for_each(|dir_entry_result| {
-                if let Ok(dir_entry) = dir_entry_result {
-                    update_toc(dir_entry, &mut toc);
+            match result {
+                Ok(dir_entry) => {
+                    if !dir_entry.file_type.is_dir() {
+                        file_cnt += 1;
+                        if max_file_cnt > 0 && file_cnt > max_file_cnt {
+                            return;
+                        }
+                    }
                 }
-            });
-            if !toc.is_empty() {
-                if root_dir.len() > root_path_len {
-                    let _ = tx.send((root_dir[root_path_len..].to_owned(), toc));
-                } else {
-                    let _ = tx.send(("".to_owned(), toc));
+                Err(e) => {
+                    let _ = tx.send(error_toc(&e, root_path_len, multi_root));
                 }
             }
-        })
-    {
-        if stop.load(Ordering::Relaxed) {
-            break;
         }
-        if let Ok(dir_entry) = result
-            && !dir_entry.file_type.is_dir()
-        {
-            file_cnt += 1;
-            if max_file_cnt > 0 && file_cnt > max_file_cnt {
-                break;
+    }
+}
+
+/// Iterator returned by `Walk::iter`.
+///
+/// The file tree is walked on the calling thread. Each item contains the relative path
+/// of a directory and its table of contents.
+pub struct WalkIter {
+    options: Options,
+    filter: Option<Filter>,
+    roots: std::vec::IntoIter<PathBuf>,
+    iter: Option<DirEntryIter<(ReadDirState, EntryState<()>)>>,
+    root_toc: Option<(String, Toc)>,
+    /// TOCs of the directories which were read, in the order of the walk.
+    pending: Arc<Mutex<VecDeque<(String, Toc)>>>,
+    root_path_len: usize,
+    file_cnt: usize,
+}
+
+impl WalkIter {
+    fn new(options: &Options, filter: Option<Filter>) -> Self {
+        WalkIter {
+            options: options.clone(),
+            filter,
+            roots: options.roots().into_iter(),
+            iter: None,
+            root_toc: None,
+            pending: Arc::new(Mutex::new(VecDeque::new())),
+            root_path_len: 0,
+            file_cnt: 0,
+        }
+    }
+
+    fn start_root(&mut self, root_path: &Path) {
+        let multi_root = self.options.is_multi_root();
+        self.root_toc = root_toc(root_path, self.options.follow_links, multi_root);
+        if self.root_toc.is_some() {
+            return;
+        }
+
+        let root_path_len = get_root_path_len(root_path);
+        self.root_path_len = root_path_len;
+        let root_device = get_root_device(root_path, self.options.one_file_system);
+        let follow_links = self.options.follow_links;
+        let gitignore = self.options.gitignore;
+        let sort_by = self.options.sort_by;
+        let store_sort_metadata = self.options.sort_needs_metadata();
+        let filter_needs_metadata = self.filter.as_ref().is_some_and(Filter::needs_metadata);
+        let filter_needs_metadata_ext =
+            self.filter.as_ref().is_some_and(Filter::needs_metadata_ext);
+        let filter = self.filter.clone();
+        let pending = self.pending.clone();
+        self.iter = Some(
+            WalkDirGeneric::<(ReadDirState, EntryState<()>)>::new(root_path)
+                .skip_hidden(self.options.skip_hidden)
+                .follow_links(self.options.follow_links)
+                .sort(self.options.sorted && self.options.sort_by.is_none())
+                .max_depth(self.options.max_depth)
+                .read_metadata(filter_needs_metadata || self.options.sort_needs_metadata())
+                .read_metadata_ext(filter_needs_metadata_ext)
+                .process_read_dir(move |_, dir_path, state, children| {
+                    if dir_path.as_os_str().len() + 1 < root_path_len {
+                        return;
+                    }
+                    if gitignore {
+                        filter_ignored(children, dir_path, &mut state.ignores);
+                    }
+                    filter_children(children, &filter, root_path_len);
+                    sort_children(children, sort_by);
+                    let mut toc = Toc::new();
+                    add_mount_point_errors(children, root_device, &mut toc);
+                    if follow_links {
+                        toc.symlink_loops =
+                            detect_symlink_loops(dir_path, &mut state.dir_ids, children);
+                    }
+                    children.iter().for_each(|dir_entry_result| {
+                        if let Ok(dir_entry) = dir_entry_result
+                            && output_entry(dir_entry, &filter, root_path_len)
+                        {
+                            update_toc(dir_entry, &mut toc, store_sort_metadata);
+                        }
+                    });
+                    if toc.is_empty() {
+                        return;
+                    }
+                    let (key, raw_dir) = toc_key(dir_path, root_path_len, multi_root);
+                    toc.raw_dir = raw_dir;
+                    pending.lock().unwrap().push_back((key, toc));
+                })
+                .into_iter(),
+        );
+    }
+}
+
+impl Iterator for WalkIter {
+    type Item = (String, Toc);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.root_toc.take() {
+                return Some(entry);
+            }
+            if let Some(entry) = self.pending.lock().unwrap().pop_front() {
+                return Some(entry);
+            }
+            let Some(ref mut iter) = self.iter else {
+                let root_path = self.roots.next()?;
+                self.start_root(&root_path);
+                continue;
+            };
+            for result in iter.by_ref() {
+                let dir_entry = match result {
+                    Ok(dir_entry) => dir_entry,
+                    Err(e) => {
+                        let multi_root = self.options.is_multi_root();
+                        return Some(error_toc(&e, self.root_path_len, multi_root));
+                    }
+                };
+                if !dir_entry.file_type.is_dir() {
+                    self.file_cnt += 1;
+                    let max_file_cnt = self.options.max_file_cnt;
+                    if max_file_cnt > 0 && self.file_cnt > max_file_cnt {
+                        self.iter = None;
+                        self.roots = Vec::new().into_iter();
+                        self.pending.lock().unwrap().clear();
+                        return None;
+                    }
+                }
+                if let Some(entry) = self.pending.lock().unwrap().pop_front() {
+                    return Some(entry);
+                }
             }
+            self.iter = None;
         }
     }
 }
@@ -121,7 +362,9 @@ pub struct Walk {
     duration: Arc<Mutex<f64>>,
     finished: Arc<AtomicBool>,
     has_errors: bool,
+    peak_queue_len: Arc<AtomicUsize>,
     // Internal
+    pending: Vec<(String, Toc)>,
     thr: Option<thread::JoinHandle<()>>,
     stop: Arc<AtomicBool>,
     rx: Option<Receiver<(String, Toc)>>,
@@ -129,10 +372,35 @@ pub struct Walk {
 
 impl Walk {
     pub fn new<P: AsRef<Path>>(root_path: P, store: Option<bool>) -> Result<Self, Error> {
-        Ok(Walk {
+        Ok(Self::from_roots(
+            check_and_expand_path(root_path)?,
+            None,
+            store,
+        ))
+    }
+
+    /// Creates an instance which walks multiple file trees in one run. Overlapping root
+    /// paths are removed. The keys of the results are the full paths of the directories.
+    pub fn new_multi<P: AsRef<Path>>(root_paths: &[P], store: Option<bool>) -> Result<Self, Error> {
+        let root_paths = check_and_expand_paths(root_paths)?;
+        Ok(Self::from_roots(
+            root_paths[0].clone(),
+            Some(root_paths),
+            store,
+        ))
+    }
+
+    fn from_roots(
+        root_path: PathBuf,
+        root_paths: Option<Vec<PathBuf>>,
+        store: Option<bool>,
+    ) -> Self {
+        Walk {
             options: Options {
-                root_path: check_and_expand_path(root_path)?,
+                root_path,
+                root_paths,
                 sorted: false,
+                sort_by: None,
                 skip_hidden: true,
                 max_depth: usize::MAX,
                 max_file_cnt: usize::MAX,
@@ -140,19 +408,55 @@ impl Walk {
                 dir_exclude: None,
                 file_include: None,
                 file_exclude: None,
+                dir_include_regex: None,
+                dir_exclude_regex: None,
+                file_include_regex: None,
+                file_exclude_regex: None,
+                min_size: None,
+                max_size: None,
+                mtime: None,
+                ctime: None,
+                atime: None,
+                entry_types: None,
+                perm: None,
+                uid: None,
+                gid: None,
+                nouser: false,
+                nogroup: false,
+                min_links: None,
+                max_links: None,
+                filter_expr: None,
+                entry_filter: None,
                 case_sensitive: false,
+                file_match_path: false,
                 follow_links: false,
+                one_file_system: false,
+                gitignore: false,
                 return_type: ReturnType::Base,
+                channel_capacity: 0,
+                du: false,
+                top_n: 0,
+                hash_algorithm: None,
+                hash_mode: HashMode::Full,
+                extension_stats: false,
+                extension_case_sensitive: false,
+                multi_part_extensions: default_multi_part_extensions(),
+                owner_stats: false,
+                resolve_names: false,
+                histograms: false,
+                age_buckets: default_age_buckets(),
             },
             store: store.unwrap_or(true),
             entries: Vec::new(),
             duration: Arc::new(Mutex::new(0.0)),
             finished: Arc::new(AtomicBool::new(false)),
             has_errors: false,
+            peak_queue_len: Arc::new(AtomicUsize::new(0)),
+            pending: Vec::new(),
             thr: None,
             stop: Arc::new(AtomicBool::new(false)),
             rx: None,
-        })
+        }
     }
 
     /// Return results in sorted order.
@@ -161,6 +465,13 @@ impl Walk {
         self
     }
 
+    /// Sort the entries of each directory by `sort_by`, e.g. by size or in natural order.
+    /// Overrides `sorted`. The metadata is read if the sort key needs it.
+    pub fn sort_by(mut self, sort_by: Option<SortBy>) -> Self {
+        self.options.sort_by = sort_by;
+        self
+    }
+
     /// Skip hidden entries. Enabled by default.
     pub fn skip_hidden(mut self, skip_hidden: bool) -> Self {
         self.options.skip_hidden = skip_hidden;
@@ -217,18 +528,157 @@ impl Walk {
         self
     }
 
+    /// Set directory include filter with regular expressions
+    pub fn dir_include_regex(mut self, dir_include_regex: Option<Vec<String>>) -> Self {
+        self.options.dir_include_regex = dir_include_regex;
+        self
+    }
+
+    /// Set directory exclude filter with regular expressions
+    pub fn dir_exclude_regex(mut self, dir_exclude_regex: Option<Vec<String>>) -> Self {
+        self.options.dir_exclude_regex = dir_exclude_regex;
+        self
+    }
+
+    /// Set file include filter with regular expressions
+    pub fn file_include_regex(mut self, file_include_regex: Option<Vec<String>>) -> Self {
+        self.options.file_include_regex = file_include_regex;
+        self
+    }
+
+    /// Set file exclude filter with regular expressions
+    pub fn file_exclude_regex(mut self, file_exclude_regex: Option<Vec<String>>) -> Self {
+        self.options.file_exclude_regex = file_exclude_regex;
+        self
+    }
+
+    /// Set minimum size of files in bytes
+    pub fn min_size(mut self, min_size: Option<u64>) -> Self {
+        self.options.min_size = min_size;
+        self
+    }
+
+    /// Set maximum size of files in bytes
+    pub fn max_size(mut self, max_size: Option<u64>) -> Self {
+        self.options.max_size = max_size;
+        self
+    }
+
+    /// Set types of the entries to return. If `None` (default) all entries are returned.
+    /// Directories are descended into also if they are not returned.
+    pub fn entry_types(mut self, entry_types: Option<Vec<EntryType>>) -> Self {
+        self.options.entry_types = entry_types;
+        self
+    }
+
+    /// Set test of the permission bits like `find -perm`. Only supported on Unix.
+    pub fn perm(mut self, perm: Option<PermMatch>) -> Self {
+        self.options.perm = perm;
+        self
+    }
+
+    /// Set user ID of the owner. Only supported on Unix.
+    pub fn uid(mut self, uid: Option<u32>) -> Self {
+        self.options.uid = uid;
+        self
+    }
+
+    /// Set group ID of the owner. Only supported on Unix.
+    pub fn gid(mut self, gid: Option<u32>) -> Self {
+        self.options.gid = gid;
+        self
+    }
+
+    /// Only entries whose user ID has no user name are returned. Only supported on Unix.
+    pub fn nouser(mut self, nouser: bool) -> Self {
+        self.options.nouser = nouser;
+        self
+    }
+
+    /// Only entries whose group ID has no group name are returned. Only supported on Unix.
+    pub fn nogroup(mut self, nogroup: bool) -> Self {
+        self.options.nogroup = nogroup;
+        self
+    }
+
+    /// Set minimum number of hard links
+    pub fn min_links(mut self, min_links: Option<u64>) -> Self {
+        self.options.min_links = min_links;
+        self
+    }
+
+    /// Set maximum number of hard links
+    pub fn max_links(mut self, max_links: Option<u64>) -> Self {
+        self.options.max_links = max_links;
+        self
+    }
+
+    /// Set find-style filter expression, e.g. `name "*.log" and size > 10M`. Directories are
+    /// descended into also if they are not returned.
+    pub fn filter_expr(mut self, filter_expr: Option<String>) -> Self {
+        self.options.filter_expr = filter_expr;
+        self
+    }
+
+    /// Set custom filter, which decides for each entry if it is returned, skipped or pruned
+    /// together with its subtree. It is applied after all other filters.
+    pub fn entry_filter(mut self, entry_filter: Option<Arc<dyn EntryFilter>>) -> Self {
+        self.options.entry_filter = entry_filter;
+        self
+    }
+
+    /// Set range of the modification time of files
+    pub fn mtime(mut self, mtime: Option<TimeRange>) -> Self {
+        self.options.mtime = mtime;
+        self
+    }
+
+    /// Set range of the creation time of files
+    pub fn ctime(mut self, ctime: Option<TimeRange>) -> Self {
+        self.options.ctime = ctime;
+        self
+    }
+
+    /// Set range of the access time of files
+    pub fn atime(mut self, atime: Option<TimeRange>) -> Self {
+        self.options.atime = atime;
+        self
+    }
+
     /// Set case sensitive filename filtering
     pub fn case_sensitive(mut self, case_sensitive: bool) -> Self {
         self.options.case_sensitive = case_sensitive;
         self
     }
 
+    /// Match the file filters against the path relative to the root path instead of the file
+    /// name, e.g. `src/**/*.rs`
+    pub fn file_match_path(mut self, file_match_path: bool) -> Self {
+        self.options.file_match_path = file_match_path;
+        self
+    }
+
     /// Set follow symlinks
     pub fn follow_links(mut self, follow_links: bool) -> Self {
         self.options.follow_links = follow_links;
         self
     }
 
+    /// Don't descend into directories on other file systems than the root path.
+    /// Crossed mount points are reported as errors. Only supported on Unix.
+    pub fn one_file_system(mut self, one_file_system: bool) -> Self {
+        self.options.one_file_system = one_file_system;
+        self
+    }
+
+    /// Skip entries which are ignored by `.gitignore`, `.ignore` and `.git/info/exclude` files,
+    /// like git does. The ignore files of the parent directories up to the root of the git
+    /// repository are respected too.
+    pub fn gitignore(mut self, gitignore: bool) -> Self {
+        self.options.gitignore = gitignore;
+        self
+    }
+
     /// Set extended return type
     pub fn return_type(mut self, return_type: ReturnType) -> Self {
         self.options.return_type = return_type;
@@ -244,6 +694,17 @@ impl Walk {
         self
     }
 
+    /// Set capacity of the results channel. If `0` (default) the channel is unbounded.
+    ///
+    /// With a bounded channel the scanner blocks while the channel is full, so memory usage
+    /// stays limited when the results are fetched with `results` slower than they are produced.
+    /// `join`, `stop` and `collect` receive all results while waiting for the scanner and
+    /// keep them in memory like with an unbounded channel.
+    pub fn channel_capacity(mut self, channel_capacity: usize) -> Self {
+        self.options.channel_capacity = channel_capacity;
+        self
+    }
+
     /// Same as method `extended`, but without moving the instance
     pub fn set_extended(&mut self, extended: bool) {
         self.options.return_type = match extended {
@@ -254,6 +715,7 @@ impl Walk {
 
     pub fn clear(&mut self) {
         self.entries.clear();
+        self.pending.clear();
         self.has_errors = false;
         *self.duration.lock().unwrap() = 0.0;
     }
@@ -265,7 +727,11 @@ impl Walk {
         self.clear();
         let options = self.options.clone();
         let filter = create_filter(&options)?;
-        let (tx, rx) = unbounded();
+        let (tx, rx) = create_channel(
+            options.channel_capacity,
+            self.stop.clone(),
+            self.peak_queue_len.clone(),
+        );
         self.rx = Some(rx);
         self.stop.store(false, Ordering::Relaxed);
         let stop = self.stop.clone();
@@ -280,8 +746,32 @@ impl Walk {
         Ok(())
     }
 
+    /// Walk the file tree on the calling thread instead of a background thread.
+    ///
+    /// The returned iterator yields the directories lazily. Results are not stored.
+    pub fn iter(&self) -> Result<WalkIter, Error> {
+        let filter = create_filter(&self.options)?;
+        Ok(WalkIter::new(&self.options, filter))
+    }
+
+    /// Receive results while the thread is running. Otherwise a thread which is blocked
+    /// by a full bounded channel would never finish.
+    fn receive_while_running(&mut self, thr: &thread::JoinHandle<()>) {
+        if self.options.channel_capacity == 0 {
+            return;
+        }
+        if let Some(ref rx) = self.rx {
+            while !thr.is_finished() {
+                if let Ok(entry) = rx.recv_timeout(Duration::from_millis(10)) {
+                    self.pending.push(entry);
+                }
+            }
+        }
+    }
+
     pub fn join(&mut self) -> bool {
         if let Some(thr) = self.thr.take() {
+            self.receive_while_running(&thr);
             if let Err(_e) = thr.join() {
                 return false;
             }
@@ -293,6 +783,7 @@ impl Walk {
     pub fn stop(&mut self) -> bool {
         if let Some(thr) = self.thr.take() {
             self.stop.store(true, Ordering::Relaxed);
+            self.receive_while_running(&thr);
             if let Err(_e) = thr.join() {
                 return false;
             }
@@ -302,7 +793,10 @@ impl Walk {
     }
 
     fn receive_all(&mut self) -> Vec<(String, Toc)> {
-        let mut entries = Vec::new();
+        let mut entries = std::mem::take(&mut self.pending);
+        if entries.iter().any(|entry| !entry.1.errors.is_empty()) {
+            self.has_errors = true;
+        }
         if let Some(ref rx) = self.rx {
             while let Ok(entry) = rx.try_recv() {
                 if !entry.1.errors.is_empty() {
@@ -329,6 +823,9 @@ impl Walk {
     }
 
     pub fn has_results(&mut self, only_new: bool) -> bool {
+        if !self.pending.is_empty() {
+            return true;
+        }
         if let Some(ref rx) = self.rx
             && !rx.is_empty()
         {
@@ -341,14 +838,15 @@ impl Walk {
     }
 
     pub fn results_cnt(&mut self, only_new: bool) -> usize {
+        let pending_cnt = self.pending.len();
         if let Some(ref rx) = self.rx {
             if only_new {
-                rx.len()
+                pending_cnt + rx.len()
             } else {
-                self.entries.len() + rx.len()
+                self.entries.len() + pending_cnt + rx.len()
             }
         } else {
-            self.entries.len()
+            self.entries.len() + pending_cnt
         }
     }
 
@@ -373,14 +871,9 @@ impl Walk {
 
     pub fn errors(&mut self, only_new: bool) -> ErrorsType {
         self.results(only_new)
-            .iter()
-            .flat_map(|e| {
-                e.1.errors
-                    .iter()
-                    .map(|err| (e.0.clone(), err.to_string()))
-                    .collect::<Vec<_>>()
-            })
-            .collect::<Vec<_>>()
+            .into_iter()
+            .flat_map(|(_, toc)| toc.errors)
+            .collect()
     }
 
     #[cfg(feature = "speedy")]
@@ -406,10 +899,19 @@ impl Walk {
             statistics.slinks += toc.symlinks.len() as i32;
             statistics.devices += toc.other.len() as i32;
             statistics.errors.extend(toc.errors.clone());
+            statistics
+                .symlink_loops
+                .extend_from_slice(&toc.symlink_loops);
         }
         statistics
     }
 
+    /// Returns the highest number of results which were queued in the channel
+    /// during the last scan.
+    pub fn peak_queue_len(&self) -> usize {
+        self.peak_queue_len.load(Ordering::Relaxed)
+    }
+
     pub fn duration(&mut self) -> f64 {
         *self.duration.lock().unwrap()
     }
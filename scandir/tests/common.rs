@@ -87,3 +87,83 @@ pub fn create_temp_file_tree(
     }
     Ok(temp_dir)
 }
+
+/// Creates a file tree with two symlinks pointing to ancestor directories. One symlink
+/// has a relative and one an absolute target.
+#[cfg(unix)]
+#[allow(dead_code)]
+pub fn create_temp_symlink_loops() -> Result<TempDir, Error> {
+    let temp_dir = setup();
+    let dir = temp_dir.path().join("dir1").join("dir1_1");
+    create_dir_all(&dir)?;
+    File::create(dir.join("file1"))?.write_all(b"HELLO")?;
+    symlink("../..", dir.join("loop_relative"))?;
+    symlink(temp_dir.path().join("dir1"), dir.join("loop_absolute"))?;
+    Ok(temp_dir)
+}
+
+/// Creates a file tree with a symlink `mount` to a directory on another file system than the
+/// temporary directory. Returns `None` if no such directory exists.
+#[cfg(unix)]
+#[allow(dead_code)]
+pub fn create_temp_mount_point_link() -> Result<Option<TempDir>, Error> {
+    use std::os::unix::fs::MetadataExt;
+
+    let temp_dir = setup();
+    let device = std::fs::metadata(temp_dir.path())?.dev();
+    let Some(target) = ["/dev/shm", "/dev", "/sys", "/proc"]
+        .into_iter()
+        .find(|target| std::fs::metadata(target).is_ok_and(|m| m.dev() != device))
+    else {
+        return Ok(None);
+    };
+    create_dir_all(temp_dir.path().join("dir1"))?;
+    File::create(temp_dir.path().join("dir1").join("file1"))?.write_all(b"HELLO")?;
+    symlink(target, temp_dir.path().join("mount"))?;
+    Ok(Some(temp_dir))
+}
+
+/// Creates a git repository with `.git/info/exclude`, `.gitignore` and `.ignore` files. Only the
+/// files `keep.log`, `sub/b.log` and `sub/top.txt`, the ignore files and directory `sub` are
+/// not ignored.
+#[allow(dead_code)]
+pub fn create_temp_git_repo() -> Result<TempDir, Error> {
+    let temp_dir = setup();
+    let root = temp_dir.path();
+    create_dir_all(root.join(".git").join("info"))?;
+    create_dir_all(root.join("build"))?;
+    create_dir_all(root.join("sub"))?;
+    std::fs::write(
+        root.join(".git").join("info").join("exclude"),
+        "excluded.txt\n",
+    )?;
+    std::fs::write(
+        root.join(".gitignore"),
+        "*.log\n!keep.log\nbuild/\n/top.txt\n",
+    )?;
+    std::fs::write(root.join("sub").join(".gitignore"), "!b.log\n")?;
+    std::fs::write(root.join("sub").join(".ignore"), "secret.txt\n")?;
+    for name in ["a.log", "keep.log", "top.txt", "excluded.txt"] {
+        std::fs::write(root.join(name), b"a")?;
+    }
+    for name in ["a.log", "b.log", "top.txt", "secret.txt"] {
+        std::fs::write(root.join("sub").join(name), b"a")?;
+    }
+    std::fs::write(root.join("build").join("out.txt"), b"a")?;
+    Ok(temp_dir)
+}
+
+/// Creates a file tree with a file and a directory whose names aren't valid UTF-8.
+#[cfg(unix)]
+#[allow(dead_code)]
+pub fn create_temp_non_utf8_names() -> Result<TempDir, Error> {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let temp_dir = setup();
+    let dir = temp_dir.path().join(OsStr::from_bytes(b"dir\xff"));
+    create_dir_all(&dir)?;
+    File::create(dir.join("file1.txt"))?.write_all(b"HELLO")?;
+    File::create(temp_dir.path().join(OsStr::from_bytes(b"caf\xe9.txt")))?.write_all(b"HELLO")?;
+    Ok(temp_dir)
+}
@@ -2,7 +2,7 @@
 
 use std::io::Error;
 
-use scandir::Count;
+use scandir::{Count, ErrorPhase, ScanError, ScanErrorKind};
 
 mod common;
 
@@ -98,6 +98,32 @@ fn test_count_follow_links() -> Result<(), Error> {
     //assert!(count.duration > 0.0); --> Fails on MAC
     assert_eq!(0, count.devices);
     #[cfg(windows)]
+    {
+        assert_eq!(193, count.files);
+        assert_eq!(40, count.dirs);
+        assert_eq!(0, count.slinks);
+    }
+    #[cfg(unix)]
+    {
+        assert_eq!(135, count.files);
+        assert_eq!(12, count.dirs);
+        assert_eq!(0, count.slinks);
+        assert_eq!(0, count.pipes);
+    }
+    assert_eq!(0, count.hlinks);
+    common::cleanup(temp_dir)
+}
+
+#[test]
+fn test_count_iter() -> Result<(), Error> {
+    #[cfg(unix)]
+    let temp_dir = common::create_temp_file_tree(3, 3, 4, 5, 6, 7)?;
+    #[cfg(windows)]
+    let temp_dir = common::create_temp_file_tree(3, 3, 4, 5, 3)?;
+    let count = Count::new(temp_dir.path())?.iter()?.last().unwrap();
+    assert!(count.errors.is_empty());
+    assert_eq!(0, count.devices);
+    #[cfg(windows)]
     {
         assert_eq!(85, count.files);
         assert_eq!(13, count.dirs);
@@ -113,3 +139,317 @@ fn test_count_follow_links() -> Result<(), Error> {
     assert_eq!(0, count.hlinks);
     common::cleanup(temp_dir)
 }
+
+#[test]
+fn test_count_multi() -> Result<(), Error> {
+    let temp_dir1 = common::create_temp_file_tree(3, 3, 4, 5, 6, 7)?;
+    let temp_dir2 = common::create_temp_file_tree(3, 3, 4, 5, 6, 7)?;
+    let single = Count::new(temp_dir1.path())?.collect()?;
+    let overlapping = temp_dir1.path().join("dir1");
+    let count = Count::new_multi(&[temp_dir1.path(), temp_dir2.path(), &overlapping])?.collect()?;
+    assert!(count.errors.is_empty());
+    assert_eq!(2 * single.files, count.files);
+    assert_eq!(2 * single.dirs, count.dirs);
+    assert_eq!(2 * single.slinks, count.slinks);
+    assert!(Count::new_multi::<&str>(&[]).is_err());
+    common::cleanup(temp_dir1)?;
+    common::cleanup(temp_dir2)
+}
+
+#[test]
+fn test_count_one_file_system() -> Result<(), Error> {
+    let temp_dir = common::create_temp_file_tree(3, 3, 4, 5, 6, 7)?;
+    let count = Count::new(temp_dir.path())?.collect()?;
+    let count_xdev = Count::new(temp_dir.path())?
+        .one_file_system(true)
+        .collect()?;
+    assert!(count_xdev.errors.is_empty());
+    assert_eq!(count.files, count_xdev.files);
+    assert_eq!(count.dirs, count_xdev.dirs);
+    assert_eq!(count.slinks, count_xdev.slinks);
+    common::cleanup(temp_dir)
+}
+
+#[test]
+fn test_count_gitignore() -> Result<(), Error> {
+    let temp_dir = common::create_temp_git_repo()?;
+    let count = Count::new(temp_dir.path())?
+        .gitignore(true)
+        .skip_hidden(false)
+        .collect()?;
+    assert!(count.errors.is_empty());
+    assert_eq!(1, count.dirs);
+    assert_eq!(6, count.files);
+    let count = Count::new(temp_dir.path())?.skip_hidden(false).collect()?;
+    assert_eq!(4, count.dirs);
+    assert_eq!(13, count.files);
+    common::cleanup(temp_dir)
+}
+
+#[cfg(unix)]
+#[test]
+fn test_count_symlink_loops() -> Result<(), Error> {
+    let temp_dir = common::create_temp_symlink_loops()?;
+    let count = Count::new(temp_dir.path())?.follow_links(true).collect()?;
+    assert!(count.errors.is_empty());
+    assert_eq!(1, count.files);
+    let mut symlink_loops = count.symlink_loops;
+    symlink_loops.sort_by(|a, b| a.path.cmp(&b.path));
+    assert_eq!(2, symlink_loops.len());
+    assert!(symlink_loops[0].path.ends_with("loop_absolute"));
+    assert!(symlink_loops[1].path.ends_with("loop_relative"));
+    assert_eq!("../..", symlink_loops[1].target);
+    assert!(
+        Count::new(temp_dir.path())?
+            .collect()?
+            .symlink_loops
+            .is_empty()
+    );
+    common::cleanup(temp_dir)
+}
+
+#[test]
+fn test_count_scan_errors() -> Result<(), Error> {
+    let e = Count::new("/does/not/exist").unwrap_err();
+    let scan_error = e.get_ref().unwrap().downcast_ref::<ScanError>().unwrap();
+    assert_eq!(ScanErrorKind::NotFound, scan_error.kind);
+    assert_eq!(ErrorPhase::Stat, scan_error.phase);
+    assert_eq!("/does/not/exist", scan_error.path);
+    let temp_dir = common::create_temp_file_tree(1, 1, 1, 1, 1, 1)?;
+    let e = Count::new(temp_dir.path())?
+        .dir_include(Some(vec!["[".to_owned()]))
+        .collect()
+        .unwrap_err();
+    assert_eq!(std::io::ErrorKind::InvalidInput, e.kind());
+    let scan_error = e.get_ref().unwrap().downcast_ref::<ScanError>().unwrap();
+    assert_eq!(ErrorPhase::FilterCompile, scan_error.phase);
+    assert!(scan_error.message.starts_with("dir_include: "));
+    common::cleanup(temp_dir)
+}
+
+#[test]
+fn test_count_du() -> Result<(), Error> {
+    let temp_dir = common::setup();
+    let sub = temp_dir.path().join("sub");
+    let subsub = sub.join("subsub");
+    std::fs::create_dir_all(&subsub)?;
+    std::fs::write(temp_dir.path().join("a.txt"), [0u8; 100])?;
+    std::fs::write(sub.join("b.txt"), [0u8; 200])?;
+    std::fs::write(subsub.join("c.txt"), [0u8; 300])?;
+    let dir_size = |path: &std::path::Path| std::fs::metadata(path).map(|m| m.len());
+    let count = Count::new(temp_dir.path())?;
+    let dir_usages = count.dir_usage_iter()?.collect::<Vec<_>>();
+    let paths = dir_usages
+        .iter()
+        .map(|d| d.path.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(vec!["sub/subsub", "sub", ""], paths);
+    let root = &dir_usages[2];
+    assert_eq!(0, root.depth);
+    assert_eq!(1, root.direct.files);
+    assert_eq!(1, root.direct.dirs);
+    // The root directory itself isn't counted
+    assert_eq!(100, root.direct.size);
+    assert_eq!(3, root.recursive.files);
+    assert_eq!(2, root.recursive.dirs);
+    assert_eq!(
+        dir_size(&sub)? + dir_size(&subsub)? + 600,
+        root.recursive.size
+    );
+    assert_eq!(
+        dir_usages[1].recursive.size,
+        dir_usages[1].direct.size + dir_usages[0].recursive.size
+    );
+    let mut count = Count::new(temp_dir.path())?.du(true);
+    let statistics = count.collect()?;
+    assert_eq!(3, statistics.files);
+    // The usage is calculated like in the statistics
+    assert_eq!(5 * 4096, statistics.usage);
+    assert_eq!(statistics.usage, root.recursive.usage);
+    assert_eq!(dir_usages, count.dir_usages(false));
+    assert!(count.dir_usages(true).is_empty());
+    common::cleanup(temp_dir)
+}
+
+#[test]
+fn test_count_top_n() -> Result<(), Error> {
+    let temp_dir = common::setup();
+    let sub = temp_dir.path().join("sub");
+    std::fs::create_dir_all(sub.join("subsub"))?;
+    std::fs::write(temp_dir.path().join("a.txt"), [0u8; 100])?;
+    std::fs::write(sub.join("b.txt"), [0u8; 20000])?;
+    std::fs::write(sub.join("subsub").join("c.txt"), [0u8; 300])?;
+    let mut count = Count::new(temp_dir.path())?.top_n(2);
+    let statistics = count.collect()?;
+    assert_eq!(3, statistics.files);
+    let largest = count.largest().unwrap();
+    let paths = |entries: &Vec<scandir::TopEntry>| {
+        entries.iter().map(|e| e.path.clone()).collect::<Vec<_>>()
+    };
+    assert_eq!(
+        vec!["sub/b.txt", "sub/subsub/c.txt"],
+        paths(&largest.files_by_size)
+    );
+    assert_eq!(20000, largest.files_by_size[0].size);
+    assert_eq!(2, largest.files_by_usage.len());
+    assert_eq!(vec!["", "sub"], paths(&largest.dirs_by_size));
+    assert!(largest.dirs_by_size[0].size > 20400);
+    // Intermediate directories are not returned without du mode
+    assert!(count.dir_usages(false).is_empty());
+    let mut count = Count::new(temp_dir.path())?;
+    count.collect()?;
+    assert!(count.largest().is_none());
+    common::cleanup(temp_dir)
+}
+
+#[test]
+fn test_count_extension_stats() -> Result<(), Error> {
+    let temp_dir = common::setup();
+    let sub = temp_dir.path().join("sub");
+    std::fs::create_dir_all(&sub)?;
+    std::fs::write(temp_dir.path().join("a.txt"), [0u8; 100])?;
+    std::fs::write(sub.join("b.TXT"), [0u8; 200])?;
+    std::fs::write(sub.join("c.tar.gz"), [0u8; 5000])?;
+    std::fs::write(sub.join(".hidden"), [0u8; 10])?;
+    std::fs::write(sub.join("README"), [0u8; 20])?;
+    let statistics = Count::new(temp_dir.path())?
+        .extension_stats(true)
+        .collect()?;
+    let keys = statistics.extensions.keys().cloned().collect::<Vec<_>>();
+    assert_eq!(vec!["", "tar.gz", "txt"], keys);
+    let txt = &statistics.extensions["txt"];
+    assert_eq!(2, txt.count);
+    assert_eq!(300, txt.size);
+    assert_eq!(8192, txt.usage);
+    assert_eq!(5000, statistics.extensions["tar.gz"].size);
+    assert_eq!(2, statistics.extensions[""].count);
+    #[cfg(feature = "json")]
+    assert!(statistics.to_json()?.contains("\"tar.gz\":{\"count\":1"));
+    let statistics = Count::new(temp_dir.path())?
+        .extension_stats(true)
+        .extension_case_sensitive(true)
+        .multi_part_extensions(Vec::new())
+        .collect()?;
+    let keys = statistics.extensions.keys().cloned().collect::<Vec<_>>();
+    assert_eq!(vec!["", "TXT", "gz", "txt"], keys);
+    let statistics = Count::new(temp_dir.path())?.collect()?;
+    assert!(statistics.extensions.is_empty());
+    common::cleanup(temp_dir)
+}
+
+#[cfg(unix)]
+#[test]
+fn test_count_owner_stats() -> Result<(), Error> {
+    use std::os::unix::fs::MetadataExt;
+
+    let temp_dir = common::setup();
+    std::fs::write(temp_dir.path().join("a.txt"), [0u8; 100])?;
+    std::fs::write(temp_dir.path().join("b.txt"), [0u8; 200])?;
+    let metadata = std::fs::metadata(temp_dir.path().join("a.txt"))?;
+    let statistics = Count::new(temp_dir.path())?.owner_stats(true).collect()?;
+    assert_eq!(1, statistics.users.len());
+    let user = &statistics.users[&metadata.uid()];
+    assert_eq!(2, user.files);
+    assert_eq!(300, user.size);
+    assert_eq!(None, user.name);
+    assert_eq!(300, statistics.groups[&metadata.gid()].size);
+    let statistics = Count::new(temp_dir.path())?
+        .owner_stats(true)
+        .resolve_names(true)
+        .collect()?;
+    assert_eq!(
+        scandir::common::user_name(metadata.uid()),
+        statistics.users[&metadata.uid()].name
+    );
+    if metadata.uid() == 0 {
+        assert_eq!(Some("root"), statistics.users[&0].name.as_deref());
+    }
+    let statistics = Count::new(temp_dir.path())?.collect()?;
+    assert!(statistics.users.is_empty());
+    common::cleanup(temp_dir)
+}
+
+#[test]
+fn test_count_histograms() -> Result<(), Error> {
+    use std::fs::{File, FileTimes};
+    use std::time::{Duration, SystemTime};
+
+    let temp_dir = common::setup();
+    let sub = temp_dir.path().join("sub");
+    std::fs::create_dir_all(&sub)?;
+    std::fs::write(temp_dir.path().join("empty.txt"), [])?;
+    std::fs::write(temp_dir.path().join("a.txt"), [0u8; 1])?;
+    std::fs::write(sub.join("b.txt"), [0u8; 1000])?;
+    std::fs::write(sub.join("c.txt"), [0u8; 1023])?;
+    let old = SystemTime::now() - Duration::from_secs(10 * 86400);
+    File::options()
+        .write(true)
+        .open(sub.join("c.txt"))?
+        .set_times(FileTimes::new().set_modified(old).set_accessed(old))?;
+    let statistics = Count::new(temp_dir.path())?.histograms(true).collect()?;
+    assert_eq!(
+        vec![1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 2],
+        statistics.size_histogram
+    );
+    assert_eq!(vec![0, 3, 2], statistics.depth_histogram);
+    assert_eq!(
+        scandir::DEFAULT_AGE_BUCKETS.to_vec(),
+        statistics.mtime_histogram.bounds
+    );
+    assert_eq!(vec![3, 0, 1, 0, 0], statistics.mtime_histogram.counts);
+    assert_eq!(vec![3, 0, 1, 0, 0], statistics.atime_histogram.counts);
+    let statistics = Count::new(temp_dir.path())?
+        .histograms(true)
+        .age_buckets(vec![3600])
+        .collect()?;
+    assert_eq!(vec![3, 1], statistics.mtime_histogram.counts);
+    let mut merged = statistics.clone();
+    merged.merge(&statistics)?;
+    assert_eq!(8, merged.files);
+    assert_eq!(vec![6, 2], merged.mtime_histogram.counts);
+    assert_eq!(vec![0, 6, 4], merged.depth_histogram);
+    let mut other = statistics.clone();
+    other.mtime_histogram = scandir::Histogram::new(&[60]);
+    other.mtime_histogram.add(0);
+    assert!(merged.merge(&other).is_err());
+    assert_eq!(8, merged.files);
+    assert_eq!(vec![6, 2], merged.mtime_histogram.counts);
+    // The default histogram has a single bucket for all values
+    let mut histogram = scandir::Histogram::default();
+    histogram.add(100);
+    assert_eq!(vec![1], histogram.counts);
+    #[cfg(unix)]
+    {
+        std::fs::hard_link(sub.join("b.txt"), sub.join("hardlink.txt"))?;
+        let statistics = Count::new(temp_dir.path())?
+            .histograms(true)
+            .extended(true)
+            .collect()?;
+        assert_eq!(1, statistics.hlinks);
+        assert_eq!(
+            vec![1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 2],
+            statistics.size_histogram
+        );
+        assert_eq!(vec![0, 3, 2], statistics.depth_histogram);
+        assert_eq!(vec![3, 0, 1, 0, 0], statistics.mtime_histogram.counts);
+        std::fs::remove_file(sub.join("hardlink.txt"))?;
+    }
+    let statistics = Count::new(temp_dir.path())?.collect()?;
+    assert!(statistics.size_histogram.is_empty());
+    assert!(statistics.mtime_histogram.is_empty());
+    common::cleanup(temp_dir)
+}
+
+#[test]
+fn test_count_size_filter() -> Result<(), Error> {
+    let temp_dir = common::setup();
+    std::fs::write(temp_dir.path().join("empty.txt"), [])?;
+    std::fs::write(temp_dir.path().join("small.txt"), [0u8; 10])?;
+    std::fs::write(temp_dir.path().join("large.txt"), [0u8; 5000])?;
+    let statistics = Count::new(temp_dir.path())?.min_size(Some(10)).collect()?;
+    assert_eq!(2, statistics.files);
+    assert_eq!(5010, statistics.size);
+    let statistics = Count::new(temp_dir.path())?.max_size(Some(0)).collect()?;
+    assert_eq!(1, statistics.files);
+    common::cleanup(temp_dir)
+}
@@ -1,8 +1,9 @@
 #![cfg_attr(windows, feature(junction_point))]
 
+use std::collections::HashMap;
 use std::io::Error;
 
-use scandir::{ReturnType, Scandir, ScandirResult};
+use scandir::{HashAlgorithm, HashMode, ReturnType, Scandir, ScandirResult};
 
 mod common;
 
@@ -125,3 +126,407 @@ fn test_scandir_follow_links() -> Result<(), Error> {
     }
     common::cleanup(temp_dir)
 }
+
+#[test]
+fn test_scandir_iter() -> Result<(), Error> {
+    #[cfg(unix)]
+    let temp_dir = common::create_temp_file_tree(3, 3, 4, 5, 6, 7)?;
+    #[cfg(windows)]
+    let temp_dir = common::create_temp_file_tree(3, 3, 4, 5, 3)?;
+    let scandir = Scandir::new(temp_dir.path(), Some(false))?;
+    let entries = scandir.iter()?.collect::<Vec<_>>();
+    #[cfg(unix)]
+    assert_eq!(210, entries.len());
+    #[cfg(windows)]
+    assert_eq!(125, entries.len());
+    assert!(entries.iter().all(|e| e.error().is_none()));
+    let dirs = scandir.iter()?.filter(|e| e.is_dir()).take(2).count();
+    assert_eq!(2, dirs);
+    common::cleanup(temp_dir)
+}
+
+#[test]
+fn test_scandir_channel_capacity() -> Result<(), Error> {
+    #[cfg(unix)]
+    let temp_dir = common::create_temp_file_tree(3, 3, 4, 5, 6, 7)?;
+    #[cfg(windows)]
+    let temp_dir = common::create_temp_file_tree(3, 3, 4, 5, 3)?;
+    let mut scandir = Scandir::new(temp_dir.path(), Some(true))?.channel_capacity(4);
+    let entries = scandir.collect()?;
+    #[cfg(unix)]
+    assert_eq!(210, entries.results.len());
+    #[cfg(windows)]
+    assert_eq!(125, entries.results.len());
+    assert_eq!(0, entries.errors.len());
+    assert!(scandir.peak_queue_len() <= 4);
+    common::cleanup(temp_dir)
+}
+
+#[test]
+fn test_scandir_multi() -> Result<(), Error> {
+    let temp_dir1 = common::create_temp_file_tree(3, 3, 4, 5, 6, 7)?;
+    let temp_dir2 = common::create_temp_file_tree(3, 3, 4, 5, 6, 7)?;
+    let overlapping = temp_dir1.path().join("dir1");
+    let roots = [temp_dir1.path(), temp_dir2.path(), &overlapping];
+    let mut scandir = Scandir::new_multi(&roots, Some(true))?;
+    let entries = scandir.collect()?;
+    assert_eq!(0, entries.errors.len());
+    assert_eq!(
+        2 * Scandir::new(temp_dir1.path(), Some(true))?
+            .collect()?
+            .results
+            .len(),
+        entries.results.len()
+    );
+    assert!(entries.results.iter().all(|e| {
+        e.path().starts_with(temp_dir1.path().to_str().unwrap())
+            || e.path().starts_with(temp_dir2.path().to_str().unwrap())
+    }));
+    assert_eq!(entries.results.len(), scandir.iter()?.count());
+    common::cleanup(temp_dir1)?;
+    common::cleanup(temp_dir2)
+}
+
+#[test]
+fn test_scandir_one_file_system() -> Result<(), Error> {
+    let temp_dir = common::create_temp_file_tree(3, 3, 4, 5, 6, 7)?;
+    let mut scandir = Scandir::new(temp_dir.path(), Some(true))?
+        .one_file_system(true)
+        .return_type(ReturnType::Ext);
+    let entries = scandir.collect()?;
+    assert_eq!(0, entries.errors.len());
+    assert_eq!(
+        Scandir::new(temp_dir.path(), Some(true))?
+            .collect()?
+            .results
+            .len(),
+        entries.results.len()
+    );
+    common::cleanup(temp_dir)
+}
+
+#[test]
+fn test_scandir_gitignore() -> Result<(), Error> {
+    let temp_dir = common::create_temp_git_repo()?;
+    let mut paths = Scandir::new(temp_dir.path(), Some(true))?
+        .gitignore(true)
+        .skip_hidden(false)
+        .collect()?
+        .results
+        .iter()
+        .map(|result| result.path().replace('\\', "/"))
+        .collect::<Vec<_>>();
+    paths.sort();
+    assert_eq!(
+        vec![
+            ".gitignore",
+            "keep.log",
+            "sub",
+            "sub/.gitignore",
+            "sub/.ignore",
+            "sub/b.log",
+            "sub/top.txt"
+        ],
+        paths
+    );
+    common::cleanup(temp_dir)
+}
+
+#[cfg(unix)]
+#[test]
+fn test_scandir_symlink_loops() -> Result<(), Error> {
+    let temp_dir = common::create_temp_symlink_loops()?;
+    let mut scandir = Scandir::new(temp_dir.path(), Some(true))?.follow_links(true);
+    let entries = scandir.collect()?;
+    assert_eq!(0, entries.errors.len());
+    assert_eq!(2, entries.symlink_loops.len());
+    assert_eq!(1, entries.results.iter().filter(|e| e.is_file()).count());
+    // The iterator returns the symlink loops in the order of the walk
+    let symlink_loops = scandir
+        .iter()?
+        .filter_map(|e| match e {
+            ScandirResult::SymlinkLoop(symlink_loop) => Some(symlink_loop),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(entries.symlink_loops, symlink_loops);
+    common::cleanup(temp_dir)
+}
+
+#[cfg(unix)]
+#[test]
+fn test_scandir_non_utf8_names() -> Result<(), Error> {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    #[cfg(feature = "json")]
+    use scandir::DirEntry;
+
+    let temp_dir = common::create_temp_non_utf8_names()?;
+    let entries = Scandir::new(temp_dir.path(), Some(true))?
+        .file_include(Some(vec!["*.txt".to_owned()]))
+        .collect()?;
+    assert_eq!(0, entries.errors.len());
+    let mut paths = entries
+        .results
+        .iter()
+        .map(|e| e.os_path())
+        .collect::<Vec<_>>();
+    paths.sort();
+    assert_eq!(
+        vec![
+            OsStr::from_bytes(b"caf\xe9.txt"),
+            OsStr::from_bytes(b"dir\xff"),
+            OsStr::from_bytes(b"dir\xff/file1.txt"),
+        ],
+        paths
+    );
+    let Some(ScandirResult::DirEntry(entry)) = entries
+        .results
+        .iter()
+        .find(|e| e.path() == "caf\u{FFFD}.txt")
+    else {
+        panic!("Entry with lossy path not found");
+    };
+    assert_eq!(Some(b"caf\xe9.txt".to_vec()), entry.path_bytes);
+    #[cfg(feature = "json")]
+    {
+        let entry: DirEntry = serde_json::from_str(&entry.to_json()?)?;
+        assert_eq!(OsStr::from_bytes(b"caf\xe9.txt"), entry.os_path());
+    }
+    common::cleanup(temp_dir)
+}
+
+#[test]
+fn test_scandir_top_n() -> Result<(), Error> {
+    let temp_dir = common::setup();
+    let sub = temp_dir.path().join("sub");
+    std::fs::create_dir_all(sub.join("subsub"))?;
+    std::fs::write(temp_dir.path().join("a.txt"), [0u8; 100])?;
+    std::fs::write(sub.join("b.txt"), [0u8; 20000])?;
+    std::fs::write(sub.join("subsub").join("c.txt"), [0u8; 300])?;
+    let mut scandir = Scandir::new(temp_dir.path(), Some(false))?
+        .return_type(ReturnType::Ext)
+        .top_n(2);
+    let entries = scandir.collect()?;
+    assert_eq!(5, entries.results.len());
+    let largest = scandir.largest().unwrap();
+    let paths = |entries: &Vec<scandir::TopEntry>| {
+        entries.iter().map(|e| e.path.clone()).collect::<Vec<_>>()
+    };
+    assert_eq!(
+        vec!["sub/b.txt", "sub/subsub/c.txt"],
+        paths(&largest.files_by_size)
+    );
+    assert_eq!(vec!["", "sub"], paths(&largest.dirs_by_size));
+    let mut count = scandir::Count::new(temp_dir.path())?.top_n(2);
+    count.collect()?;
+    assert_eq!(count.largest().unwrap().dirs_by_size, largest.dirs_by_size);
+    common::cleanup(temp_dir)
+}
+
+#[test]
+fn test_scandir_hash() -> Result<(), Error> {
+    let temp_dir = common::setup();
+    std::fs::write(temp_dir.path().join("abc.txt"), b"abc")?;
+    std::fs::write(temp_dir.path().join("long.txt"), b"abcd-ignored-efgh")?;
+    std::fs::write(temp_dir.path().join("short.txt"), b"abcdefgh")?;
+    std::fs::create_dir(temp_dir.path().join("sub"))?;
+    let digests = |scandir: &mut Scandir| -> Result<HashMap<String, Option<String>>, Error> {
+        Ok(scandir
+            .collect()?
+            .results
+            .iter()
+            .map(|e| (e.path().clone(), e.digest().cloned()))
+            .collect())
+    };
+    let mut scandir = Scandir::new(temp_dir.path(), None)?.hash(Some(HashAlgorithm::Sha256));
+    let results = digests(&mut scandir)?;
+    assert_eq!(
+        Some("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"),
+        results["abc.txt"].as_deref()
+    );
+    assert_eq!(None, results["sub"]);
+    let mut scandir = Scandir::new(temp_dir.path(), None)?
+        .return_type(ReturnType::Ext)
+        .hash(Some(HashAlgorithm::Blake3));
+    assert_eq!(
+        Some("6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"),
+        digests(&mut scandir)?["abc.txt"].as_deref()
+    );
+    // Partial hashes of files with same first and last bytes are equal
+    let mut scandir = Scandir::new(temp_dir.path(), None)?
+        .hash(Some(HashAlgorithm::Xxh3))
+        .hash_mode(HashMode::Partial(4));
+    let results = digests(&mut scandir)?;
+    assert!(results["long.txt"].is_some());
+    assert_eq!(results["long.txt"], results["short.txt"]);
+    let iter_results = scandir
+        .iter()?
+        .map(|e| (e.path().clone(), e.digest().cloned()))
+        .collect::<HashMap<_, _>>();
+    assert_eq!(results, iter_results);
+    common::cleanup(temp_dir)
+}
+
+#[test]
+fn test_scandir_duplicates() -> Result<(), Error> {
+    let temp_dir = common::setup();
+    let sub = temp_dir.path().join("sub");
+    std::fs::create_dir(&sub)?;
+    std::fs::write(temp_dir.path().join("a.txt"), b"hello world")?;
+    std::fs::write(sub.join("b.txt"), b"hello world")?;
+    std::fs::write(temp_dir.path().join("c.txt"), b"hello worle")?;
+    std::fs::write(temp_dir.path().join("empty1.txt"), b"")?;
+    std::fs::write(temp_dir.path().join("empty2.txt"), b"")?;
+    #[cfg(unix)]
+    std::fs::hard_link(temp_dir.path().join("a.txt"), temp_dir.path().join("d.txt"))?;
+    // Same first and last bytes, but different content in the middle
+    let mut data = vec![1u8; 10000];
+    std::fs::write(temp_dir.path().join("e1.bin"), &data)?;
+    data[5000] = 2;
+    std::fs::write(temp_dir.path().join("e2.bin"), &data)?;
+    for return_type in [ReturnType::Base, ReturnType::Ext] {
+        let mut scandir = Scandir::new(temp_dir.path(), None)?.return_type(return_type);
+        scandir.collect()?;
+        let duplicates = scandir.duplicates(HashAlgorithm::Xxh3, 4096)?;
+        assert!(duplicates.errors.is_empty());
+        assert_eq!(1, duplicates.sets.len());
+        let set = &duplicates.sets[0];
+        assert_eq!(11, set.size);
+        assert_eq!(2, set.paths.len());
+        assert_eq!("sub/b.txt", set.paths[1]);
+        assert_eq!(11, duplicates.reclaimable);
+    }
+    // Digests created while scanning are reused
+    let mut scandir = Scandir::new(temp_dir.path(), None)?.hash(Some(HashAlgorithm::Sha256));
+    scandir.collect()?;
+    let duplicates = scandir.duplicates(HashAlgorithm::Sha256, 0)?;
+    assert_eq!(1, duplicates.sets.len());
+    let mut scandir = Scandir::new(temp_dir.path(), Some(false))?;
+    scandir.collect()?;
+    assert!(scandir.duplicates(HashAlgorithm::Xxh3, 4096).is_err());
+    common::cleanup(temp_dir)
+}
+
+#[test]
+fn test_scandir_time_filter() -> Result<(), Error> {
+    use std::fs::{File, FileTimes};
+    use std::time::{Duration, SystemTime};
+
+    use scandir::{TimeBound, TimeRange};
+
+    let temp_dir = common::setup();
+    std::fs::write(temp_dir.path().join("new.txt"), b"new")?;
+    std::fs::write(temp_dir.path().join("old.txt"), b"old")?;
+    let old = SystemTime::now() - Duration::from_secs(100 * 86400);
+    File::options()
+        .write(true)
+        .open(temp_dir.path().join("old.txt"))?
+        .set_times(FileTimes::new().set_modified(old).set_accessed(old))?;
+    let paths = |results: &Vec<ScandirResult>| {
+        let mut paths = results.iter().map(|r| r.path().clone()).collect::<Vec<_>>();
+        paths.sort();
+        paths
+    };
+    let entries = Scandir::new(temp_dir.path(), Some(true))?
+        .mtime(Some(TimeRange::older_than(TimeBound::Age(
+            Duration::from_secs(90 * 86400),
+        ))))
+        .collect()?;
+    assert_eq!(vec!["old.txt"], paths(&entries.results));
+    let entries = Scandir::new(temp_dir.path(), Some(true))?
+        .atime(Some(TimeRange::newer_than(TimeBound::Age(
+            Duration::from_secs(3600),
+        ))))
+        .collect()?;
+    assert_eq!(vec!["new.txt"], paths(&entries.results));
+    let entries = Scandir::new(temp_dir.path(), Some(true))?
+        .mtime(Some(TimeRange::new(
+            Some(TimeBound::At(old - Duration::from_secs(1))),
+            Some(TimeBound::At(old + Duration::from_secs(1))),
+        )))
+        .collect()?;
+    assert_eq!(vec!["old.txt"], paths(&entries.results));
+    common::cleanup(temp_dir)
+}
+
+#[test]
+fn test_scandir_entry_types() -> Result<(), Error> {
+    use scandir::EntryType;
+
+    let temp_dir = common::setup();
+    let sub = temp_dir.path().join("sub");
+    std::fs::create_dir_all(sub.join("subsub"))?;
+    std::fs::write(temp_dir.path().join("a.txt"), b"a")?;
+    std::fs::write(sub.join("subsub").join("b.txt"), b"b")?;
+    let paths = |results: &Vec<ScandirResult>| {
+        let mut paths = results.iter().map(|r| r.path().clone()).collect::<Vec<_>>();
+        paths.sort();
+        paths
+    };
+    let entries = Scandir::new(temp_dir.path(), Some(true))?
+        .entry_types(Some(vec![EntryType::File]))
+        .collect()?;
+    assert_eq!(vec!["a.txt", "sub/subsub/b.txt"], paths(&entries.results));
+    let entries = Scandir::new(temp_dir.path(), Some(true))?
+        .entry_types(Some(vec![EntryType::Dir]))
+        .collect()?;
+    assert_eq!(vec!["sub", "sub/subsub"], paths(&entries.results));
+    let results = Scandir::new(temp_dir.path(), Some(true))?
+        .entry_types(Some(vec![EntryType::File]))
+        .iter()?
+        .collect::<Vec<_>>();
+    assert_eq!(vec!["a.txt", "sub/subsub/b.txt"], paths(&results));
+    common::cleanup(temp_dir)
+}
+
+#[test]
+fn test_scandir_sort_by() -> Result<(), Error> {
+    use scandir::{EntryType, SortBy, SortKey};
+
+    let temp_dir = common::setup();
+    let root = temp_dir.path();
+    std::fs::create_dir_all(root.join("sub"))?;
+    std::fs::write(root.join("file10.txt"), b"abc")?;
+    std::fs::write(root.join("file2.txt"), b"abcdefghij")?;
+    std::fs::write(root.join("file1.log"), b"a")?;
+    std::fs::write(root.join("sub").join("big.bin"), [0u8; 100])?;
+    let sorted_paths = |sort_by: &str| -> Result<Vec<String>, Error> {
+        Ok(Scandir::new(root, Some(true))?
+            .max_depth(1)
+            .entry_types(Some(vec![EntryType::File]))
+            .sort_by(Some(sort_by.parse()?))
+            .iter()?
+            .map(|result| result.path().clone())
+            .collect())
+    };
+    assert_eq!(
+        vec!["file1.log", "file2.txt", "file10.txt"],
+        sorted_paths("natural")?
+    );
+    assert_eq!(
+        vec!["file2.txt", "file10.txt", "file1.log"],
+        sorted_paths("-size")?
+    );
+    assert_eq!(
+        vec!["file1.log", "file10.txt", "file2.txt"],
+        sorted_paths("ext")?
+    );
+    assert!("-colour".parse::<SortBy>().is_err());
+    // Global sort of all directories
+    let mut entries = Scandir::new(root, Some(true))?
+        .entry_types(Some(vec![EntryType::File]))
+        .collect()?;
+    entries.sort(SortBy::new(SortKey::Size, true));
+    let paths = entries
+        .results
+        .iter()
+        .map(|result| result.path().clone())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vec!["sub/big.bin", "file2.txt", "file10.txt", "file1.log"],
+        paths
+    );
+    common::cleanup(temp_dir)
+}
@@ -2,7 +2,8 @@
 
 use std::io::Error;
 
-use scandir::Walk;
+use scandir::common::mount_point_error;
+use scandir::{ErrorPhase, Toc, Walk};
 
 mod common;
 
@@ -111,3 +112,706 @@ fn test_walk_follow_links() -> Result<(), Error> {
     assert_eq!(0, toc.errors.len());
     common::cleanup(temp_dir)
 }
+
+#[test]
+fn test_walk_iter() -> Result<(), Error> {
+    #[cfg(unix)]
+    let temp_dir = common::create_temp_file_tree(3, 3, 4, 5, 6, 7)?;
+    #[cfg(windows)]
+    let temp_dir = common::create_temp_file_tree(3, 3, 4, 5, 3)?;
+    let walk = Walk::new(temp_dir.path(), Some(true))?;
+    let mut toc = Toc::new();
+    for (root_dir, dir_toc) in walk.iter()? {
+        toc.extend(&root_dir, &dir_toc);
+    }
+    #[cfg(windows)]
+    {
+        assert_eq!(67, toc.files.len());
+        assert_eq!(13, toc.dirs.len());
+        assert_eq!(27, toc.symlinks.len());
+        assert_eq!(0, toc.other.len());
+    }
+    #[cfg(unix)]
+    {
+        assert_eq!(63, toc.files.len());
+        assert_eq!(12, toc.dirs.len());
+        assert_eq!(54, toc.symlinks.len());
+        assert_eq!(63, toc.other.len());
+    }
+    assert_eq!(0, toc.errors.len());
+    assert_eq!(
+        1,
+        walk.iter()?
+            .take_while(|(root_dir, _)| root_dir.is_empty())
+            .count()
+    );
+    common::cleanup(temp_dir)
+}
+
+#[test]
+fn test_walk_channel_capacity() -> Result<(), Error> {
+    #[cfg(unix)]
+    let temp_dir = common::create_temp_file_tree(3, 3, 4, 5, 6, 7)?;
+    #[cfg(windows)]
+    let temp_dir = common::create_temp_file_tree(3, 3, 4, 5, 3)?;
+    let mut walk = Walk::new(temp_dir.path(), Some(true))?.channel_capacity(1);
+    walk.start()?;
+    let mut toc = Toc::new();
+    while !walk.finished() || walk.has_results(true) {
+        for (root_dir, dir_toc) in walk.results(true) {
+            toc.extend(&root_dir, &dir_toc);
+        }
+    }
+    walk.join();
+    for (root_dir, dir_toc) in walk.results(true) {
+        toc.extend(&root_dir, &dir_toc);
+    }
+    #[cfg(windows)]
+    assert_eq!(67, toc.files.len());
+    #[cfg(unix)]
+    assert_eq!(63, toc.files.len());
+    assert!(walk.peak_queue_len() <= 1);
+    common::cleanup(temp_dir)
+}
+
+#[test]
+fn test_walk_multi() -> Result<(), Error> {
+    let temp_dir1 = common::create_temp_file_tree(3, 3, 4, 5, 6, 7)?;
+    let temp_dir2 = common::create_temp_file_tree(3, 3, 4, 5, 6, 7)?;
+    let single = Walk::new(temp_dir1.path(), Some(true))?.collect()?;
+    let overlapping = temp_dir1.path().join("dir1");
+    let roots = [temp_dir1.path(), temp_dir2.path(), &overlapping];
+    let mut walk = Walk::new_multi(&roots, Some(true))?;
+    let toc = walk.collect()?;
+    assert_eq!(0, toc.errors.len());
+    assert_eq!(2 * single.files.len(), toc.files.len());
+    assert_eq!(2 * single.dirs.len(), toc.dirs.len());
+    assert!(walk.results(false).iter().all(|(root_dir, _)| {
+        root_dir.starts_with(temp_dir1.path().to_str().unwrap())
+            || root_dir.starts_with(temp_dir2.path().to_str().unwrap())
+    }));
+    assert_eq!(walk.results(false).len(), walk.iter()?.count());
+    common::cleanup(temp_dir1)?;
+    common::cleanup(temp_dir2)
+}
+
+#[cfg(unix)]
+#[test]
+fn test_walk_symlink_loops() -> Result<(), Error> {
+    let temp_dir = common::create_temp_symlink_loops()?;
+    let mut walk = Walk::new(temp_dir.path(), Some(true))?.follow_links(true);
+    let toc = walk.collect()?;
+    assert_eq!(2, walk.statistics().symlink_loops.len());
+    assert_eq!(0, toc.errors.len());
+    assert_eq!(1, toc.files.len());
+    let mut symlink_loops = toc.symlink_loops;
+    symlink_loops.sort_by(|a, b| a.path.cmp(&b.path));
+    assert!(symlink_loops[0].path.ends_with("loop_absolute"));
+    assert_eq!(
+        temp_dir.path().join("dir1").to_str().unwrap(),
+        symlink_loops[0].target
+    );
+    assert!(symlink_loops[1].path.ends_with("loop_relative"));
+    assert_eq!("../..", symlink_loops[1].target);
+    common::cleanup(temp_dir)
+}
+
+#[cfg(unix)]
+#[test]
+fn test_walk_iter_symlink_loop_only() -> Result<(), Error> {
+    let temp_dir = common::setup();
+    let dir = temp_dir.path().join("dir1");
+    std::fs::create_dir_all(&dir)?;
+    std::os::unix::fs::symlink(temp_dir.path(), dir.join("loop"))?;
+    // The only child of `dir1` is a symlink loop, which is removed from the entries
+    let walk = Walk::new(temp_dir.path(), Some(true))?.follow_links(true);
+    let tocs = walk.iter()?.collect::<Vec<_>>();
+    let (_, toc) = tocs.iter().find(|(dir, _)| dir == "dir1").unwrap();
+    assert!(toc.files.is_empty() && toc.dirs.is_empty());
+    assert_eq!(1, toc.symlink_loops.len());
+    assert!(toc.symlink_loops[0].path.ends_with("loop"));
+    common::cleanup(temp_dir)
+}
+
+#[cfg(unix)]
+#[test]
+fn test_walk_toc_extend_errors() {
+    let mut dir_toc = Toc::new();
+    dir_toc.files.push("file1".to_owned());
+    dir_toc
+        .errors
+        .push(mount_point_error("/dir1/proc".to_owned()));
+    let mut toc = Toc::new();
+    toc.extend("dir1", &dir_toc);
+    assert_eq!(vec!["dir1/file1".to_owned()], toc.files);
+    assert_eq!(dir_toc.errors, toc.errors);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_walk_one_file_system() -> Result<(), Error> {
+    let Some(temp_dir) = common::create_temp_mount_point_link()? else {
+        return Ok(());
+    };
+    let toc = Walk::new(temp_dir.path(), Some(true))?
+        .follow_links(true)
+        .one_file_system(true)
+        .collect()?;
+    assert_eq!(vec!["dir1/file1".to_owned()], toc.files);
+    assert_eq!(1, toc.errors.len());
+    assert_eq!(ErrorPhase::ReadDir, toc.errors[0].phase);
+    assert!(toc.errors[0].path.ends_with("mount"));
+    assert!(toc.dirs.iter().all(|dir| !dir.starts_with("mount/")));
+    common::cleanup(temp_dir)
+}
+
+#[cfg(unix)]
+#[test]
+fn test_walk_non_utf8_names() -> Result<(), Error> {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let temp_dir = common::create_temp_non_utf8_names()?;
+    let mut walk = Walk::new(temp_dir.path(), Some(true))?;
+    let toc = walk.collect()?;
+    assert!(toc.errors.is_empty());
+    assert_eq!(vec![OsStr::from_bytes(b"dir\xff")], toc.os_dirs());
+    let mut files = toc.os_files();
+    files.sort();
+    assert_eq!(
+        vec![
+            OsStr::from_bytes(b"caf\xe9.txt"),
+            OsStr::from_bytes(b"dir\xff/file1.txt"),
+        ],
+        files
+    );
+    let results = walk.results(false);
+    let (key, dir_toc) = results
+        .iter()
+        .find(|(_, toc)| toc.raw_dir.is_some())
+        .unwrap();
+    assert_eq!(OsStr::from_bytes(b"dir\xff"), dir_toc.os_dir(key));
+    common::cleanup(temp_dir)
+}
+
+#[test]
+fn test_walk_size_filter() -> Result<(), Error> {
+    let temp_dir = common::setup();
+    let sub = temp_dir.path().join("sub");
+    std::fs::create_dir_all(&sub)?;
+    std::fs::write(temp_dir.path().join("empty.txt"), [])?;
+    std::fs::write(temp_dir.path().join("small.txt"), [0u8; 10])?;
+    std::fs::write(sub.join("large.txt"), [0u8; 5000])?;
+    let mut toc = Walk::new(temp_dir.path(), Some(true))?
+        .min_size(Some(100))
+        .collect()?;
+    assert_eq!(vec!["sub"], toc.dirs);
+    assert_eq!(vec!["sub/large.txt"], toc.files);
+    toc = Walk::new(temp_dir.path(), Some(true))?
+        .max_size(Some(0))
+        .collect()?;
+    assert_eq!(vec!["empty.txt"], toc.files);
+    toc = Walk::new(temp_dir.path(), Some(true))?
+        .min_size(Some(1))
+        .max_size(Some(10))
+        .collect()?;
+    assert_eq!(vec!["small.txt"], toc.files);
+    common::cleanup(temp_dir)
+}
+
+#[test]
+fn test_walk_regex_filter() -> Result<(), Error> {
+    use scandir::{ErrorPhase, ScanError};
+
+    let temp_dir = common::setup();
+    let build = temp_dir.path().join("build_2024");
+    let src = temp_dir.path().join("src");
+    std::fs::create_dir_all(&build)?;
+    std::fs::create_dir_all(&src)?;
+    for name in ["log.1", "log.12", "log.txt", "Data.CSV"] {
+        std::fs::write(src.join(name), b"a")?;
+        std::fs::write(build.join(name), b"a")?;
+    }
+    let mut toc = Walk::new(temp_dir.path(), Some(true))?
+        .dir_exclude_regex(Some(vec![r"^build_\d+$".to_owned()]))
+        .file_include_regex(Some(vec![r"^log\.\d+$".to_owned()]))
+        .collect()?;
+    assert_eq!(vec!["src"], toc.dirs);
+    toc.files.sort();
+    assert_eq!(vec!["src/log.1", "src/log.12"], toc.files);
+    toc = Walk::new(temp_dir.path(), Some(true))?
+        .dir_include_regex(Some(vec!["^src".to_owned()]))
+        .file_include(Some(vec!["*.txt".to_owned()]))
+        .file_include_regex(Some(vec![r"\.csv$".to_owned()]))
+        .case_sensitive(false)
+        .collect()?;
+    assert_eq!(vec!["src"], toc.dirs);
+    toc.files.sort();
+    assert_eq!(vec!["src/Data.CSV", "src/log.txt"], toc.files);
+    let e = Walk::new(temp_dir.path(), Some(true))?
+        .file_exclude_regex(Some(vec!["(".to_owned()]))
+        .collect()
+        .unwrap_err();
+    assert_eq!(std::io::ErrorKind::InvalidInput, e.kind());
+    let scan_error = e.get_ref().unwrap().downcast_ref::<ScanError>().unwrap();
+    assert_eq!(ErrorPhase::FilterCompile, scan_error.phase);
+    assert!(scan_error.message.starts_with("file_exclude_regex: "));
+    common::cleanup(temp_dir)
+}
+
+#[cfg(unix)]
+#[test]
+fn test_walk_entry_types() -> Result<(), Error> {
+    use scandir::EntryType;
+
+    let temp_dir = common::setup();
+    let sub = temp_dir.path().join("sub");
+    std::fs::create_dir_all(&sub)?;
+    std::fs::write(sub.join("a.txt"), b"a")?;
+    std::os::unix::fs::symlink("a.txt", sub.join("link"))?;
+    let toc = Walk::new(temp_dir.path(), Some(true))?
+        .entry_types(Some(vec![EntryType::Symlink]))
+        .collect()?;
+    assert!(toc.dirs.is_empty());
+    assert!(toc.files.is_empty());
+    assert_eq!(vec!["sub/link"], toc.symlinks);
+    let statistics = scandir::Count::new(temp_dir.path())?
+        .entry_types(Some(vec![EntryType::File]))
+        .collect()?;
+    assert_eq!(0, statistics.dirs);
+    assert_eq!(0, statistics.slinks);
+    assert_eq!(1, statistics.files);
+    common::cleanup(temp_dir)
+}
+
+#[cfg(unix)]
+#[test]
+fn test_walk_perm_owner_links() -> Result<(), Error> {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+    use scandir::PermMatch;
+
+    let temp_dir = common::setup();
+    let shared = temp_dir.path().join("shared");
+    std::fs::create_dir_all(&shared)?;
+    std::fs::write(shared.join("private.txt"), b"a")?;
+    std::fs::write(temp_dir.path().join("public.txt"), b"b")?;
+    std::fs::set_permissions(&shared, std::fs::Permissions::from_mode(0o777))?;
+    std::fs::set_permissions(
+        shared.join("private.txt"),
+        std::fs::Permissions::from_mode(0o600),
+    )?;
+    std::fs::set_permissions(
+        temp_dir.path().join("public.txt"),
+        std::fs::Permissions::from_mode(0o666),
+    )?;
+    std::fs::hard_link(shared.join("private.txt"), temp_dir.path().join("hard.txt"))?;
+    let toc = Walk::new(temp_dir.path(), Some(true))?
+        .perm(Some("-002".parse()?))
+        .collect()?;
+    assert_eq!(vec!["shared"], toc.dirs);
+    assert_eq!(vec!["public.txt"], toc.files);
+    let toc = Walk::new(temp_dir.path(), Some(true))?
+        .perm(Some(PermMatch::Exact(0o600)))
+        .min_links(Some(2))
+        .collect()?;
+    assert!(toc.dirs.is_empty());
+    assert_eq!(vec!["hard.txt", "shared/private.txt"], toc.files);
+    let uid = std::fs::metadata(temp_dir.path())?.uid();
+    let gid = std::fs::metadata(temp_dir.path())?.gid();
+    let toc = Walk::new(temp_dir.path(), Some(true))?
+        .uid(Some(uid))
+        .max_links(Some(1))
+        .collect()?;
+    assert!(toc.dirs.is_empty());
+    assert_eq!(vec!["public.txt"], toc.files);
+    let toc = Walk::new(temp_dir.path(), Some(true))?
+        .uid(Some(uid.wrapping_add(1)))
+        .collect()?;
+    assert!(toc.dirs.is_empty());
+    assert!(toc.files.is_empty());
+    assert!("-0999".parse::<PermMatch>().is_err());
+    // User and group names are resolved while parsing the filter expression
+    if let Some(name) = scandir::common::user_name(uid) {
+        assert_eq!(Some(uid), scandir::common::user_id(&name));
+        let toc = Walk::new(temp_dir.path(), Some(true))?
+            .filter_expr(Some(format!("user {name} and group {gid}")))
+            .collect()?;
+        assert!(!toc.files.is_empty());
+    }
+    let e = Walk::new(temp_dir.path(), Some(true))?
+        .filter_expr(Some("user no-such-user-name".to_owned()))
+        .collect()
+        .unwrap_err();
+    assert!(e.to_string().contains("Unknown user no-such-user-name"));
+    let unknown_uid = 3_999_999_999;
+    assert!(scandir::common::uid_exists(uid));
+    assert!(!scandir::common::uid_exists(unknown_uid));
+    let owners = scandir::OwnerCache::default();
+    assert!(!owners.uid_exists(unknown_uid));
+    assert!(!owners.clone().uid_exists(unknown_uid));
+    let toc = Walk::new(temp_dir.path(), Some(true))?
+        .nouser(true)
+        .collect()?;
+    assert!(toc.files.is_empty());
+    let toc = Walk::new(temp_dir.path(), Some(true))?
+        .filter_expr(Some("nouser or nogroup".to_owned()))
+        .collect()?;
+    assert!(toc.files.is_empty());
+    // Only root can change the owner of a file to an unknown user
+    if uid == 0 {
+        std::os::unix::fs::chown(temp_dir.path().join("public.txt"), Some(unknown_uid), None)?;
+        let toc = Walk::new(temp_dir.path(), Some(true))?
+            .nouser(true)
+            .collect()?;
+        assert_eq!(vec!["public.txt"], toc.files);
+        let toc = Walk::new(temp_dir.path(), Some(true))?
+            .filter_expr(Some("nouser".to_owned()))
+            .collect()?;
+        assert_eq!(vec!["public.txt"], toc.files);
+    }
+    common::cleanup(temp_dir)
+}
+
+#[test]
+fn test_walk_gitignore() -> Result<(), Error> {
+    let temp_dir = common::create_temp_git_repo()?;
+    let root = temp_dir.path();
+    let mut toc = Walk::new(root, Some(true))?.gitignore(true).collect()?;
+    toc.files.sort();
+    assert_eq!(vec!["sub"], toc.dirs);
+    assert_eq!(vec!["keep.log", "sub/b.log", "sub/top.txt"], toc.files);
+    // The ignore files of the parent directories are respected too
+    toc = Walk::new(root.join("sub"), Some(true))?
+        .gitignore(true)
+        .collect()?;
+    toc.files.sort();
+    assert_eq!(vec!["b.log", "top.txt"], toc.files);
+    // .git is never returned, also if hidden entries are returned
+    toc = Walk::new(root, Some(true))?
+        .gitignore(true)
+        .skip_hidden(false)
+        .collect()?;
+    toc.dirs.sort();
+    toc.files.sort();
+    assert_eq!(vec!["sub"], toc.dirs);
+    assert_eq!(
+        vec![
+            ".gitignore",
+            "keep.log",
+            "sub/.gitignore",
+            "sub/.ignore",
+            "sub/b.log",
+            "sub/top.txt"
+        ],
+        toc.files
+    );
+    common::cleanup(temp_dir)
+}
+
+#[test]
+fn test_walk_entry_filter() -> Result<(), Error> {
+    use std::path::PathBuf;
+    use std::sync::{Arc, Mutex};
+
+    use scandir::{EntryFilter, EntryInfo, FilterAction};
+
+    let temp_dir = common::setup();
+    let root = temp_dir.path().to_path_buf();
+    std::fs::create_dir_all(root.join("proj1").join("src"))?;
+    std::fs::create_dir_all(root.join("proj2"))?;
+    std::fs::write(root.join("proj1").join("Cargo.toml"), b"a")?;
+    std::fs::write(root.join("proj1").join("src").join("a.rs"), b"a")?;
+    std::fs::write(root.join("proj2").join("b.rs"), b"a")?;
+    std::fs::write(root.join("x.dat"), b"a")?;
+    std::fs::write(root.join("x.dat.meta"), b"a")?;
+    // Projects without manifest are pruned, the project directories themselves are skipped
+    let filter_root: PathBuf = root.clone();
+    let entry_filter: Arc<dyn EntryFilter> = Arc::new(move |entry: &EntryInfo<'_>| {
+        if entry.file_type.is_dir() && entry.depth == 1 {
+            return match filter_root
+                .join(entry.relative_path)
+                .join("Cargo.toml")
+                .exists()
+            {
+                true => FilterAction::Skip,
+                false => FilterAction::Prune,
+            };
+        }
+        match entry.name.to_string_lossy().ends_with(".meta") {
+            true => FilterAction::Skip,
+            false => FilterAction::Keep,
+        }
+    });
+    let mut toc = Walk::new(&root, Some(true))?
+        .entry_filter(Some(entry_filter.clone()))
+        .collect()?;
+    toc.files.sort();
+    assert_eq!(vec!["proj1/src"], toc.dirs);
+    assert_eq!(
+        vec!["proj1/Cargo.toml", "proj1/src/a.rs", "x.dat"],
+        toc.files
+    );
+    let statistics = scandir::Count::new(&root)?
+        .entry_filter(Some(entry_filter))
+        .collect()?;
+    assert_eq!(3, statistics.files);
+    // The filter is called once per entry, also for directories
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let filter_calls = calls.clone();
+    let entry_filter: Arc<dyn EntryFilter> = Arc::new(move |entry: &EntryInfo<'_>| {
+        let mut calls = filter_calls.lock().unwrap();
+        calls.push(entry.relative_path.to_path_buf());
+        match calls.len() % 2 {
+            0 => FilterAction::Skip,
+            _ => FilterAction::Keep,
+        }
+    });
+    let toc = Walk::new(&root, Some(true))?
+        .entry_filter(Some(entry_filter))
+        .collect()?;
+    let mut calls = calls.lock().unwrap().clone();
+    calls.sort();
+    let count = calls.len();
+    calls.dedup();
+    assert_eq!(count, calls.len());
+    assert_eq!(8, count);
+    assert_eq!(4, toc.dirs.len() + toc.files.len());
+    common::cleanup(temp_dir)
+}
+
+#[cfg(unix)]
+#[test]
+fn test_walk_filter_expr() -> Result<(), Error> {
+    use scandir::{ErrorPhase, ScanError};
+
+    let temp_dir = common::setup();
+    let root = temp_dir.path();
+    std::fs::create_dir_all(root.join("logs"))?;
+    std::fs::write(root.join("logs").join("big.log"), vec![0u8; 2048])?;
+    std::fs::write(root.join("logs").join("small.log"), b"a")?;
+    std::fs::write(root.join("data.txt"), vec![0u8; 2048])?;
+    std::os::unix::fs::symlink(root.join("missing"), root.join("broken"))?;
+    std::os::unix::fs::symlink(root.join("data.txt"), root.join("valid"))?;
+    let mut toc = Walk::new(root, Some(true))?
+        .filter_expr(Some(
+            r#"(name "*.log" and size > 1k) or (type l and not exists-target)"#.to_owned(),
+        ))
+        .collect()?;
+    toc.files.sort();
+    assert!(toc.dirs.is_empty());
+    assert_eq!(vec!["logs/big.log"], toc.files);
+    assert_eq!(vec!["broken"], toc.symlinks);
+    // Directories are descended into even if they don't match
+    toc = Walk::new(root, Some(true))?
+        .filter_expr(Some("-path 'logs/*' -size -1k".to_owned()))
+        .collect()?;
+    assert_eq!(vec!["logs/small.log"], toc.files);
+    let e = Walk::new(root, Some(true))?
+        .filter_expr(Some("name '*.log' and (size >".to_owned()))
+        .collect()
+        .unwrap_err();
+    assert_eq!(std::io::ErrorKind::InvalidInput, e.kind());
+    let scan_error = e.get_ref().unwrap().downcast_ref::<ScanError>().unwrap();
+    assert_eq!(ErrorPhase::FilterCompile, scan_error.phase);
+    assert!(scan_error.message.starts_with("filter_expr: "));
+    assert!(
+        Walk::new(root, Some(true))?
+            .filter_expr(Some("size >".to_owned()))
+            .iter()
+            .is_err()
+    );
+    common::cleanup(temp_dir)
+}
+
+#[test]
+fn test_walk_file_match_path() -> Result<(), Error> {
+    let temp_dir = common::setup();
+    let root = temp_dir.path();
+    std::fs::create_dir_all(root.join("src").join("sub"))?;
+    std::fs::create_dir_all(root.join("foo"))?;
+    std::fs::write(root.join("a.rs"), b"a")?;
+    std::fs::write(root.join("src").join("b.rs"), b"a")?;
+    std::fs::write(root.join("src").join("sub").join("c.rs"), b"a")?;
+    std::fs::write(root.join("src").join("foo"), b"a")?;
+    std::fs::write(root.join("foo").join("d.txt"), b"a")?;
+    // File names don't contain separators
+    let toc = Walk::new(root, Some(true))?
+        .file_include(Some(vec!["src/**/*.rs".to_owned()]))
+        .collect()?;
+    assert!(toc.files.is_empty());
+    let mut toc = Walk::new(root, Some(true))?
+        .file_include(Some(vec!["src/**/*.rs".to_owned()]))
+        .file_match_path(true)
+        .collect()?;
+    toc.files.sort();
+    assert_eq!(vec!["src/b.rs", "src/sub/c.rs"], toc.files);
+    // "foo/**" matches the contents of directory foo and "*/foo/**" the file src/foo
+    toc = Walk::new(root, Some(true))?
+        .file_exclude(Some(vec!["foo/**".to_owned()]))
+        .file_match_path(true)
+        .collect()?;
+    toc.files.sort();
+    assert_eq!(
+        vec!["a.rs", "src/b.rs", "src/foo", "src/sub/c.rs"],
+        toc.files
+    );
+    toc = Walk::new(root, Some(true))?
+        .file_exclude(Some(vec!["*/foo/**".to_owned()]))
+        .file_match_path(true)
+        .collect()?;
+    toc.files.sort();
+    assert_eq!(
+        vec!["a.rs", "foo/d.txt", "src/b.rs", "src/sub/c.rs"],
+        toc.files
+    );
+    toc = Walk::new(root, Some(true))?
+        .file_exclude_regex(Some(vec![r"^src/.*\.rs$".to_owned()]))
+        .file_match_path(true)
+        .collect()?;
+    toc.files.sort();
+    assert_eq!(vec!["a.rs", "foo/d.txt", "src/foo"], toc.files);
+    common::cleanup(temp_dir)
+}
+
+#[test]
+fn test_walk_large_pattern_set() -> Result<(), Error> {
+    let temp_dir = common::setup();
+    let root = temp_dir.path();
+    for dir in ["vendor", "build", "src"] {
+        std::fs::create_dir_all(root.join(dir))?;
+        for name in [
+            "a.rs",
+            "b.O",
+            "c.tmp",
+            "Makefile",
+            "d.json",
+            "x1.txt",
+            "x{1,2}.txt",
+        ] {
+            std::fs::write(root.join(dir).join(name), b"a")?;
+        }
+    }
+    // Literals, extensions, prefixes and wildcards, mostly not matching
+    let mut dir_exclude = (0..500).map(|i| format!("dir_{i}")).collect::<Vec<_>>();
+    dir_exclude.extend(["vendor".to_owned(), "build/**".to_owned()]);
+    let mut file_exclude = (0..500)
+        .map(|i| format!("*.ext{i}"))
+        .chain((0..500).map(|i| format!("file_{i}_*")))
+        .collect::<Vec<_>>();
+    file_exclude.extend([
+        "*.o".to_owned(),
+        "*.tmp".to_owned(),
+        "Makefile".to_owned(),
+        // Braces are literal characters like in `glob_sl`
+        "x{1,2}.txt".to_owned(),
+    ]);
+    let mut toc = Walk::new(root, Some(true))?
+        .dir_exclude(Some(dir_exclude.clone()))
+        .file_exclude(Some(file_exclude.clone()))
+        .case_sensitive(true)
+        .collect()?;
+    toc.files.sort();
+    assert_eq!(vec!["src"], toc.dirs);
+    assert_eq!(
+        vec!["src/a.rs", "src/b.O", "src/d.json", "src/x1.txt"],
+        toc.files
+    );
+    toc = Walk::new(root, Some(true))?
+        .dir_exclude(Some(dir_exclude))
+        .file_exclude(Some(file_exclude))
+        .case_sensitive(false)
+        .collect()?;
+    toc.files.sort();
+    assert_eq!(vec!["src/a.rs", "src/d.json", "src/x1.txt"], toc.files);
+    // Filter expressions use the same pattern syntax
+    toc = Walk::new(root, Some(true))?
+        .dir_include(Some(vec!["src".to_owned()]))
+        .filter_expr(Some("name 'x{1,2}.txt' or ipath 'SRC/*.o'".to_owned()))
+        .collect()?;
+    toc.files.sort();
+    assert_eq!(vec!["src/b.O", "src/x{1,2}.txt"], toc.files);
+    common::cleanup(temp_dir)
+}
+
+#[test]
+fn test_walk_sort_by() -> Result<(), Error> {
+    use scandir::{SortBy, SortKey};
+
+    let temp_dir = common::setup();
+    let root = temp_dir.path();
+    std::fs::create_dir_all(root.join("sub"))?;
+    std::fs::write(root.join("v1.10.txt"), b"abc")?;
+    std::fs::write(root.join("v1.9.txt"), b"abcdefghij")?;
+    std::fs::write(root.join("v1.2.txt"), b"a")?;
+    std::fs::write(root.join("sub").join("big.bin"), [0u8; 100])?;
+    let toc = Walk::new(root, Some(true))?
+        .max_depth(1)
+        .sort_by(Some(SortBy::new(SortKey::Natural, false)))
+        .collect()?;
+    assert_eq!(vec!["v1.2.txt", "v1.9.txt", "v1.10.txt"], toc.files);
+    let toc = Walk::new(root, Some(true))?
+        .max_depth(1)
+        .sort_by(Some(SortBy::new(SortKey::Size, false)))
+        .collect()?;
+    assert_eq!(vec!["v1.2.txt", "v1.10.txt", "v1.9.txt"], toc.files);
+    // Global sort of the collected TOC
+    let mut toc = Walk::new(root, Some(true))?.collect()?;
+    toc.sort(SortBy::new(SortKey::Size, true), root);
+    assert_eq!(
+        vec!["sub/big.bin", "v1.9.txt", "v1.10.txt", "v1.2.txt"],
+        toc.files
+    );
+    // The metadata read during the walk is used instead of reading it again
+    let mut toc = Walk::new(root, Some(true))?
+        .sort_by(Some(SortBy::new(SortKey::Size, false)))
+        .collect()?;
+    assert_eq!(5, toc.sort_metadata.len());
+    std::fs::write(root.join("v1.2.txt"), [0u8; 200])?;
+    toc.sort(SortBy::new(SortKey::Size, true), root);
+    assert_eq!(
+        vec!["sub/big.bin", "v1.9.txt", "v1.10.txt", "v1.2.txt"],
+        toc.files
+    );
+    common::cleanup(temp_dir)
+}
+
+#[test]
+fn test_walk_glob_syntax() -> Result<(), Error> {
+    use scandir::{ErrorPhase, ScanError};
+
+    let temp_dir = common::setup();
+    let root = temp_dir.path();
+    std::fs::create_dir_all(root.join("dir"))?;
+    std::fs::create_dir_all(root.join("other"))?;
+    for name in ["x1.txt", "x^.txt", "xa.txt", "x{a,b}.txt"] {
+        std::fs::write(root.join(name), b"a")?;
+    }
+    std::fs::write(root.join("dir").join("a.txt"), b"a")?;
+    std::fs::write(root.join("other").join("dir"), b"a")?;
+    let include = |pattern: &str| -> Result<Vec<String>, Error> {
+        let mut toc = Walk::new(root, Some(true))?
+            .file_include(Some(vec![pattern.to_owned()]))
+            .collect()?;
+        toc.files.sort();
+        Ok(toc.files)
+    };
+    assert_eq!(vec!["x^.txt", "xa.txt"], include("x[!1].txt")?);
+    // `^` isn't a negation like `!`
+    assert_eq!(vec!["x1.txt", "x^.txt"], include("x[^1].txt")?);
+    assert_eq!(vec!["x^.txt"], include("x[^].txt")?);
+    // Braces are literal characters
+    assert_eq!(vec!["x{a,b}.txt"], include("x{a,b}.txt")?);
+    // "dir/**" matches a file named dir too
+    assert_eq!(vec!["other/dir"], include("dir/**")?);
+    for pattern in ["a***", "a**/b", "a/**b", "x[1"] {
+        let e = Walk::new(root, Some(true))?
+            .file_include(Some(vec![pattern.to_owned()]))
+            .collect()
+            .unwrap_err();
+        assert_eq!(std::io::ErrorKind::InvalidInput, e.kind());
+        let scan_error = e.get_ref().unwrap().downcast_ref::<ScanError>().unwrap();
+        assert_eq!(ErrorPhase::FilterCompile, scan_error.phase);
+    }
+    common::cleanup(temp_dir)
+}